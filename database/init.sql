@@ -7,11 +7,22 @@
 \ir initial/views.sql
 \ir initial/attachment.sql
 \ir initial/contact.sql
+\ir initial/access_token.sql
+\ir initial/oidc.sql
+\ir initial/webauthn.sql
+\ir initial/admin.sql
+\ir initial/block.sql
+\ir initial/ws_bus.sql
+\ir initial/ws_ticket.sql
+\ir initial/push_subscription.sql
+\ir initial/email_digest.sql
+\ir initial/conversation_setting.sql
+\ir initial/conversation_summary.sql
 
 
 -- Mock Users
-INSERT INTO public.user (username, email, password)
-    VALUES ('Bryn Ghiffar', 'bryn.ghiffar@gmail.com', crypt('bryn.ghiffar@gmail.com', gen_salt('bf', 5)));
+INSERT INTO public.user (username, email, password, role)
+    VALUES ('Bryn Ghiffar', 'bryn.ghiffar@gmail.com', crypt('bryn.ghiffar@gmail.com', gen_salt('bf', 5)), 'admin');
 INSERT INTO public.user (username, email, password)
     VALUES ('Jackmann', 'jack@mail.com', crypt('jack@mail.com', gen_salt('bf', 5)));
 INSERT INTO public.user (username, email, password)
@@ -0,0 +1,24 @@
+CREATE TABLE public.access_token (
+    id integer PRIMARY KEY,
+    user_id integer NOT NULL,
+    name text NOT NULL CONSTRAINT access_token_name_chk CHECK (char_length(name) >= 1),
+    token_hash text NOT NULL,
+    scopes text[] NOT NULL,
+    created_at timestamp(3) without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    expires_at timestamp(3) without time zone,
+    last_used_at timestamp(3) without time zone,
+    revoked boolean DEFAULT false NOT NULL,
+    CONSTRAINT fk_access_token_user_id FOREIGN KEY (user_id) REFERENCES public.user(id)
+);
+
+CREATE SEQUENCE public.access_token_id_seq
+    AS integer
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1;
+
+ALTER TABLE ONLY public.access_token ALTER COLUMN id SET DEFAULT nextval('public.access_token_id_seq'::regclass);
+CREATE UNIQUE INDEX access_token_token_hash_key ON public.access_token USING btree (token_hash);
+CREATE INDEX access_token_user_id_idx ON public.access_token USING btree (user_id);
@@ -0,0 +1,21 @@
+CREATE TABLE public.admin_audit_log (
+    id integer PRIMARY KEY,
+    admin_id integer NOT NULL,
+    action text NOT NULL,
+    target_user_id integer,
+    details text,
+    created_at timestamp(3) without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    CONSTRAINT fk_admin_audit_log_admin_id FOREIGN KEY (admin_id) REFERENCES public.user(id),
+    CONSTRAINT fk_admin_audit_log_target_user_id FOREIGN KEY (target_user_id) REFERENCES public.user(id)
+);
+
+CREATE SEQUENCE public.admin_audit_log_id_seq
+    AS integer
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1;
+
+ALTER TABLE ONLY public.admin_audit_log ALTER COLUMN id SET DEFAULT nextval('public.admin_audit_log_id_seq'::regclass);
+CREATE INDEX admin_audit_log_created_at_idx ON public.admin_audit_log USING btree (created_at);
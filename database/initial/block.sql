@@ -0,0 +1,12 @@
+CREATE TABLE public.user_block (
+    blocker_id integer NOT NULL,
+    blocked_id integer NOT NULL,
+    collapse_group_messages boolean DEFAULT false NOT NULL,
+    created_at timestamp(3) without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    PRIMARY KEY (blocker_id, blocked_id),
+    CONSTRAINT fk_user_block_blocker_id FOREIGN KEY (blocker_id) REFERENCES public.user(id),
+    CONSTRAINT fk_user_block_blocked_id FOREIGN KEY (blocked_id) REFERENCES public.user(id),
+    CONSTRAINT user_block_not_self CHECK (blocker_id <> blocked_id)
+);
+
+CREATE INDEX user_block_blocked_id_idx ON public.user_block USING btree (blocked_id);
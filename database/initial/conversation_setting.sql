@@ -0,0 +1,14 @@
+-- Per-user mute, archive and pin state of direct (by contact id) and group conversations
+CREATE TABLE public.conversation_setting (
+    user_id integer NOT NULL,
+    conversation_type text NOT NULL CONSTRAINT conversation_setting_type_chk CHECK (conversation_type IN ('direct', 'group')),
+    conversation_id integer NOT NULL,
+    muted boolean DEFAULT false NOT NULL,
+    muted_until timestamp(3) without time zone,
+    archive text CONSTRAINT conversation_setting_archive_chk CHECK (archive IN ('until_activity', 'permanent')),
+    archived_at timestamp(3) without time zone,
+    pin_order integer,
+    updated_at timestamp(3) without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    CONSTRAINT conversation_setting_pkey PRIMARY KEY (user_id, conversation_type, conversation_id),
+    CONSTRAINT fk_conversation_setting_user_id FOREIGN KEY (user_id) REFERENCES public.user(id)
+);
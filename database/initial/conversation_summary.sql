@@ -0,0 +1,42 @@
+-- One row per participant of every direct (by contact id) and group conversation, kept up to date by
+-- the statements writing messages, reads and group members so the inbox needs no aggregation
+CREATE TABLE public.conversation_summary (
+    user_id integer NOT NULL,
+    conversation_type text NOT NULL CONSTRAINT conversation_summary_type_chk CHECK (conversation_type IN ('direct', 'group')),
+    conversation_id integer NOT NULL,
+    last_message_id integer,
+    last_sender_id integer,
+    last_message_preview text,
+    last_message_deleted boolean DEFAULT false NOT NULL,
+    last_activity_at timestamp(3) without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    unread_count integer DEFAULT 0 NOT NULL,
+    CONSTRAINT conversation_summary_pkey PRIMARY KEY (user_id, conversation_type, conversation_id),
+    CONSTRAINT fk_conversation_summary_user_id FOREIGN KEY (user_id) REFERENCES public.user(id)
+);
+
+CREATE INDEX conversation_summary_activity_idx ON public.conversation_summary USING btree (user_id, last_activity_at DESC, conversation_type DESC, conversation_id DESC);
+CREATE INDEX conversation_summary_last_message_idx ON public.conversation_summary USING btree (conversation_type, last_message_id);
+
+-- Backfill from the messages already stored
+INSERT INTO public.conversation_summary
+    (user_id, conversation_type, conversation_id, last_message_id, last_sender_id, last_message_preview, last_message_deleted, last_activity_at, unread_count)
+SELECT DISTINCT ON (p.user_id, p.contact_id)
+    p.user_id, 'direct', p.contact_id, m.id, m.sender_id, left(m.content, 200), m.deleted, m.sent_at,
+    (SELECT count(*) FROM public.message u WHERE u.receiver_id = p.user_id AND u.sender_id = p.contact_id AND NOT u.read)
+FROM public.message m
+    CROSS JOIN LATERAL (VALUES (m.sender_id, m.receiver_id), (m.receiver_id, m.sender_id)) AS p (user_id, contact_id)
+ORDER BY p.user_id, p.contact_id, m.sent_at DESC, m.id DESC;
+
+INSERT INTO public.conversation_summary
+    (user_id, conversation_type, conversation_id, last_message_id, last_sender_id, last_message_preview, last_message_deleted, last_activity_at, unread_count)
+SELECT
+    gmem.user_id, 'group', gmem.group_id, lm.id, lm.sender_id, left(lm.content, 200), coalesce(lm.deleted, false),
+    coalesce(lm.sent_at, CURRENT_TIMESTAMP),
+    (SELECT count(*) FROM public.group_message gm
+        WHERE gm.group_id = gmem.group_id
+            AND gm.sender_id <> gmem.user_id
+            AND NOT EXISTS (SELECT 1 FROM public.group_message_read r WHERE r.message_id = gm.id AND r.reader_id = gmem.user_id))
+FROM public.group_member gmem
+    LEFT JOIN LATERAL (
+        SELECT * FROM public.group_message WHERE group_id = gmem.group_id ORDER BY sent_at DESC, id DESC LIMIT 1
+    ) lm ON TRUE;
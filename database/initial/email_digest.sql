@@ -0,0 +1,10 @@
+-- How often each user is emailed a digest of their unread conversations
+CREATE TABLE public.email_digest (
+    user_id integer PRIMARY KEY,
+    frequency text DEFAULT 'off' NOT NULL CONSTRAINT email_digest_frequency_chk CHECK (frequency IN ('off', 'hourly', 'daily')),
+    unsubscribe_token text NOT NULL,
+    last_sent_at timestamp(3) without time zone,
+    CONSTRAINT fk_email_digest_user_id FOREIGN KEY (user_id) REFERENCES public.user(id)
+);
+
+CREATE UNIQUE INDEX email_digest_unsubscribe_token_key ON public.email_digest USING btree (unsubscribe_token);
@@ -0,0 +1,15 @@
+CREATE TABLE public.oidc_login_attempt (
+    state text PRIMARY KEY,
+    code_verifier text NOT NULL,
+    nonce text NOT NULL,
+    created_at timestamp(3) without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE TABLE public.user_oidc_identity (
+    issuer text NOT NULL,
+    subject text NOT NULL,
+    user_id integer NOT NULL,
+    created_at timestamp(3) without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    PRIMARY KEY (issuer, subject),
+    CONSTRAINT fk_user_oidc_identity_user_id FOREIGN KEY (user_id) REFERENCES public.user(id)
+);
@@ -0,0 +1,23 @@
+-- Web Push subscriptions of the browsers and devices of each user
+CREATE TABLE public.push_subscription (
+    id integer PRIMARY KEY,
+    user_id integer NOT NULL,
+    endpoint text NOT NULL,
+    p256dh text NOT NULL,
+    auth text NOT NULL,
+    user_agent text,
+    created_at timestamp(3) without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    CONSTRAINT fk_push_subscription_user_id FOREIGN KEY (user_id) REFERENCES public.user(id)
+);
+
+CREATE SEQUENCE public.push_subscription_id_seq
+    AS integer
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1;
+
+ALTER TABLE ONLY public.push_subscription ALTER COLUMN id SET DEFAULT nextval('public.push_subscription_id_seq'::regclass);
+CREATE UNIQUE INDEX push_subscription_endpoint_key ON public.push_subscription USING btree (endpoint);
+CREATE INDEX push_subscription_user_id_idx ON public.push_subscription USING btree (user_id);
@@ -1,4 +1,6 @@
 -- Views
+DROP VIEW IF EXISTS public.conversation_partner;
+DROP VIEW IF EXISTS public.user_profile;
 DROP VIEW IF EXISTS public.unread_message_content;
 DROP VIEW IF EXISTS public.unread_message_count;
 DROP VIEW IF EXISTS public.last_message;
@@ -7,6 +9,19 @@ DROP VIEW IF EXISTS public.last_message_group;
 DROP VIEW IF EXISTS public.username_group_message;
 
 -- Tables
+DROP TABLE IF EXISTS public.conversation_summary;
+DROP TABLE IF EXISTS public.conversation_setting;
+DROP TABLE IF EXISTS public.email_digest;
+DROP TABLE IF EXISTS public.push_subscription;
+DROP TABLE IF EXISTS public.ws_ticket;
+DROP TABLE IF EXISTS public.ws_bus_payload;
+DROP TABLE IF EXISTS public.user_block;
+DROP TABLE IF EXISTS public.admin_audit_log;
+DROP TABLE IF EXISTS public.webauthn_ceremony;
+DROP TABLE IF EXISTS public.webauthn_credential;
+DROP TABLE IF EXISTS public.user_oidc_identity;
+DROP TABLE IF EXISTS public.oidc_login_attempt;
+DROP TABLE IF EXISTS public.access_token;
 DROP TABLE IF EXISTS PUBLIC.ATTACHMENT_MESSAGE;
 DROP TABLE IF EXISTS PUBLIC.ATTACHMENT;
 DROP TABLE IF EXISTS public.group_message_read;
@@ -20,6 +35,12 @@ DROP TABLE IF EXISTS public.session;
 DROP TABLE IF EXISTS public.user;
 
 -- Sequences
+DROP SEQUENCE IF EXISTS public.push_subscription_id_seq;
+DROP SEQUENCE IF EXISTS public.ws_bus_payload_id_seq;
+DROP SEQUENCE IF EXISTS public.ws_node_id_seq;
+DROP SEQUENCE IF EXISTS public.admin_audit_log_id_seq;
+DROP SEQUENCE IF EXISTS public.webauthn_credential_id_seq;
+DROP SEQUENCE IF EXISTS public.access_token_id_seq;
 DROP SEQUENCE IF EXISTS PUBLIC.ATTACHMENT_ID_SEQ;
 DROP SEQUENCE IF EXISTS public.user_id_seq;
 DROP SEQUENCE IF EXISTS public.message_id_seq;
@@ -29,4 +50,5 @@ DROP SEQUENCE IF EXISTS public.group_avatar_id_seq;
 DROP SEQUENCE IF EXISTS public.group_message_id_seq;
 
 
-CREATE EXTENSION IF NOT EXISTS pgcrypto;
\ No newline at end of file
+CREATE EXTENSION IF NOT EXISTS pgcrypto;
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
\ No newline at end of file
@@ -3,7 +3,19 @@ CREATE TABLE public.user (
     username text NOT NULL,
     email text NOT NULL,
     password text NOT NULL,
-    role text DEFAULT 'guest'::text NOT NULL
+    role text DEFAULT 'user'::text NOT NULL CONSTRAINT user_role_chk CHECK (role IN ('admin', 'user', 'guest')),
+    suspended_at timestamp(3) without time zone,
+    password_reset_required boolean DEFAULT false NOT NULL,
+    discoverable boolean DEFAULT true NOT NULL,
+    display_name text,
+    bio text,
+    pronouns text,
+    status_text text,
+    status_emoji text,
+    status_expires_at timestamp(3) without time zone,
+    last_seen_at timestamp(3) without time zone,
+    presence_visibility text DEFAULT 'everyone'::text NOT NULL CONSTRAINT user_presence_visibility_chk CHECK (presence_visibility IN ('everyone', 'contacts', 'nobody')),
+    presence_state text DEFAULT 'online'::text NOT NULL CONSTRAINT user_presence_state_chk CHECK (presence_state IN ('online', 'away', 'busy', 'invisible'))
 );
 
 CREATE SEQUENCE public.user_id_seq
@@ -16,4 +28,5 @@ CREATE SEQUENCE public.user_id_seq
 
 ALTER TABLE ONLY public.user ALTER COLUMN id SET DEFAULT nextval('public.user_id_seq'::regclass);
 CREATE UNIQUE INDEX user_email_key ON public.user USING btree (email);
-CREATE UNIQUE INDEX user_username_key ON public.user USING btree (username);
\ No newline at end of file
+CREATE UNIQUE INDEX user_username_key ON public.user USING btree (username);
+CREATE INDEX user_username_trgm_idx ON public.user USING gin (lower(username) gin_trgm_ops);
\ No newline at end of file
@@ -32,4 +32,24 @@ CREATE VIEW unread_message_content as
     from message
     join last_message on message.id = last_message.last_msg_id
     left join unread_message_count on message.sender_id = unread_message_count.sender_id and message.receiver_id = unread_message_count.receiver_id
-    ;
\ No newline at end of file
+    ;
+
+CREATE VIEW user_profile as
+    select
+        id as user_id,
+        display_name,
+        bio,
+        pronouns,
+        (case when status_expires_at is null or status_expires_at > current_timestamp then status_text end) as status_text,
+        (case when status_expires_at is null or status_expires_at > current_timestamp then status_emoji end) as status_emoji,
+        (case when status_expires_at > current_timestamp then status_expires_at end) as status_expires_at
+    from public.user;
+
+CREATE VIEW conversation_partner as
+    select sender_id as user_id, receiver_id as partner_id from message
+    union
+    select receiver_id as user_id, sender_id as partner_id from message
+    union
+    select a.user_id, b.user_id as partner_id
+    from group_member a
+    join group_member b on a.group_id = b.group_id and a.user_id <> b.user_id;
@@ -0,0 +1,32 @@
+CREATE TABLE public.webauthn_credential (
+    id integer PRIMARY KEY,
+    user_id integer NOT NULL,
+    name text NOT NULL CONSTRAINT webauthn_credential_name_chk CHECK (char_length(name) >= 1),
+    credential_id text NOT NULL,
+    passkey text NOT NULL,
+    sign_count bigint DEFAULT 0 NOT NULL,
+    created_at timestamp(3) without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    last_used_at timestamp(3) without time zone,
+    CONSTRAINT fk_webauthn_credential_user_id FOREIGN KEY (user_id) REFERENCES public.user(id)
+);
+
+CREATE SEQUENCE public.webauthn_credential_id_seq
+    AS integer
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1;
+
+ALTER TABLE ONLY public.webauthn_credential ALTER COLUMN id SET DEFAULT nextval('public.webauthn_credential_id_seq'::regclass);
+CREATE UNIQUE INDEX webauthn_credential_credential_id_key ON public.webauthn_credential USING btree (credential_id);
+CREATE INDEX webauthn_credential_user_id_idx ON public.webauthn_credential USING btree (user_id);
+
+CREATE TABLE public.webauthn_ceremony (
+    id text PRIMARY KEY,
+    user_id integer NOT NULL,
+    kind text NOT NULL,
+    state text NOT NULL,
+    created_at timestamp(3) without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    CONSTRAINT fk_webauthn_ceremony_user_id FOREIGN KEY (user_id) REFERENCES public.user(id)
+);
@@ -0,0 +1,29 @@
+-- Websocket fan-out events too large for a NOTIFY payload, kept for a few minutes
+CREATE TABLE public.ws_bus_payload (
+    id bigint PRIMARY KEY,
+    payload text NOT NULL,
+    created_at timestamp(3) without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE SEQUENCE public.ws_bus_payload_id_seq
+    AS bigint
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1;
+
+ALTER TABLE ONLY public.ws_bus_payload ALTER COLUMN id SET DEFAULT nextval('public.ws_bus_payload_id_seq'::regclass);
+
+CREATE INDEX ws_bus_payload_created_at_idx ON public.ws_bus_payload USING btree (created_at);
+
+-- Hands every starting node its own id; ids stay below 2^20 so session ids built
+-- from them remain exact in JavaScript numbers
+CREATE SEQUENCE public.ws_node_id_seq
+    AS integer
+    START WITH 1
+    INCREMENT BY 1
+    MINVALUE 1
+    MAXVALUE 1048575
+    CYCLE
+    CACHE 1;
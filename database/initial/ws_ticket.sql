@@ -0,0 +1,11 @@
+-- Short-lived single-use tickets for opening a websocket without a token in the URL
+CREATE TABLE public.ws_ticket (
+    ticket_hash text PRIMARY KEY,
+    user_id integer NOT NULL,
+    token_expires_at timestamp(3) without time zone NOT NULL,
+    expires_at timestamp(3) without time zone NOT NULL,
+    created_at timestamp(3) without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    CONSTRAINT fk_ws_ticket_user_id FOREIGN KEY (user_id) REFERENCES public.user(id)
+);
+
+CREATE INDEX ws_ticket_expires_at_idx ON public.ws_ticket USING btree (expires_at);
@@ -0,0 +1,223 @@
+//! Measures how long direct messages take to reach their receiver while many users
+//! send at once.
+//!
+//! Start the server, then run
+//! `cargo run --release --example ws_bench -- <base-url> <pairs> <messages>`,
+//! e.g. `cargo run --release --example ws_bench -- http://localhost:8080 100 20`.
+//! The benchmark registers `bench-<n>@bench.local` users as needed; every pair of
+//! users sends `<messages>` messages to each other over the websocket.
+
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use futures_util::SinkExt;
+use futures_util::StreamExt;
+use serde_json::json;
+use serde_json::Value;
+use tokio::sync::mpsc;
+use tokio::sync::Barrier;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message;
+
+struct BenchUser {
+    user_id: i32,
+    token: String,
+}
+
+async fn bench_user(
+    client: &reqwest::Client,
+    base_url: &str,
+    n: usize,
+) -> anyhow::Result<BenchUser> {
+    let email = format!("bench-{n}@bench.local");
+    let form = json!({ "email": email, "password": email });
+    // the user may exist from an earlier run
+    client
+        .post(format!("{base_url}/api/auth/register"))
+        .json(&form)
+        .send()
+        .await?;
+    let login = client
+        .post(format!("{base_url}/api/auth/login"))
+        .json(&form)
+        .send()
+        .await?
+        .json::<Value>()
+        .await?;
+    let token = login["payload"]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("login failed: {login}"))?
+        .to_string();
+    let details = client
+        .get(format!("{base_url}/api/user/details"))
+        .bearer_auth(&token)
+        .send()
+        .await?
+        .json::<Value>()
+        .await?;
+    let user_id = details["payload"]["user_id"]
+        .as_i64()
+        .ok_or_else(|| anyhow::anyhow!("cannot read user details: {details}"))?;
+    Ok(BenchUser {
+        user_id: user_id as i32,
+        token,
+    })
+}
+
+/// Sends `messages` messages to `partner_id` once every user is connected, and
+/// reports the latency of each message received from the partner.
+async fn run_user(
+    ws_url: String,
+    user: BenchUser,
+    partner_id: i32,
+    messages: usize,
+    epoch: Instant,
+    connected: Arc<Barrier>,
+    latency_tx: mpsc::UnboundedSender<Duration>,
+) -> anyhow::Result<()> {
+    let mut request = format!("{ws_url}/api/ws").into_client_request()?;
+    request.headers_mut().insert(
+        "Sec-WebSocket-Protocol",
+        format!("chatbyte, bearer.{}", user.token).parse()?,
+    );
+    let (stream, _) = tokio_tungstenite::connect_async(request).await?;
+    let (mut write, mut read) = stream.split();
+    connected.wait().await;
+    let sender = tokio::spawn(async move {
+        for _ in 0..messages {
+            let sent_at = epoch.elapsed().as_micros();
+            let request = json!({
+                "type": "SEND_MESSAGE",
+                "receiverUid": partner_id,
+                "message": sent_at.to_string(),
+                "attachments": [],
+            });
+            write.send(Message::Text(request.to_string())).await?;
+        }
+        anyhow::Ok(write)
+    });
+    let mut received = 0;
+    while received < messages {
+        let Some(message) = read.next().await else {
+            break;
+        };
+        let Message::Text(text) = message? else {
+            continue;
+        };
+        let notification = serde_json::from_str::<Value>(&text)?;
+        if notification["type"] != "MESSAGE_NOTIFICATION"
+            || notification["senderUid"] != partner_id
+            || notification["receiverUid"] != user.user_id
+        {
+            continue;
+        }
+        let sent_at = notification["content"]
+            .as_str()
+            .and_then(|c| c.parse::<u64>().ok())
+            .unwrap_or_default();
+        let latency = epoch.elapsed() - Duration::from_micros(sent_at);
+        latency_tx.send(latency)?;
+        received += 1;
+    }
+    let mut write = sender.await??;
+    write.close().await?;
+    Ok(())
+}
+
+fn percentile(
+    sorted: &[Duration],
+    p: f64,
+) -> Duration {
+    let index = ((sorted.len() as f64 - 1.0) * p).round() as usize;
+    sorted[index]
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let base_url = args
+        .next()
+        .unwrap_or_else(|| "http://localhost:8080".to_string());
+    let pairs = args
+        .next()
+        .map(|v| {
+            v.parse::<usize>()
+                .expect("pairs cannot be parsed into usize")
+        })
+        .unwrap_or(50);
+    let messages = args
+        .next()
+        .map(|v| {
+            v.parse::<usize>()
+                .expect("messages cannot be parsed into usize")
+        })
+        .unwrap_or(20);
+    let ws_url = base_url.replacen("http", "ws", 1);
+
+    let client = reqwest::Client::new();
+    let mut users = Vec::new();
+    for n in 0..pairs * 2 {
+        users.push(bench_user(&client, &base_url, n).await?);
+    }
+    println!("{} senders, {messages} messages each", users.len());
+
+    let (latency_tx, mut latency_rx) = mpsc::unbounded_channel();
+    let epoch = Instant::now();
+    let connected = Arc::new(Barrier::new(users.len() + 1));
+    let mut tasks = Vec::new();
+    let mut users = users.into_iter();
+    while let (Some(a), Some(b)) = (users.next(), users.next()) {
+        let (a_id, b_id) = (a.user_id, b.user_id);
+        for (user, partner_id) in [(a, b_id), (b, a_id)] {
+            let task = run_user(
+                ws_url.clone(),
+                user,
+                partner_id,
+                messages,
+                epoch,
+                connected.clone(),
+                latency_tx.clone(),
+            );
+            tasks.push(tokio::spawn(tokio::time::timeout(
+                Duration::from_secs(60),
+                task,
+            )));
+        }
+    }
+    drop(latency_tx);
+    connected.wait().await;
+    let start = Instant::now();
+    for task in tasks {
+        match task.await? {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => println!("sender failed: {e}"),
+            Err(_) => println!("sender timed out"),
+        }
+    }
+    let elapsed = start.elapsed();
+
+    let mut latencies = Vec::new();
+    while let Some(latency) = latency_rx.recv().await {
+        latencies.push(latency);
+    }
+    if latencies.is_empty() {
+        println!("no messages received");
+        return Ok(());
+    }
+    latencies.sort();
+    println!(
+        "received {} of {} messages in {elapsed:.2?} ({:.0} msg/s)",
+        latencies.len(),
+        pairs * 2 * messages,
+        latencies.len() as f64 / elapsed.as_secs_f64()
+    );
+    println!(
+        "latency p50 {:.2?}  p95 {:.2?}  p99 {:.2?}  max {:.2?}",
+        percentile(&latencies, 0.50),
+        percentile(&latencies, 0.95),
+        percentile(&latencies, 0.99),
+        latencies[latencies.len() - 1]
+    );
+    Ok(())
+}
@@ -0,0 +1,101 @@
+# Minimal OpenID Connect provider for testing the OIDC login locally.
+# pip install cryptography
+#
+# OIDC_ISSUER_URL=http://localhost:9400 OIDC_CLIENT_ID=chatbyte \
+# OIDC_REDIRECT_URI=http://localhost:9000/api/auth/oidc/callback cargo run
+#
+# Every authorization request is approved immediately for MOCK_IDP_EMAIL.
+import base64
+import hashlib
+import json
+import os
+import secrets
+import time
+from http.server import BaseHTTPRequestHandler, HTTPServer
+from urllib.parse import parse_qs, urlencode, urlparse
+
+from cryptography.hazmat.primitives.asymmetric.ed25519 import Ed25519PrivateKey
+from cryptography.hazmat.primitives.serialization import Encoding, PublicFormat
+
+PORT = int(os.environ.get("MOCK_IDP_PORT", "9400"))
+ISSUER = f"http://localhost:{PORT}"
+EMAIL = os.environ.get("MOCK_IDP_EMAIL", "jack@mail.com")
+SUBJECT = os.environ.get("MOCK_IDP_SUBJECT", "mock-subject-1")
+KID = "mock-idp"
+
+key = Ed25519PrivateKey.generate()
+codes = {}
+
+
+def b64url(data):
+    return base64.urlsafe_b64encode(data).rstrip(b"=").decode()
+
+
+def sign(claims):
+    header = b64url(json.dumps({"alg": "EdDSA", "typ": "JWT", "kid": KID}).encode())
+    payload = b64url(json.dumps(claims).encode())
+    signature = b64url(key.sign(f"{header}.{payload}".encode()))
+    return f"{header}.{payload}.{signature}"
+
+
+class Handler(BaseHTTPRequestHandler):
+    def send_json(self, status, body):
+        data = json.dumps(body).encode()
+        self.send_response(status)
+        self.send_header("Content-Type", "application/json")
+        self.send_header("Content-Length", str(len(data)))
+        self.end_headers()
+        self.wfile.write(data)
+
+    def do_GET(self):
+        url = urlparse(self.path)
+        if url.path == "/.well-known/openid-configuration":
+            return self.send_json(200, {
+                "issuer": ISSUER,
+                "authorization_endpoint": f"{ISSUER}/authorize",
+                "token_endpoint": f"{ISSUER}/token",
+                "jwks_uri": f"{ISSUER}/jwks",
+            })
+        if url.path == "/jwks":
+            x = b64url(key.public_key().public_bytes(Encoding.Raw, PublicFormat.Raw))
+            return self.send_json(200, {"keys": [
+                {"kty": "OKP", "crv": "Ed25519", "x": x, "kid": KID, "alg": "EdDSA", "use": "sig"}
+            ]})
+        if url.path == "/authorize":
+            query = {k: v[0] for k, v in parse_qs(url.query).items()}
+            code = secrets.token_urlsafe(16)
+            codes[code] = query
+            location = query["redirect_uri"] + "?" + urlencode({"code": code, "state": query["state"]})
+            self.send_response(302)
+            self.send_header("Location", location)
+            self.end_headers()
+            return
+        self.send_json(404, {"error": "not_found"})
+
+    def do_POST(self):
+        length = int(self.headers.get("Content-Length", 0))
+        form = {k: v[0] for k, v in parse_qs(self.rfile.read(length).decode()).items()}
+        request = codes.pop(form.get("code"), None)
+        if request is None:
+            return self.send_json(400, {"error": "invalid_grant"})
+        challenge = b64url(hashlib.sha256(form["code_verifier"].encode()).digest())
+        if challenge != request["code_challenge"]:
+            return self.send_json(400, {"error": "invalid_grant", "error_description": "PKCE"})
+        now = int(time.time())
+        id_token = sign({
+            "iss": ISSUER,
+            "sub": SUBJECT,
+            "aud": request["client_id"],
+            "iat": now,
+            "exp": now + 300,
+            "nonce": request["nonce"],
+            "email": EMAIL,
+            "email_verified": True,
+            "preferred_username": EMAIL.split("@")[0],
+        })
+        self.send_json(200, {"access_token": "mock", "token_type": "Bearer", "id_token": id_token})
+
+
+if __name__ == "__main__":
+    print(f"Mock IdP listening on {ISSUER}")
+    HTTPServer(("0.0.0.0", PORT), Handler).serve_forever()
@@ -0,0 +1,100 @@
+# Minimal Web Push service for testing push notifications locally.
+# pip install cryptography
+#
+# PUSH_ALLOW_HTTP_ENDPOINTS=true cargo run
+# python pyclient/mock_push.py
+#
+# Prints a subscription to register with POST /api/user/push/subscriptions, then
+# checks the VAPID authorization of every push, decrypts it and prints the payload.
+# Set MOCK_PUSH_STATUS=410 to answer like an expired subscription.
+import base64
+import json
+import os
+import secrets
+import time
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+from cryptography.hazmat.primitives import hashes
+from cryptography.hazmat.primitives.asymmetric import ec
+from cryptography.hazmat.primitives.asymmetric.utils import encode_dss_signature
+from cryptography.hazmat.primitives.ciphers.aead import AESGCM
+from cryptography.hazmat.primitives.kdf.hkdf import HKDF
+from cryptography.hazmat.primitives.serialization import Encoding, PublicFormat
+
+PORT = int(os.environ.get("MOCK_PUSH_PORT", "9500"))
+ORIGIN = f"http://localhost:{PORT}"
+STATUS = int(os.environ.get("MOCK_PUSH_STATUS", "201"))
+
+key = ec.generate_private_key(ec.SECP256R1())
+public_key = key.public_key().public_bytes(Encoding.X962, PublicFormat.UncompressedPoint)
+auth_secret = secrets.token_bytes(16)
+
+
+def b64url(data):
+    return base64.urlsafe_b64encode(data).rstrip(b"=").decode()
+
+
+def b64url_decode(data):
+    return base64.urlsafe_b64decode(data + "=" * (-len(data) % 4))
+
+
+def hkdf(salt, ikm, info, length):
+    return HKDF(algorithm=hashes.SHA256(), length=length, salt=salt, info=info).derive(ikm)
+
+
+def verify_vapid(authorization):
+    params = dict(p.strip().split("=", 1) for p in authorization[len("vapid ") :].split(","))
+    header, payload, signature = params["t"].split(".")
+    server_key = ec.EllipticCurvePublicKey.from_encoded_point(ec.SECP256R1(), b64url_decode(params["k"]))
+    raw = b64url_decode(signature)
+    der = encode_dss_signature(int.from_bytes(raw[:32], "big"), int.from_bytes(raw[32:], "big"))
+    server_key.verify(der, f"{header}.{payload}".encode(), ec.ECDSA(hashes.SHA256()))
+    claims = json.loads(b64url_decode(payload))
+    assert claims["aud"] == ORIGIN, f"unexpected audience {claims['aud']}"
+    assert time.time() < claims["exp"] <= time.time() + 24 * 60 * 60, "bad expiry"
+    return claims
+
+
+def decrypt(body):
+    salt, record_size, id_len = body[:16], int.from_bytes(body[16:20], "big"), body[20]
+    server_public = body[21 : 21 + id_len]
+    ciphertext = body[21 + id_len :]
+    assert len(ciphertext) <= record_size, "payload exceeds the record size"
+    sender = ec.EllipticCurvePublicKey.from_encoded_point(ec.SECP256R1(), server_public)
+    shared = key.exchange(ec.ECDH(), sender)
+    ikm = hkdf(auth_secret, shared, b"WebPush: info\0" + public_key + server_public, 32)
+    cek = hkdf(salt, ikm, b"Content-Encoding: aes128gcm\0", 16)
+    nonce = hkdf(salt, ikm, b"Content-Encoding: nonce\0", 12)
+    plaintext = AESGCM(cek).decrypt(nonce, ciphertext, None)
+    assert plaintext.rstrip(b"\0").endswith(b"\x02"), "missing last record delimiter"
+    return plaintext.rstrip(b"\0")[:-1]
+
+
+class Handler(BaseHTTPRequestHandler):
+    def do_POST(self):
+        body = self.rfile.read(int(self.headers.get("Content-Length", 0)))
+        try:
+            assert self.headers.get("Content-Encoding") == "aes128gcm", "expected aes128gcm"
+            claims = verify_vapid(self.headers.get("Authorization", ""))
+            payload = json.loads(decrypt(body))
+            print(f"push from {claims['sub']} (TTL {self.headers.get('TTL')}):", json.dumps(payload), flush=True)
+            self.send_response(STATUS)
+        except Exception as e:
+            print("rejected push:", repr(e), flush=True)
+            self.send_response(400)
+        self.end_headers()
+
+    def log_message(self, *args):
+        pass
+
+
+print(
+    json.dumps(
+        {
+            "endpoint": f"{ORIGIN}/push/{secrets.token_hex(8)}",
+            "keys": {"p256dh": b64url(public_key), "auth": b64url(auth_secret)},
+        }
+    ),
+    flush=True,
+)
+HTTPServer(("localhost", PORT), Handler).serve_forever()
@@ -2,26 +2,56 @@ use sqlx::postgres::PgPoolOptions;
 use std::fs::File;
 use std::io::prelude::*;
 
+use crate::repository::AccessTokenRepository;
+use crate::repository::AdminRepository;
 use crate::repository::AttachmentRepository;
 use crate::repository::AuthRepository;
+use crate::repository::BlockRepository;
 use crate::repository::ContactRepository;
+use crate::repository::ConversationSettingRepository;
+use crate::repository::ConversationSummaryRepository;
+use crate::repository::DigestRepository;
 use crate::repository::GroupRepository;
 use crate::repository::MessageRepository;
+use crate::repository::OidcRepository;
+use crate::repository::PresenceRepository;
+use crate::repository::PushRepository;
 use crate::repository::SessionRepository;
 use crate::repository::UserRepository;
+use crate::repository::WebauthnRepository;
+use crate::repository::WsTicketRepository;
+use crate::service::AccessTokenService;
+use crate::service::AdminService;
 use crate::service::AttachmentService;
 use crate::service::AuthService;
+use crate::service::BlockService;
 use crate::service::ContactService;
+use crate::service::ConversationService;
+use crate::service::DigestService;
 use crate::service::GroupService;
+use crate::service::JwtService;
+use crate::service::Mailer;
 use crate::service::MessageService;
+use crate::service::OidcConfig;
+use crate::service::OidcService;
+use crate::service::PresenceService;
+use crate::service::PushService;
 use crate::service::UserService;
+use crate::service::VapidKey;
+use crate::service::WebauthnConfig;
+use crate::service::WebauthnService;
+use crate::service::WsTicketService;
+use crate::websocket::bus::FanoutBus;
+use crate::websocket::message::AppMessage;
+use crate::websocket::FallbackSessions;
 use crate::websocket::SessionFactory;
 use crate::websocket::WsServer;
 
 #[derive(Clone)]
 pub struct AppState {
-    pub env_jwt_secret: String,
     pub env_jwt_secret_mins: u64,
+    /// Whether websockets may still be opened with a `token` query parameter.
+    pub ws_allow_query_token: bool,
     pub empty_profile: Vec<u8>,
     pub message_repository: MessageRepository,
     pub contact_repository: ContactRepository,
@@ -29,30 +59,51 @@ pub struct AppState {
     pub user_repository: UserRepository,
     pub session_repository: SessionRepository,
     pub session_factory: SessionFactory,
+    pub fallback_sessions: FallbackSessions,
     pub group_repository: GroupRepository,
     pub attachment_repository: AttachmentRepository,
+    pub jwt_service: JwtService,
     pub auth_service: AuthService,
     pub contact_service: ContactService,
+    pub conversation_service: ConversationService,
     pub message_service: MessageService,
     pub group_service: GroupService,
     pub user_service: UserService,
     pub attachment_service: AttachmentService,
+    pub access_token_service: AccessTokenService,
+    pub oidc_service: OidcService,
+    pub webauthn_service: WebauthnService,
+    pub admin_service: AdminService,
+    pub block_service: BlockService,
+    pub presence_service: PresenceService,
+    pub ws_ticket_service: WsTicketService,
+    pub push_service: PushService,
+    pub digest_service: DigestService,
 }
 
 impl AppState {
     pub async fn default() -> (Self, WsServer) {
         let db_url = std::env::var("DATABASE_URL").expect("DATABASE_URL is missing");
-        let env_jwt_secret = std::env::var("JWT_SECRET").expect("JWT_SECRET is missing");
         let env_jwt_secret_mins = std::env::var("JWT_EXPIRATION_MINS")
             .expect("JWT_EXPIRATION_MINS is missing")
             .parse::<u64>()
             .expect("JWT_EXPIRATION_MINS cannot be parsed into u64");
+        let ws_allow_query_token = std::env::var("WS_ALLOW_QUERY_TOKEN")
+            .map(|v| v != "false")
+            .unwrap_or(true);
+        let max_connections = std::env::var("DATABASE_MAX_CONNECTIONS")
+            .map(|v| {
+                v.parse::<u32>()
+                    .expect("DATABASE_MAX_CONNECTIONS cannot be parsed into u32")
+            })
+            .unwrap_or(5);
         let sqlx_conn = PgPoolOptions::new()
-            .max_connections(5)
+            .max_connections(max_connections)
             .connect(&db_url)
             .await
             .unwrap();
         let empty_profile = Self::read_empty_profile();
+        let jwt_service = JwtService::from_env().expect("Failed loading JWT signing keys");
 
         let message_repository = MessageRepository::new(sqlx_conn.clone());
         let contact_repository = ContactRepository::new(sqlx_conn.clone());
@@ -61,48 +112,133 @@ impl AppState {
         let session_repository = SessionRepository::new(sqlx_conn.clone());
         let group_repository = GroupRepository::new(sqlx_conn.clone());
         let attachment_repository = AttachmentRepository::new(sqlx_conn.clone());
+        let block_repository = BlockRepository::new(sqlx_conn.clone());
+        let presence_repository = PresenceRepository::new(sqlx_conn.clone());
+        let conversation_setting_repository = ConversationSettingRepository::new(sqlx_conn.clone());
         let message_service = MessageService::new(sqlx_conn.clone());
+        let push_service = PushService::new(
+            PushRepository::new(sqlx_conn.clone()),
+            conversation_setting_repository.clone(),
+            VapidKey::from_env().expect("Failed loading the VAPID key"),
+        );
+        let (app_tx, app_rx) = AppMessage::channel();
         let auth_service = AuthService::new(
             auth_repository.clone(),
-            env_jwt_secret.clone(),
+            jwt_service.clone(),
             env_jwt_secret_mins,
+            app_tx.clone(),
         );
         let contact_service = ContactService::new(
             contact_repository.clone(),
             message_repository.clone(),
             group_repository.clone(),
+            block_repository.clone(),
+            conversation_setting_repository.clone(),
         );
         let (ws_server, session_factory) = WsServer::new(
             message_repository.clone(),
             group_repository.clone(),
             message_service.clone(),
             contact_service.clone(),
+            presence_repository.clone(),
+            push_service.clone(),
+            auth_service.clone(),
+            jwt_service.clone(),
+            FanoutBus::from_env(sqlx_conn.clone()).await,
+            app_tx,
+            app_rx,
         );
         let group_service = GroupService::new(
             sqlx_conn.clone(),
             group_repository.clone(),
             empty_profile.clone(),
         );
-        let user_service = UserService::new(user_repository.clone(), auth_repository.clone());
+        let user_service = UserService::new(
+            user_repository.clone(),
+            auth_repository.clone(),
+            session_factory.app_tx.clone(),
+        );
         let attachment_service = AttachmentService::new(attachment_repository.clone());
+        let access_token_service = AccessTokenService::new(
+            AccessTokenRepository::new(sqlx_conn.clone()),
+            session_factory.app_tx.clone(),
+        );
+        let oidc_service = OidcService::new(
+            OidcConfig::from_env(),
+            OidcRepository::new(sqlx_conn.clone()),
+            auth_repository.clone(),
+            auth_service.clone(),
+        );
+        let webauthn_service = WebauthnService::new(
+            WebauthnConfig::from_env(),
+            WebauthnRepository::new(sqlx_conn.clone()),
+            auth_repository.clone(),
+            auth_service.clone(),
+        )
+        .expect("Invalid WebAuthn relying party configuration");
+        let admin_service = AdminService::new(
+            AdminRepository::new(sqlx_conn.clone()),
+            auth_repository.clone(),
+            message_repository.clone(),
+            group_repository.clone(),
+            session_factory.app_tx.clone(),
+        );
+        let block_service = BlockService::new(
+            block_repository,
+            auth_repository.clone(),
+            session_factory.app_tx.clone(),
+        );
+        let presence_service =
+            PresenceService::new(presence_repository, session_factory.app_tx.clone());
+        let ws_ticket_service = WsTicketService::new(
+            WsTicketRepository::new(sqlx_conn.clone()),
+            jwt_service.clone(),
+            auth_service.clone(),
+        );
+        let conversation_service = ConversationService::new(
+            conversation_setting_repository.clone(),
+            ConversationSummaryRepository::new(sqlx_conn.clone()),
+            auth_repository.clone(),
+            group_repository.clone(),
+            session_factory.app_tx.clone(),
+        );
+        let digest_service = DigestService::new(
+            DigestRepository::new(sqlx_conn.clone()),
+            message_repository.clone(),
+            group_repository.clone(),
+            conversation_setting_repository,
+            Mailer::from_env(),
+        );
         let app_state = AppState {
-            env_jwt_secret,
             env_jwt_secret_mins,
+            ws_allow_query_token,
             empty_profile,
             message_repository,
             contact_repository,
             auth_repository,
             user_repository,
             session_factory,
+            fallback_sessions: FallbackSessions::default(),
             session_repository,
             group_repository,
             attachment_repository,
+            jwt_service,
             auth_service,
             contact_service,
+            conversation_service,
             message_service,
             group_service,
             user_service,
             attachment_service,
+            access_token_service,
+            oidc_service,
+            webauthn_service,
+            admin_service,
+            block_service,
+            presence_service,
+            ws_ticket_service,
+            push_service,
+            digest_service,
         };
         (app_state, ws_server)
     }
@@ -10,7 +10,7 @@ mod utility;
 mod websocket;
 // use entities::{ prelude::*, * };
 
-#[tokio::main(flavor = "current_thread")]
+#[tokio::main]
 async fn main() -> std::io::Result<()> {
     // run::run().await
     run::axum_run().await;
@@ -0,0 +1,14 @@
+use chrono::NaiveDateTime;
+
+#[derive(sqlx::FromRow, Clone)]
+pub struct AccessTokenRepositoryModel {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub token_hash: String,
+    pub scopes: Vec<String>,
+    pub created_at: NaiveDateTime,
+    pub expires_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub revoked: bool,
+}
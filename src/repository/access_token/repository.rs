@@ -0,0 +1,75 @@
+use sqlx::Pool;
+use sqlx::Postgres;
+
+use super::AccessTokenRepositoryModel;
+use super::CREATE_ACCESS_TOKEN_STMT;
+use super::FIND_ACCESS_TOKENS_BY_USER_ID_STMT;
+use super::FIND_VALID_ACCESS_TOKEN_BY_HASH_STMT;
+use super::REVOKE_ACCESS_TOKEN_STMT;
+
+#[derive(Clone)]
+pub struct AccessTokenRepository {
+    conn: Pool<Postgres>,
+}
+
+impl AccessTokenRepository {
+    pub fn new(conn: Pool<Postgres>) -> Self {
+        AccessTokenRepository { conn }
+    }
+
+    pub async fn create_access_token(
+        &self,
+        user_id: i32,
+        name: String,
+        token_hash: String,
+        scopes: Vec<String>,
+        expires_in_days: Option<i32>,
+    ) -> Result<AccessTokenRepositoryModel, String> {
+        sqlx::query_as::<_, AccessTokenRepositoryModel>(CREATE_ACCESS_TOKEN_STMT)
+            .bind(user_id)
+            .bind(name)
+            .bind(token_hash)
+            .bind(scopes)
+            .bind(expires_in_days)
+            .fetch_one(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    pub async fn find_access_tokens_by_user_id(
+        &self,
+        user_id: i32,
+    ) -> Result<Vec<AccessTokenRepositoryModel>, String> {
+        sqlx::query_as::<_, AccessTokenRepositoryModel>(FIND_ACCESS_TOKENS_BY_USER_ID_STMT)
+            .bind(user_id)
+            .fetch_all(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// Looks up a token that is neither revoked nor expired, recording its use.
+    pub async fn find_valid_access_token_by_hash(
+        &self,
+        token_hash: String,
+    ) -> Result<Option<AccessTokenRepositoryModel>, String> {
+        sqlx::query_as::<_, AccessTokenRepositoryModel>(FIND_VALID_ACCESS_TOKEN_BY_HASH_STMT)
+            .bind(token_hash)
+            .fetch_optional(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    pub async fn revoke_access_token(
+        &self,
+        token_id: i32,
+        user_id: i32,
+    ) -> Result<bool, String> {
+        sqlx::query(REVOKE_ACCESS_TOKEN_STMT)
+            .bind(token_id)
+            .bind(user_id)
+            .execute(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.rows_affected() == 1)
+    }
+}
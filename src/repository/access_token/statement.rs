@@ -0,0 +1,25 @@
+pub const CREATE_ACCESS_TOKEN_STMT: &str = "
+INSERT INTO PUBLIC.ACCESS_TOKEN (USER_ID, NAME, TOKEN_HASH, SCOPES, EXPIRES_AT)
+VALUES (
+    $1, $2, $3, $4,
+    CASE WHEN $5::INTEGER IS NULL THEN NULL ELSE CURRENT_TIMESTAMP + MAKE_INTERVAL(DAYS => $5) END
+) RETURNING *;
+";
+pub const FIND_ACCESS_TOKENS_BY_USER_ID_STMT: &str = "
+SELECT * FROM PUBLIC.ACCESS_TOKEN
+WHERE USER_ID = $1 AND REVOKED = FALSE
+ORDER BY CREATED_AT DESC;
+";
+pub const FIND_VALID_ACCESS_TOKEN_BY_HASH_STMT: &str = "
+UPDATE PUBLIC.ACCESS_TOKEN
+    SET LAST_USED_AT = CURRENT_TIMESTAMP
+WHERE TOKEN_HASH = $1
+    AND REVOKED = FALSE
+    AND (EXPIRES_AT IS NULL OR EXPIRES_AT > CURRENT_TIMESTAMP)
+RETURNING *;
+";
+pub const REVOKE_ACCESS_TOKEN_STMT: &str = "
+UPDATE PUBLIC.ACCESS_TOKEN
+    SET REVOKED = TRUE
+WHERE ID = $1 AND USER_ID = $2 AND REVOKED = FALSE;
+";
@@ -0,0 +1,20 @@
+use chrono::NaiveDateTime;
+
+#[derive(sqlx::FromRow)]
+pub struct AuditLogRepositoryModel {
+    pub id: i32,
+    pub admin_id: i32,
+    pub action: String,
+    pub target_user_id: Option<i32>,
+    pub details: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(sqlx::FromRow)]
+pub struct ServerStatsRepositoryModel {
+    pub users: i64,
+    pub suspended_users: i64,
+    pub direct_messages: i64,
+    pub group_messages: i64,
+    pub groups: i64,
+}
@@ -0,0 +1,131 @@
+use sqlx::Pool;
+use sqlx::Postgres;
+
+use crate::repository::UserModelRepository;
+
+use super::AuditLogRepositoryModel;
+use super::ServerStatsRepositoryModel;
+use super::CREATE_AUDIT_LOG_STMT;
+use super::FIND_AUDIT_LOG_STMT;
+use super::FORCE_PASSWORD_RESET_STMT;
+use super::SEARCH_USERS_STMT;
+use super::SERVER_STATS_STMT;
+use super::SUSPEND_USER_STMT;
+use super::UNSUSPEND_USER_STMT;
+use super::UPDATE_USER_ROLE_STMT;
+
+#[derive(Clone)]
+pub struct AdminRepository {
+    conn: Pool<Postgres>,
+}
+
+impl AdminRepository {
+    pub fn new(conn: Pool<Postgres>) -> Self {
+        AdminRepository { conn }
+    }
+
+    pub async fn search_users(
+        &self,
+        query: Option<String>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<UserModelRepository>, String> {
+        sqlx::query_as::<_, UserModelRepository>(SEARCH_USERS_STMT)
+            .bind(query)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    pub async fn update_role(
+        &self,
+        user_id: i32,
+        role: String,
+    ) -> Result<bool, String> {
+        sqlx::query(UPDATE_USER_ROLE_STMT)
+            .bind(user_id)
+            .bind(role)
+            .execute(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.rows_affected() == 1)
+    }
+
+    pub async fn suspend_user(
+        &self,
+        user_id: i32,
+    ) -> Result<bool, String> {
+        sqlx::query(SUSPEND_USER_STMT)
+            .bind(user_id)
+            .execute(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.rows_affected() == 1)
+    }
+
+    pub async fn unsuspend_user(
+        &self,
+        user_id: i32,
+    ) -> Result<bool, String> {
+        sqlx::query(UNSUSPEND_USER_STMT)
+            .bind(user_id)
+            .execute(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.rows_affected() == 1)
+    }
+
+    pub async fn force_password_reset(
+        &self,
+        user_id: i32,
+        temporary_password: String,
+    ) -> Result<bool, String> {
+        sqlx::query(FORCE_PASSWORD_RESET_STMT)
+            .bind(user_id)
+            .bind(temporary_password)
+            .execute(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.rows_affected() == 1)
+    }
+
+    pub async fn create_audit_log(
+        &self,
+        admin_id: i32,
+        action: String,
+        target_user_id: Option<i32>,
+        details: Option<String>,
+    ) -> Result<bool, String> {
+        sqlx::query(CREATE_AUDIT_LOG_STMT)
+            .bind(admin_id)
+            .bind(action)
+            .bind(target_user_id)
+            .bind(details)
+            .execute(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.rows_affected() == 1)
+    }
+
+    pub async fn find_audit_log(
+        &self,
+        before_id: Option<i32>,
+        limit: i64,
+    ) -> Result<Vec<AuditLogRepositoryModel>, String> {
+        sqlx::query_as::<_, AuditLogRepositoryModel>(FIND_AUDIT_LOG_STMT)
+            .bind(before_id)
+            .bind(limit)
+            .fetch_all(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    pub async fn server_stats(&self) -> Result<ServerStatsRepositoryModel, String> {
+        sqlx::query_as::<_, ServerStatsRepositoryModel>(SERVER_STATS_STMT)
+            .fetch_one(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+    }
+}
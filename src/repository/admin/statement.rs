@@ -0,0 +1,35 @@
+pub const SEARCH_USERS_STMT: &str = "
+SELECT * FROM PUBLIC.USER
+WHERE $1::TEXT IS NULL OR USERNAME ILIKE '%' || $1 || '%' OR EMAIL ILIKE '%' || $1 || '%'
+ORDER BY ID
+LIMIT $2 OFFSET $3;
+";
+pub const UPDATE_USER_ROLE_STMT: &str = "UPDATE PUBLIC.USER SET ROLE = $2 WHERE ID = $1";
+pub const SUSPEND_USER_STMT: &str = "
+UPDATE PUBLIC.USER SET SUSPENDED_AT = CURRENT_TIMESTAMP WHERE ID = $1 AND SUSPENDED_AT IS NULL;
+";
+pub const UNSUSPEND_USER_STMT: &str = "
+UPDATE PUBLIC.USER SET SUSPENDED_AT = NULL WHERE ID = $1 AND SUSPENDED_AT IS NOT NULL;
+";
+pub const FORCE_PASSWORD_RESET_STMT: &str = "
+UPDATE PUBLIC.USER
+    SET PASSWORD = CRYPT($2, GEN_SALT('bf', 5)), PASSWORD_RESET_REQUIRED = TRUE
+WHERE ID = $1;
+";
+pub const CREATE_AUDIT_LOG_STMT: &str = "
+INSERT INTO PUBLIC.ADMIN_AUDIT_LOG (ADMIN_ID, ACTION, TARGET_USER_ID, DETAILS) VALUES ($1, $2, $3, $4);
+";
+pub const FIND_AUDIT_LOG_STMT: &str = "
+SELECT * FROM PUBLIC.ADMIN_AUDIT_LOG
+WHERE $1::INTEGER IS NULL OR ID < $1
+ORDER BY ID DESC
+LIMIT $2;
+";
+pub const SERVER_STATS_STMT: &str = "
+SELECT
+    (SELECT COUNT(*) FROM PUBLIC.USER) AS USERS,
+    (SELECT COUNT(*) FROM PUBLIC.USER WHERE SUSPENDED_AT IS NOT NULL) AS SUSPENDED_USERS,
+    (SELECT COUNT(*) FROM PUBLIC.MESSAGE WHERE DELETED = FALSE) AS DIRECT_MESSAGES,
+    (SELECT COUNT(*) FROM PUBLIC.GROUP_MESSAGE WHERE DELETED = FALSE) AS GROUP_MESSAGES,
+    (SELECT COUNT(*) FROM PUBLIC.GROUP) AS GROUPS;
+";
@@ -1,7 +1,14 @@
+use chrono::NaiveDateTime;
+
 #[derive(sqlx::FromRow)]
 pub struct UserModelRepository {
     pub id: i32,
     pub email: String,
     pub username: String,
     pub password: String,
+    pub role: String,
+    pub suspended_at: Option<NaiveDateTime>,
+    pub password_reset_required: bool,
+    pub discoverable: bool,
+    pub presence_visibility: String,
 }
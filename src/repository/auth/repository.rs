@@ -3,8 +3,10 @@ use sqlx::Postgres;
 
 use super::UserModelRepository;
 use super::CREATE_USER_STMT;
+use super::CREATE_USER_WITH_USERNAME_STMT;
 use super::FIND_USER_BY_EMAIL_STMT;
 use super::FIND_USER_BY_ID_STMT;
+use super::FIND_USER_BY_USERNAME_STMT;
 use super::UPDATE_EMAIL_STMT;
 use super::UPDATE_PASSWORD_STMT;
 use super::UPDATE_USERNAME_STMT;
@@ -41,6 +43,17 @@ impl AuthRepository {
             .map_err(|e| e.to_string())
     }
 
+    pub async fn find_user_by_username(
+        &self,
+        username: String,
+    ) -> Result<Option<UserModelRepository>, String> {
+        sqlx::query_as::<_, UserModelRepository>(FIND_USER_BY_USERNAME_STMT)
+            .bind(username)
+            .fetch_optional(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
     pub async fn update_username(
         &self,
         uid: i32,
@@ -96,4 +109,19 @@ impl AuthRepository {
             .map_err(|e| e.to_string())
             .map(|r| r.rows_affected() == 1)
     }
+
+    pub async fn create_user_with_username(
+        &self,
+        username: String,
+        email: String,
+        password: String,
+    ) -> Result<UserModelRepository, String> {
+        sqlx::query_as::<_, UserModelRepository>(CREATE_USER_WITH_USERNAME_STMT)
+            .bind(username)
+            .bind(email)
+            .bind(password)
+            .fetch_one(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+    }
 }
@@ -0,0 +1,7 @@
+mod model;
+mod repository;
+mod statement;
+
+pub use model::*;
+pub use repository::*;
+pub use statement::*;
@@ -0,0 +1,9 @@
+use chrono::NaiveDateTime;
+
+#[derive(sqlx::FromRow, Clone)]
+pub struct BlockedUserRepositoryModel {
+    pub user_id: i32,
+    pub username: String,
+    pub collapse_group_messages: bool,
+    pub blocked_at: NaiveDateTime,
+}
@@ -0,0 +1,126 @@
+use sqlx::Pool;
+use sqlx::Postgres;
+
+use super::BlockedUserRepositoryModel;
+use super::DELETE_USER_BLOCK_STMT;
+use super::FIND_BLOCKED_IDS_STMT;
+use super::FIND_BLOCKED_USERS_STMT;
+use super::FIND_BLOCK_RELATED_IDS_STMT;
+use super::FIND_COLLAPSED_SENDER_IDS_STMT;
+use super::FIND_COLLAPSING_READER_IDS_STMT;
+use super::IS_BLOCKED_BETWEEN_STMT;
+use super::UPSERT_USER_BLOCK_STMT;
+
+#[derive(Clone)]
+pub struct BlockRepository {
+    conn: Pool<Postgres>,
+}
+
+impl BlockRepository {
+    pub fn new(conn: Pool<Postgres>) -> Self {
+        Self { conn }
+    }
+
+    async fn find_ids(
+        &self,
+        stmt: &str,
+        user_id: i32,
+    ) -> Result<Vec<i32>, String> {
+        sqlx::query_as::<_, (i32,)>(stmt)
+            .bind(user_id)
+            .fetch_all(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.iter().map(|t| t.0).collect())
+    }
+
+    pub async fn block_user(
+        &self,
+        blocker_id: i32,
+        blocked_id: i32,
+        collapse_group_messages: bool,
+    ) -> Result<bool, String> {
+        sqlx::query(UPSERT_USER_BLOCK_STMT)
+            .bind(blocker_id)
+            .bind(blocked_id)
+            .bind(collapse_group_messages)
+            .execute(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.rows_affected() == 1)
+    }
+
+    pub async fn unblock_user(
+        &self,
+        blocker_id: i32,
+        blocked_id: i32,
+    ) -> Result<bool, String> {
+        sqlx::query(DELETE_USER_BLOCK_STMT)
+            .bind(blocker_id)
+            .bind(blocked_id)
+            .execute(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.rows_affected() == 1)
+    }
+
+    pub async fn find_blocked_users(
+        &self,
+        blocker_id: i32,
+    ) -> Result<Vec<BlockedUserRepositoryModel>, String> {
+        sqlx::query_as::<_, BlockedUserRepositoryModel>(FIND_BLOCKED_USERS_STMT)
+            .bind(blocker_id)
+            .fetch_all(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// Whether either user has blocked the other.
+    pub async fn is_blocked_between(
+        &self,
+        user_id: i32,
+        other_id: i32,
+    ) -> Result<bool, String> {
+        sqlx::query_as::<_, (bool,)>(IS_BLOCKED_BETWEEN_STMT)
+            .bind(user_id)
+            .bind(other_id)
+            .fetch_one(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.0)
+    }
+
+    /// Users blocked by `blocker_id`.
+    pub async fn find_blocked_ids(
+        &self,
+        blocker_id: i32,
+    ) -> Result<Vec<i32>, String> {
+        self.find_ids(FIND_BLOCKED_IDS_STMT, blocker_id).await
+    }
+
+    /// Users that `user_id` has blocked or has been blocked by.
+    pub async fn find_block_related_ids(
+        &self,
+        user_id: i32,
+    ) -> Result<Vec<i32>, String> {
+        self.find_ids(FIND_BLOCK_RELATED_IDS_STMT, user_id).await
+    }
+
+    /// Senders whose group messages `reader_id` wants collapsed.
+    pub async fn find_collapsed_sender_ids(
+        &self,
+        reader_id: i32,
+    ) -> Result<Vec<i32>, String> {
+        self.find_ids(FIND_COLLAPSED_SENDER_IDS_STMT, reader_id)
+            .await
+    }
+
+    /// Readers that want the group messages of `sender_id` collapsed.
+    pub async fn find_collapsing_reader_ids(
+        &self,
+        sender_id: i32,
+    ) -> Result<Vec<i32>, String> {
+        self.find_ids(FIND_COLLAPSING_READER_IDS_STMT, sender_id)
+            .await
+    }
+}
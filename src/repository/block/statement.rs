@@ -0,0 +1,38 @@
+pub const UPSERT_USER_BLOCK_STMT: &str = "
+INSERT INTO PUBLIC.USER_BLOCK (BLOCKER_ID, BLOCKED_ID, COLLAPSE_GROUP_MESSAGES)
+VALUES ($1, $2, $3)
+ON CONFLICT (BLOCKER_ID, BLOCKED_ID)
+    DO UPDATE SET COLLAPSE_GROUP_MESSAGES = EXCLUDED.COLLAPSE_GROUP_MESSAGES;
+";
+pub const DELETE_USER_BLOCK_STMT: &str = "
+DELETE FROM PUBLIC.USER_BLOCK WHERE BLOCKER_ID = $1 AND BLOCKED_ID = $2;
+";
+pub const FIND_BLOCKED_USERS_STMT: &str = "
+SELECT U.ID AS USER_ID, U.USERNAME, B.COLLAPSE_GROUP_MESSAGES, B.CREATED_AT AS BLOCKED_AT
+FROM PUBLIC.USER_BLOCK B
+    JOIN PUBLIC.USER U ON U.ID = B.BLOCKED_ID
+WHERE B.BLOCKER_ID = $1
+ORDER BY B.CREATED_AT DESC;
+";
+pub const IS_BLOCKED_BETWEEN_STMT: &str = "
+SELECT EXISTS (
+    SELECT 1 FROM PUBLIC.USER_BLOCK
+    WHERE (BLOCKER_ID = $1 AND BLOCKED_ID = $2) OR (BLOCKER_ID = $2 AND BLOCKED_ID = $1)
+);
+";
+pub const FIND_BLOCKED_IDS_STMT: &str = "
+SELECT BLOCKED_ID FROM PUBLIC.USER_BLOCK WHERE BLOCKER_ID = $1;
+";
+pub const FIND_BLOCK_RELATED_IDS_STMT: &str = "
+SELECT BLOCKED_ID FROM PUBLIC.USER_BLOCK WHERE BLOCKER_ID = $1
+UNION
+SELECT BLOCKER_ID FROM PUBLIC.USER_BLOCK WHERE BLOCKED_ID = $1;
+";
+pub const FIND_COLLAPSED_SENDER_IDS_STMT: &str = "
+SELECT BLOCKED_ID FROM PUBLIC.USER_BLOCK
+WHERE BLOCKER_ID = $1 AND COLLAPSE_GROUP_MESSAGES;
+";
+pub const FIND_COLLAPSING_READER_IDS_STMT: &str = "
+SELECT BLOCKER_ID FROM PUBLIC.USER_BLOCK
+WHERE BLOCKED_ID = $1 AND COLLAPSE_GROUP_MESSAGES;
+";
@@ -0,0 +1,5 @@
+mod repository;
+mod statement;
+
+pub use repository::*;
+pub use statement::*;
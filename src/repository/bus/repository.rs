@@ -0,0 +1,89 @@
+use sqlx::postgres::PgListener;
+use sqlx::Pool;
+use sqlx::Postgres;
+
+use super::DELETE_STALE_BUS_PAYLOADS_STMT;
+use super::FIND_BUS_PAYLOAD_STMT;
+use super::INSERT_BUS_PAYLOAD_STMT;
+use super::NEXT_NODE_ID_STMT;
+use super::NOTIFY_STMT;
+
+#[derive(Clone)]
+pub struct BusRepository {
+    conn: Pool<Postgres>,
+}
+
+impl BusRepository {
+    pub fn new(conn: Pool<Postgres>) -> Self {
+        Self { conn }
+    }
+
+    pub async fn listen(
+        &self,
+        channel: &str,
+    ) -> Result<PgListener, String> {
+        let mut listener = PgListener::connect_with(&self.conn)
+            .await
+            .map_err(|e| e.to_string())?;
+        listener
+            .listen(channel)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(listener)
+    }
+
+    pub async fn notify(
+        &self,
+        channel: &str,
+        payload: String,
+    ) -> Result<(), String> {
+        sqlx::query(NOTIFY_STMT)
+            .bind(channel)
+            .bind(payload)
+            .execute(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|_| ())
+    }
+
+    pub async fn store_payload(
+        &self,
+        payload: String,
+    ) -> Result<i64, String> {
+        sqlx::query_as::<_, (i64,)>(INSERT_BUS_PAYLOAD_STMT)
+            .bind(payload)
+            .fetch_one(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.0)
+    }
+
+    pub async fn find_payload(
+        &self,
+        id: i64,
+    ) -> Result<Option<String>, String> {
+        sqlx::query_as::<_, (String,)>(FIND_BUS_PAYLOAD_STMT)
+            .bind(id)
+            .fetch_optional(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.map(|t| t.0))
+    }
+
+    /// A node id that no other running node holds.
+    pub async fn next_node_id(&self) -> Result<i64, String> {
+        sqlx::query_as::<_, (i64,)>(NEXT_NODE_ID_STMT)
+            .fetch_one(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.0)
+    }
+
+    pub async fn delete_stale_payloads(&self) -> Result<u64, String> {
+        sqlx::query(DELETE_STALE_BUS_PAYLOADS_STMT)
+            .execute(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.rows_affected())
+    }
+}
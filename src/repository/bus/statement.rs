@@ -0,0 +1,15 @@
+pub const NOTIFY_STMT: &str = "
+SELECT PG_NOTIFY($1, $2);
+";
+pub const INSERT_BUS_PAYLOAD_STMT: &str = "
+INSERT INTO PUBLIC.WS_BUS_PAYLOAD (PAYLOAD) VALUES ($1) RETURNING ID;
+";
+pub const FIND_BUS_PAYLOAD_STMT: &str = "
+SELECT PAYLOAD FROM PUBLIC.WS_BUS_PAYLOAD WHERE ID = $1;
+";
+pub const NEXT_NODE_ID_STMT: &str = "
+SELECT NEXTVAL('PUBLIC.WS_NODE_ID_SEQ');
+";
+pub const DELETE_STALE_BUS_PAYLOADS_STMT: &str = "
+DELETE FROM PUBLIC.WS_BUS_PAYLOAD WHERE CREATED_AT < NOW() - INTERVAL '5 minutes';
+";
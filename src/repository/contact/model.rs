@@ -1,8 +1,14 @@
+use chrono::NaiveDateTime;
 use serde::Serialize;
 
+use crate::repository::UserProfileRepositoryModel;
+
 #[derive(sqlx::FromRow, Serialize)]
 pub struct ContactRepositoryModel {
     pub id: i32,
     pub email: String,
     pub username: String,
+    pub last_seen_at: Option<NaiveDateTime>,
+    #[sqlx(flatten)]
+    pub profile: UserProfileRepositoryModel,
 }
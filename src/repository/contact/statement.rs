@@ -2,7 +2,20 @@ pub const GET_CONTACT_STMT: &str = "
 SELECT
     ID,
     EMAIL,
-    USERNAME
+    USERNAME,
+    CASE WHEN PRESENCE_VISIBILITY = 'everyone'
+        OR (PRESENCE_VISIBILITY = 'contacts' AND EXISTS (
+            SELECT 1 FROM PUBLIC.CONVERSATION_PARTNER P
+            WHERE P.USER_ID = PUBLIC.USER.ID AND P.PARTNER_ID = $1
+        ))
+    THEN LAST_SEEN_AT END AS LAST_SEEN_AT,
+    PUBLIC.USER_PROFILE.*
 FROM PUBLIC.USER
-WHERE ID != $1;    
+    JOIN PUBLIC.USER_PROFILE ON PUBLIC.USER_PROFILE.USER_ID = PUBLIC.USER.ID
+WHERE ID != $1
+    AND NOT EXISTS (
+        SELECT 1 FROM PUBLIC.USER_BLOCK B
+        WHERE (B.BLOCKER_ID = $1 AND B.BLOCKED_ID = PUBLIC.USER.ID)
+            OR (B.BLOCKER_ID = PUBLIC.USER.ID AND B.BLOCKED_ID = $1)
+    );
 ";
@@ -0,0 +1,12 @@
+use chrono::NaiveDateTime;
+
+#[derive(sqlx::FromRow, Clone)]
+pub struct ConversationSettingRepositoryModel {
+    pub conversation_type: String,
+    pub conversation_id: i32,
+    pub muted: bool,
+    pub muted_until: Option<NaiveDateTime>,
+    pub archive: Option<String>,
+    pub archived_at: Option<NaiveDateTime>,
+    pub pin_order: Option<i32>,
+}
@@ -0,0 +1,76 @@
+use chrono::NaiveDateTime;
+use sqlx::Pool;
+use sqlx::Postgres;
+
+use super::ConversationSettingRepositoryModel;
+use super::FIND_CONVERSATION_SETTINGS_STMT;
+use super::FIND_MUTED_USER_IDS_STMT;
+use super::UPSERT_CONVERSATION_SETTING_STMT;
+
+#[derive(Clone)]
+pub struct ConversationSettingRepository {
+    conn: Pool<Postgres>,
+}
+
+impl ConversationSettingRepository {
+    pub fn new(conn: Pool<Postgres>) -> Self {
+        ConversationSettingRepository { conn }
+    }
+
+    /// The settings a user made for their conversations of one type.
+    pub async fn find_settings(
+        &self,
+        user_id: i32,
+        conversation_type: &str,
+    ) -> Result<Vec<ConversationSettingRepositoryModel>, String> {
+        sqlx::query_as::<_, ConversationSettingRepositoryModel>(FIND_CONVERSATION_SETTINGS_STMT)
+            .bind(user_id)
+            .bind(conversation_type)
+            .fetch_all(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// Replaces the settings of a conversation. The archive time is kept while the
+    /// archive mode stays the same.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn upsert_setting(
+        &self,
+        user_id: i32,
+        conversation_type: &str,
+        conversation_id: i32,
+        muted: bool,
+        muted_until: Option<NaiveDateTime>,
+        archive: Option<String>,
+        pin_order: Option<i32>,
+    ) -> Result<ConversationSettingRepositoryModel, String> {
+        sqlx::query_as::<_, ConversationSettingRepositoryModel>(UPSERT_CONVERSATION_SETTING_STMT)
+            .bind(user_id)
+            .bind(conversation_type)
+            .bind(conversation_id)
+            .bind(muted)
+            .bind(muted_until)
+            .bind(archive)
+            .bind(pin_order)
+            .fetch_one(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// Those of the users who currently mute the conversation.
+    pub async fn find_muted_user_ids(
+        &self,
+        user_ids: Vec<i32>,
+        conversation_type: &str,
+        conversation_id: i32,
+    ) -> Result<Vec<i32>, String> {
+        sqlx::query_as::<_, (i32,)>(FIND_MUTED_USER_IDS_STMT)
+            .bind(user_ids)
+            .bind(conversation_type)
+            .bind(conversation_id)
+            .fetch_all(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.into_iter().map(|t| t.0).collect())
+    }
+}
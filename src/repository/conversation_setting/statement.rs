@@ -0,0 +1,31 @@
+pub const FIND_CONVERSATION_SETTINGS_STMT: &str = "
+SELECT CONVERSATION_TYPE, CONVERSATION_ID, MUTED, MUTED_UNTIL, ARCHIVE, ARCHIVED_AT, PIN_ORDER
+FROM PUBLIC.CONVERSATION_SETTING
+WHERE USER_ID = $1 AND CONVERSATION_TYPE = $2;
+";
+pub const UPSERT_CONVERSATION_SETTING_STMT: &str = "
+INSERT INTO PUBLIC.CONVERSATION_SETTING
+    (USER_ID, CONVERSATION_TYPE, CONVERSATION_ID, MUTED, MUTED_UNTIL, ARCHIVE, ARCHIVED_AT, PIN_ORDER)
+VALUES ($1, $2, $3, $4, $5, $6, CASE WHEN $6 IS NULL THEN NULL ELSE CURRENT_TIMESTAMP END, $7)
+ON CONFLICT (USER_ID, CONVERSATION_TYPE, CONVERSATION_ID) DO UPDATE SET
+    MUTED = EXCLUDED.MUTED,
+    MUTED_UNTIL = EXCLUDED.MUTED_UNTIL,
+    ARCHIVE = EXCLUDED.ARCHIVE,
+    ARCHIVED_AT = CASE
+        WHEN EXCLUDED.ARCHIVE IS NULL THEN NULL
+        WHEN CONVERSATION_SETTING.ARCHIVE IS NOT DISTINCT FROM EXCLUDED.ARCHIVE
+            THEN CONVERSATION_SETTING.ARCHIVED_AT
+        ELSE CURRENT_TIMESTAMP
+    END,
+    PIN_ORDER = EXCLUDED.PIN_ORDER,
+    UPDATED_AT = CURRENT_TIMESTAMP
+RETURNING CONVERSATION_TYPE, CONVERSATION_ID, MUTED, MUTED_UNTIL, ARCHIVE, ARCHIVED_AT, PIN_ORDER;
+";
+pub const FIND_MUTED_USER_IDS_STMT: &str = "
+SELECT USER_ID FROM PUBLIC.CONVERSATION_SETTING
+WHERE USER_ID = ANY($1)
+    AND CONVERSATION_TYPE = $2
+    AND CONVERSATION_ID = $3
+    AND MUTED
+    AND (MUTED_UNTIL IS NULL OR MUTED_UNTIL > CURRENT_TIMESTAMP);
+";
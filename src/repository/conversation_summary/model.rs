@@ -0,0 +1,18 @@
+use chrono::NaiveDateTime;
+
+/// A conversation of the inbox with its title and the settings of the user.
+#[derive(sqlx::FromRow, Clone)]
+pub struct ConversationSummaryRepositoryModel {
+    pub conversation_type: String,
+    pub conversation_id: i32,
+    pub title: String,
+    pub last_message_id: Option<i32>,
+    pub last_sender_id: Option<i32>,
+    pub last_sender_name: Option<String>,
+    pub last_message_preview: Option<String>,
+    pub last_message_deleted: bool,
+    pub last_activity_at: NaiveDateTime,
+    pub unread_count: i32,
+    pub muted: bool,
+    pub pin_order: Option<i32>,
+}
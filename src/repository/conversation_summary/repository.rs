@@ -0,0 +1,44 @@
+use chrono::NaiveDateTime;
+use sqlx::Pool;
+use sqlx::Postgres;
+
+use super::ConversationSummaryRepositoryModel;
+use super::FIND_CONVERSATION_SUMMARIES_STMT;
+
+/// Reads the conversation summaries; they are written by the statements that store
+/// messages, reads and group members.
+#[derive(Clone)]
+pub struct ConversationSummaryRepository {
+    conn: Pool<Postgres>,
+}
+
+impl ConversationSummaryRepository {
+    pub fn new(conn: Pool<Postgres>) -> Self {
+        ConversationSummaryRepository { conn }
+    }
+
+    /// A page of the user's conversations by latest activity, after the conversation
+    /// at `before` when given.
+    pub async fn find_summaries(
+        &self,
+        user_id: i32,
+        archived: bool,
+        before: Option<(NaiveDateTime, String, i32)>,
+        limit: i64,
+    ) -> Result<Vec<ConversationSummaryRepositoryModel>, String> {
+        let (before_at, before_type, before_id) = match before {
+            Some((at, conversation_type, id)) => (Some(at), Some(conversation_type), Some(id)),
+            None => (None, None, None),
+        };
+        sqlx::query_as::<_, ConversationSummaryRepositoryModel>(FIND_CONVERSATION_SUMMARIES_STMT)
+            .bind(user_id)
+            .bind(archived)
+            .bind(before_at)
+            .bind(before_type)
+            .bind(before_id)
+            .bind(limit)
+            .fetch_all(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+    }
+}
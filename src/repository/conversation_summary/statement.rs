@@ -0,0 +1,44 @@
+pub const FIND_CONVERSATION_SUMMARIES_STMT: &str = "
+SELECT
+    CS.CONVERSATION_TYPE,
+    CS.CONVERSATION_ID,
+    CASE WHEN CS.CONVERSATION_TYPE = 'direct'
+        THEN COALESCE(NULLIF(U.DISPLAY_NAME, ''), U.USERNAME)
+        ELSE G.NAME
+    END AS TITLE,
+    CS.LAST_MESSAGE_ID,
+    CS.LAST_SENDER_ID,
+    SU.USERNAME AS LAST_SENDER_NAME,
+    CS.LAST_MESSAGE_PREVIEW,
+    CS.LAST_MESSAGE_DELETED,
+    CS.LAST_ACTIVITY_AT,
+    CS.UNREAD_COUNT,
+    COALESCE(CSET.MUTED AND (CSET.MUTED_UNTIL IS NULL OR CSET.MUTED_UNTIL > CURRENT_TIMESTAMP), FALSE) AS MUTED,
+    CSET.PIN_ORDER
+FROM PUBLIC.CONVERSATION_SUMMARY CS
+    LEFT JOIN PUBLIC.USER U ON CS.CONVERSATION_TYPE = 'direct' AND U.ID = CS.CONVERSATION_ID
+    LEFT JOIN PUBLIC.GROUP G ON CS.CONVERSATION_TYPE = 'group' AND G.ID = CS.CONVERSATION_ID
+    LEFT JOIN PUBLIC.USER SU ON SU.ID = CS.LAST_SENDER_ID
+    LEFT JOIN PUBLIC.CONVERSATION_SETTING CSET
+        ON CSET.USER_ID = CS.USER_ID
+            AND CSET.CONVERSATION_TYPE = CS.CONVERSATION_TYPE
+            AND CSET.CONVERSATION_ID = CS.CONVERSATION_ID
+WHERE CS.USER_ID = $1
+    AND NOT EXISTS (
+        SELECT 1 FROM PUBLIC.USER_BLOCK B
+        WHERE CS.CONVERSATION_TYPE = 'direct'
+            AND ((B.BLOCKER_ID = $1 AND B.BLOCKED_ID = CS.CONVERSATION_ID)
+                OR (B.BLOCKER_ID = CS.CONVERSATION_ID AND B.BLOCKED_ID = $1))
+    )
+    AND COALESCE(
+        CSET.ARCHIVE = 'permanent'
+            OR (CSET.ARCHIVE = 'until_activity' AND CS.LAST_ACTIVITY_AT <= CSET.ARCHIVED_AT),
+        FALSE
+    ) = $2
+    AND (
+        $3::TIMESTAMP(3) IS NULL
+        OR (CS.LAST_ACTIVITY_AT, CS.CONVERSATION_TYPE, CS.CONVERSATION_ID) < ($3, $4::TEXT, $5::INTEGER)
+    )
+ORDER BY CS.LAST_ACTIVITY_AT DESC, CS.CONVERSATION_TYPE DESC, CS.CONVERSATION_ID DESC
+LIMIT $6;
+";
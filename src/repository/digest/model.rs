@@ -0,0 +1,13 @@
+use chrono::NaiveDateTime;
+
+/// A user whose digest is due, claimed for sending.
+#[derive(sqlx::FromRow)]
+pub struct DueDigestRepositoryModel {
+    pub user_id: i32,
+    pub email: String,
+    pub username: String,
+    pub presence_state: String,
+    pub unsubscribe_token: String,
+    /// When the previous digest went out, if one did.
+    pub previous_sent_at: Option<NaiveDateTime>,
+}
@@ -0,0 +1,74 @@
+use sqlx::Pool;
+use sqlx::Postgres;
+
+use super::DueDigestRepositoryModel;
+use super::CLAIM_DUE_DIGESTS_STMT;
+use super::FIND_DIGEST_FREQUENCY_STMT;
+use super::UNSUBSCRIBE_DIGEST_STMT;
+use super::UPSERT_DIGEST_FREQUENCY_STMT;
+
+#[derive(Clone)]
+pub struct DigestRepository {
+    conn: Pool<Postgres>,
+}
+
+impl DigestRepository {
+    pub fn new(conn: Pool<Postgres>) -> Self {
+        DigestRepository { conn }
+    }
+
+    pub async fn find_frequency(
+        &self,
+        user_id: i32,
+    ) -> Result<Option<String>, String> {
+        sqlx::query_as::<_, (String,)>(FIND_DIGEST_FREQUENCY_STMT)
+            .bind(user_id)
+            .fetch_optional(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.map(|t| t.0))
+    }
+
+    /// Sets the frequency; `unsubscribe_token` is only stored for a user's first
+    /// preference, later updates keep the token of earlier emails working.
+    pub async fn upsert_frequency(
+        &self,
+        user_id: i32,
+        frequency: String,
+        unsubscribe_token: String,
+    ) -> Result<bool, String> {
+        sqlx::query(UPSERT_DIGEST_FREQUENCY_STMT)
+            .bind(user_id)
+            .bind(frequency)
+            .bind(unsubscribe_token)
+            .execute(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.rows_affected() == 1)
+    }
+
+    pub async fn unsubscribe(
+        &self,
+        unsubscribe_token: String,
+    ) -> Result<bool, String> {
+        sqlx::query(UNSUBSCRIBE_DIGEST_STMT)
+            .bind(unsubscribe_token)
+            .execute(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.rows_affected() == 1)
+    }
+
+    /// Marks up to `limit` due digests as sent and returns them, skipping those
+    /// another node is claiming at the same time.
+    pub async fn claim_due_digests(
+        &self,
+        limit: i64,
+    ) -> Result<Vec<DueDigestRepositoryModel>, String> {
+        sqlx::query_as::<_, DueDigestRepositoryModel>(CLAIM_DUE_DIGESTS_STMT)
+            .bind(limit)
+            .fetch_all(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,31 @@
+pub const FIND_DIGEST_FREQUENCY_STMT: &str = "
+SELECT FREQUENCY FROM PUBLIC.EMAIL_DIGEST WHERE USER_ID = $1;
+";
+pub const UPSERT_DIGEST_FREQUENCY_STMT: &str = "
+INSERT INTO PUBLIC.EMAIL_DIGEST (USER_ID, FREQUENCY, UNSUBSCRIBE_TOKEN)
+VALUES ($1, $2, $3)
+ON CONFLICT (USER_ID) DO UPDATE SET FREQUENCY = EXCLUDED.FREQUENCY;
+";
+pub const UNSUBSCRIBE_DIGEST_STMT: &str = "
+UPDATE PUBLIC.EMAIL_DIGEST SET FREQUENCY = 'off' WHERE UNSUBSCRIBE_TOKEN = $1;
+";
+pub const CLAIM_DUE_DIGESTS_STMT: &str = "
+WITH DUE AS (
+    SELECT D.USER_ID, D.LAST_SENT_AT FROM PUBLIC.EMAIL_DIGEST D
+        JOIN PUBLIC.USER U ON U.ID = D.USER_ID
+    WHERE D.FREQUENCY <> 'off'
+        AND U.SUSPENDED_AT IS NULL
+        AND (
+            D.LAST_SENT_AT IS NULL
+            OR D.LAST_SENT_AT <= CURRENT_TIMESTAMP
+                - CASE D.FREQUENCY WHEN 'hourly' THEN INTERVAL '1 hour' ELSE INTERVAL '1 day' END
+        )
+    LIMIT $1
+    FOR UPDATE OF D SKIP LOCKED
+)
+UPDATE PUBLIC.EMAIL_DIGEST D SET LAST_SENT_AT = CURRENT_TIMESTAMP
+FROM DUE, PUBLIC.USER U
+WHERE D.USER_ID = DUE.USER_ID AND U.ID = D.USER_ID
+RETURNING D.USER_ID, U.EMAIL, U.USERNAME, U.PRESENCE_STATE, D.UNSUBSCRIBE_TOKEN,
+    DUE.LAST_SENT_AT AS PREVIOUS_SENT_AT;
+";
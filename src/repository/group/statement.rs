@@ -1,4 +1,8 @@
 pub const READ_ALL_MESSAGE_STMT: &str = "
+WITH S AS (
+    UPDATE PUBLIC.CONVERSATION_SUMMARY SET UNREAD_COUNT = 0
+    WHERE USER_ID = $1 AND CONVERSATION_TYPE = 'group' AND CONVERSATION_ID = $2
+)
 INSERT INTO PUBLIC.GROUP_MESSAGE_READ(message_id, reader_id, group_id)
 SELECT GM.id as message_id, $1 as reader_id, GM.GROUP_ID FROM PUBLIC.GROUP_MESSAGE GM
 WHERE GM.GROUP_ID = $2
@@ -52,6 +56,21 @@ ORDER BY GM.SENT_AT ASC;
 pub const CREATE_GROUP_MESSAGE_STMT: &str = "
 WITH GM AS (
     INSERT INTO PUBLIC.GROUP_MESSAGE (GROUP_ID, SENDER_ID, CONTENT) VALUES($1, $2, $3) RETURNING *
+), S AS (
+    INSERT INTO PUBLIC.CONVERSATION_SUMMARY
+        (USER_ID, CONVERSATION_TYPE, CONVERSATION_ID, LAST_MESSAGE_ID, LAST_SENDER_ID,
+        LAST_MESSAGE_PREVIEW, LAST_MESSAGE_DELETED, LAST_ACTIVITY_AT, UNREAD_COUNT)
+    SELECT GMEM.USER_ID, 'group', GM.GROUP_ID, GM.ID, GM.SENDER_ID,
+        LEFT(GM.CONTENT, 200), GM.DELETED, GM.SENT_AT,
+        CASE WHEN GMEM.USER_ID = GM.SENDER_ID THEN 0 ELSE 1 END
+    FROM GM JOIN PUBLIC.GROUP_MEMBER GMEM ON GMEM.GROUP_ID = GM.GROUP_ID
+    ON CONFLICT (USER_ID, CONVERSATION_TYPE, CONVERSATION_ID) DO UPDATE SET
+        LAST_MESSAGE_ID = EXCLUDED.LAST_MESSAGE_ID,
+        LAST_SENDER_ID = EXCLUDED.LAST_SENDER_ID,
+        LAST_MESSAGE_PREVIEW = EXCLUDED.LAST_MESSAGE_PREVIEW,
+        LAST_MESSAGE_DELETED = EXCLUDED.LAST_MESSAGE_DELETED,
+        LAST_ACTIVITY_AT = EXCLUDED.LAST_ACTIVITY_AT,
+        UNREAD_COUNT = CONVERSATION_SUMMARY.UNREAD_COUNT + EXCLUDED.UNREAD_COUNT
 ) SELECT     
     GM.ID as ID,
     GM.SENDER_ID AS SENDER_ID,
@@ -87,15 +106,36 @@ pub const DELETE_GROUP_STMT: &str = "
 UPDATE PUBLIC.GROUP SET DISBANDED = TRUE WHERE ID = $1
 ";
 pub const REMOVE_USER_FROM_GROUP_STMT: &str = "
+WITH S AS (
+    DELETE FROM PUBLIC.CONVERSATION_SUMMARY
+    WHERE USER_ID = $1 AND CONVERSATION_TYPE = 'group' AND CONVERSATION_ID = $2
+)
 DELETE FROM PUBLIC.GROUP_MEMBER WHERE user_id = $1 and group_id = $2
 ";
 pub const ADD_USER_TO_GROUP_STMT: &str = "
+WITH S AS (
+    INSERT INTO PUBLIC.CONVERSATION_SUMMARY
+        (USER_ID, CONVERSATION_TYPE, CONVERSATION_ID, LAST_MESSAGE_ID, LAST_SENDER_ID,
+        LAST_MESSAGE_PREVIEW, LAST_MESSAGE_DELETED, LAST_ACTIVITY_AT, UNREAD_COUNT)
+    SELECT $2, 'group', $1, LM.ID, LM.SENDER_ID,
+        LEFT(LM.CONTENT, 200), COALESCE(LM.DELETED, FALSE), COALESCE(LM.SENT_AT, CURRENT_TIMESTAMP),
+        (SELECT COUNT(*) FROM PUBLIC.GROUP_MESSAGE WHERE GROUP_ID = $1)
+    FROM (SELECT 1) ONE
+        LEFT JOIN LATERAL (
+            SELECT * FROM PUBLIC.GROUP_MESSAGE WHERE GROUP_ID = $1 ORDER BY SENT_AT DESC, ID DESC LIMIT 1
+        ) LM ON TRUE
+    ON CONFLICT (USER_ID, CONVERSATION_TYPE, CONVERSATION_ID) DO NOTHING
+)
 INSERT INTO PUBLIC.GROUP_MEMBER (group_id, user_id) VALUES ($1, $2)
 ";
 pub const RENAME_GROUP_STMT: &str = "
 UPDATE PUBLIC.GROUP SET name = $2 WHERE id = $1
 ";
 pub const SET_MESSAGE_DELETE_STMT: &str = "
+WITH S AS (
+    UPDATE PUBLIC.CONVERSATION_SUMMARY SET LAST_MESSAGE_DELETED = TRUE
+    WHERE CONVERSATION_TYPE = 'group' AND LAST_MESSAGE_ID = $1
+)
 UPDATE PUBLIC.GROUP_MESSAGE SET DELETED = TRUE WHERE ID = $1
 ";
 pub const FIND_GROUP_MESSAGE_BY_ID: &str = "
@@ -113,7 +153,10 @@ FROM PUBLIC.GROUP_MESSAGE GM
 WHERE GM.ID = $1;
 ";
 pub const EDIT_MESSAGE_BY_ID_STMT: &str = "
-WITH GM AS (
+WITH S AS (
+    UPDATE PUBLIC.CONVERSATION_SUMMARY SET LAST_MESSAGE_PREVIEW = LEFT($2, 200)
+    WHERE CONVERSATION_TYPE = 'group' AND LAST_MESSAGE_ID = $1
+), GM AS (
     UPDATE PUBLIC.GROUP_MESSAGE
         SET CONTENT = $2, EDITED = TRUE
     WHERE ID = $1 RETURNING *
@@ -1,5 +1,7 @@
 use chrono::NaiveDateTime;
 
+use crate::repository::UserProfileRepositoryModel;
+
 #[derive(sqlx::FromRow, Clone)]
 pub struct MessageRepositoryModel {
     pub id: i32,
@@ -23,4 +25,6 @@ pub struct ConversationRecentMessageRepositoryModel {
     pub unread_count: i64,
     pub username: String,
     pub deleted: bool,
+    #[sqlx(flatten)]
+    pub profile: UserProfileRepositoryModel,
 }
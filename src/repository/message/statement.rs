@@ -5,6 +5,10 @@ SELECT * FROM PUBLIC.MESSAGE
 ORDER BY SENT_AT ASC;
 ";
 pub const UPDATE_MESSAGE_READ_STMT: &str = "
+WITH S AS (
+    UPDATE PUBLIC.CONVERSATION_SUMMARY SET UNREAD_COUNT = 0
+    WHERE USER_ID = $1 AND CONVERSATION_TYPE = 'direct' AND CONVERSATION_ID = $2
+)
 UPDATE PUBLIC.MESSAGE
     SET READ = TRUE
 WHERE RECEIVER_ID = $1
@@ -18,7 +22,8 @@ SELECT
     LAST_MESSAGE,
     CASE WHEN RECEIVER_ID != $1 THEN 0 ELSE UNREAD_COUNT END,
     PUBLIC.USER.USERNAME,
-    DELETED
+    DELETED,
+    PUBLIC.USER_PROFILE.*
 FROM PUBLIC.UNREAD_MESSAGE_CONTENT
     JOIN PUBLIC.USER ON 
         CASE WHEN LEAST(RECEIVER_ID, SENDER_ID) = $1 THEN
@@ -26,15 +31,41 @@ FROM PUBLIC.UNREAD_MESSAGE_CONTENT
         ELSE
             LEAST(RECEIVER_ID, SENDER_ID) = PUBLIC.USER.ID
         END
+    JOIN PUBLIC.USER_PROFILE ON PUBLIC.USER_PROFILE.USER_ID = PUBLIC.USER.ID
 WHERE $1 IN (SENDER_ID, RECEIVER_ID)
 ORDER BY SENT_AT DESC
 ;
 ";
 pub const CREATE_MESSAGE_STMT: &str = "
+WITH M AS (
     INSERT INTO PUBLIC.MESSAGE (SENDER_ID, RECEIVER_ID, CONTENT)
-    VALUES ($1, $2, $3) RETURNING *;
+    VALUES ($1, $2, $3) RETURNING *
+), S AS (
+    INSERT INTO PUBLIC.CONVERSATION_SUMMARY
+        (USER_ID, CONVERSATION_TYPE, CONVERSATION_ID, LAST_MESSAGE_ID, LAST_SENDER_ID,
+        LAST_MESSAGE_PREVIEW, LAST_MESSAGE_DELETED, LAST_ACTIVITY_AT, UNREAD_COUNT)
+    SELECT P.USER_ID, 'direct', P.CONTACT_ID, M.ID, M.SENDER_ID,
+        LEFT(M.CONTENT, 200), M.DELETED, M.SENT_AT, P.UNREAD
+    FROM M
+        CROSS JOIN LATERAL (
+            VALUES (M.SENDER_ID, M.RECEIVER_ID, 0), (M.RECEIVER_ID, M.SENDER_ID, 1)
+        ) AS P (USER_ID, CONTACT_ID, UNREAD)
+    WHERE P.UNREAD = 0 OR M.SENDER_ID <> M.RECEIVER_ID
+    ON CONFLICT (USER_ID, CONVERSATION_TYPE, CONVERSATION_ID) DO UPDATE SET
+        LAST_MESSAGE_ID = EXCLUDED.LAST_MESSAGE_ID,
+        LAST_SENDER_ID = EXCLUDED.LAST_SENDER_ID,
+        LAST_MESSAGE_PREVIEW = EXCLUDED.LAST_MESSAGE_PREVIEW,
+        LAST_MESSAGE_DELETED = EXCLUDED.LAST_MESSAGE_DELETED,
+        LAST_ACTIVITY_AT = EXCLUDED.LAST_ACTIVITY_AT,
+        UNREAD_COUNT = CONVERSATION_SUMMARY.UNREAD_COUNT + EXCLUDED.UNREAD_COUNT
+)
+SELECT * FROM M;
 ";
 pub const DELETE_MESSAGE_STMT: &str = "
+WITH S AS (
+    UPDATE PUBLIC.CONVERSATION_SUMMARY SET LAST_MESSAGE_DELETED = TRUE
+    WHERE CONVERSATION_TYPE = 'direct' AND LAST_MESSAGE_ID = $1
+)
 UPDATE PUBLIC.MESSAGE
     SET DELETED = TRUE
 WHERE ID = $1;
@@ -45,6 +76,10 @@ pub const FIND_MESSAGE_BY_ID_STMT: &str = "
     LIMIT 1
 ";
 pub const EDIT_MESSAGE_BY_ID_STMT: &str = "
+WITH S AS (
+    UPDATE PUBLIC.CONVERSATION_SUMMARY SET LAST_MESSAGE_PREVIEW = LEFT($2, 200)
+    WHERE CONVERSATION_TYPE = 'direct' AND LAST_MESSAGE_ID = $1
+)
 UPDATE PUBLIC.MESSAGE
     SET CONTENT = $2, EDITED = TRUE
 WHERE ID = $1 RETURNING *
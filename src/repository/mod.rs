@@ -1,15 +1,39 @@
+mod access_token;
+mod admin;
 mod attachment;
 pub mod auth;
+mod block;
+mod bus;
 pub mod contact;
+mod conversation_setting;
+mod conversation_summary;
+mod digest;
 pub mod group;
 pub mod message;
+mod oidc;
+mod presence;
+mod push;
 pub mod session;
 pub mod user;
+mod webauthn;
+mod ws_ticket;
 
+pub use access_token::*;
+pub use admin::*;
 pub use attachment::*;
 pub use auth::*;
+pub use block::*;
+pub use bus::*;
 pub use contact::*;
+pub use conversation_setting::*;
+pub use conversation_summary::*;
+pub use digest::*;
 pub use group::*;
 pub use message::*;
+pub use oidc::*;
+pub use presence::*;
+pub use push::*;
 pub use session::*;
 pub use user::*;
+pub use webauthn::*;
+pub use ws_ticket::*;
@@ -0,0 +1,9 @@
+use chrono::NaiveDateTime;
+
+#[derive(sqlx::FromRow)]
+pub struct OidcLoginAttemptRepositoryModel {
+    pub state: String,
+    pub code_verifier: String,
+    pub nonce: String,
+    pub created_at: NaiveDateTime,
+}
@@ -0,0 +1,93 @@
+use sqlx::Pool;
+use sqlx::Postgres;
+
+use super::OidcLoginAttemptRepositoryModel;
+use super::CREATE_OIDC_LOGIN_ATTEMPT_STMT;
+use super::DELETE_EXPIRED_OIDC_LOGIN_ATTEMPT_STMT;
+use super::FIND_USER_ID_BY_OIDC_IDENTITY_STMT;
+use super::LINK_OIDC_IDENTITY_STMT;
+use super::TAKE_OIDC_LOGIN_ATTEMPT_STMT;
+
+#[derive(Clone)]
+pub struct OidcRepository {
+    conn: Pool<Postgres>,
+}
+
+impl OidcRepository {
+    pub fn new(conn: Pool<Postgres>) -> Self {
+        OidcRepository { conn }
+    }
+
+    pub async fn create_login_attempt(
+        &self,
+        state: String,
+        code_verifier: String,
+        nonce: String,
+    ) -> Result<bool, String> {
+        sqlx::query(CREATE_OIDC_LOGIN_ATTEMPT_STMT)
+            .bind(state)
+            .bind(code_verifier)
+            .bind(nonce)
+            .execute(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.rows_affected() == 1)
+    }
+
+    /// Removes and returns a login attempt younger than `max_age_secs`, so every
+    /// `state` can only complete a login once.
+    pub async fn take_login_attempt(
+        &self,
+        state: String,
+        max_age_secs: f64,
+    ) -> Result<Option<OidcLoginAttemptRepositoryModel>, String> {
+        sqlx::query_as::<_, OidcLoginAttemptRepositoryModel>(TAKE_OIDC_LOGIN_ATTEMPT_STMT)
+            .bind(state)
+            .bind(max_age_secs)
+            .fetch_optional(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    pub async fn delete_expired_login_attempts(
+        &self,
+        max_age_secs: f64,
+    ) -> Result<u64, String> {
+        sqlx::query(DELETE_EXPIRED_OIDC_LOGIN_ATTEMPT_STMT)
+            .bind(max_age_secs)
+            .execute(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.rows_affected())
+    }
+
+    pub async fn find_user_id_by_identity(
+        &self,
+        issuer: String,
+        subject: String,
+    ) -> Result<Option<i32>, String> {
+        sqlx::query_as::<_, (i32,)>(FIND_USER_ID_BY_OIDC_IDENTITY_STMT)
+            .bind(issuer)
+            .bind(subject)
+            .fetch_optional(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.map(|t| t.0))
+    }
+
+    pub async fn link_identity(
+        &self,
+        issuer: String,
+        subject: String,
+        user_id: i32,
+    ) -> Result<bool, String> {
+        sqlx::query(LINK_OIDC_IDENTITY_STMT)
+            .bind(issuer)
+            .bind(subject)
+            .bind(user_id)
+            .execute(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.rows_affected() == 1)
+    }
+}
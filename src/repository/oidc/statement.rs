@@ -0,0 +1,18 @@
+pub const CREATE_OIDC_LOGIN_ATTEMPT_STMT: &str = "
+INSERT INTO PUBLIC.OIDC_LOGIN_ATTEMPT (STATE, CODE_VERIFIER, NONCE) VALUES ($1, $2, $3);
+";
+pub const TAKE_OIDC_LOGIN_ATTEMPT_STMT: &str = "
+DELETE FROM PUBLIC.OIDC_LOGIN_ATTEMPT
+WHERE STATE = $1 AND CREATED_AT > CURRENT_TIMESTAMP - MAKE_INTERVAL(SECS => $2)
+RETURNING *;
+";
+pub const DELETE_EXPIRED_OIDC_LOGIN_ATTEMPT_STMT: &str = "
+DELETE FROM PUBLIC.OIDC_LOGIN_ATTEMPT
+WHERE CREATED_AT <= CURRENT_TIMESTAMP - MAKE_INTERVAL(SECS => $1);
+";
+pub const FIND_USER_ID_BY_OIDC_IDENTITY_STMT: &str = "
+SELECT USER_ID FROM PUBLIC.USER_OIDC_IDENTITY WHERE ISSUER = $1 AND SUBJECT = $2;
+";
+pub const LINK_OIDC_IDENTITY_STMT: &str = "
+INSERT INTO PUBLIC.USER_OIDC_IDENTITY (ISSUER, SUBJECT, USER_ID) VALUES ($1, $2, $3);
+";
@@ -0,0 +1,5 @@
+#[derive(sqlx::FromRow)]
+pub struct PresenceVisibilityRepositoryModel {
+    pub user_id: i32,
+    pub presence_visibility: String,
+}
@@ -0,0 +1,98 @@
+use chrono::NaiveDateTime;
+use sqlx::Pool;
+use sqlx::Postgres;
+
+use super::PresenceVisibilityRepositoryModel;
+use super::FIND_CONVERSATION_PARTNER_IDS_STMT;
+use super::FIND_PRESENCE_STATE_STMT;
+use super::FIND_PRESENCE_VISIBILITY_STMT;
+use super::UPDATE_LAST_SEEN_STMT;
+use super::UPDATE_PRESENCE_STATE_STMT;
+use super::UPDATE_PRESENCE_VISIBILITY_STMT;
+
+#[derive(Clone)]
+pub struct PresenceRepository {
+    conn: Pool<Postgres>,
+}
+
+impl PresenceRepository {
+    pub fn new(conn: Pool<Postgres>) -> Self {
+        Self { conn }
+    }
+
+    pub async fn update_last_seen(
+        &self,
+        user_id: i32,
+    ) -> Result<Option<NaiveDateTime>, String> {
+        sqlx::query_as::<_, (Option<NaiveDateTime>,)>(UPDATE_LAST_SEEN_STMT)
+            .bind(user_id)
+            .fetch_optional(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.and_then(|t| t.0))
+    }
+
+    pub async fn find_presence_visibility(
+        &self,
+        user_ids: Vec<i32>,
+    ) -> Result<Vec<PresenceVisibilityRepositoryModel>, String> {
+        sqlx::query_as::<_, PresenceVisibilityRepositoryModel>(FIND_PRESENCE_VISIBILITY_STMT)
+            .bind(user_ids)
+            .fetch_all(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// Users sharing a direct conversation or a group with `user_id`.
+    pub async fn find_conversation_partner_ids(
+        &self,
+        user_id: i32,
+    ) -> Result<Vec<i32>, String> {
+        sqlx::query_as::<_, (i32,)>(FIND_CONVERSATION_PARTNER_IDS_STMT)
+            .bind(user_id)
+            .fetch_all(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.iter().map(|t| t.0).collect())
+    }
+
+    pub async fn find_presence_state(
+        &self,
+        user_id: i32,
+    ) -> Result<Option<String>, String> {
+        sqlx::query_as::<_, (String,)>(FIND_PRESENCE_STATE_STMT)
+            .bind(user_id)
+            .fetch_optional(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.map(|t| t.0))
+    }
+
+    pub async fn update_presence_state(
+        &self,
+        user_id: i32,
+        state: String,
+    ) -> Result<bool, String> {
+        sqlx::query(UPDATE_PRESENCE_STATE_STMT)
+            .bind(user_id)
+            .bind(state)
+            .execute(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.rows_affected() == 1)
+    }
+
+    pub async fn update_visibility(
+        &self,
+        user_id: i32,
+        visibility: String,
+    ) -> Result<bool, String> {
+        sqlx::query(UPDATE_PRESENCE_VISIBILITY_STMT)
+            .bind(user_id)
+            .bind(visibility)
+            .execute(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.rows_affected() == 1)
+    }
+}
@@ -0,0 +1,18 @@
+pub const UPDATE_LAST_SEEN_STMT: &str = "
+UPDATE PUBLIC.USER SET LAST_SEEN_AT = CURRENT_TIMESTAMP WHERE ID = $1 RETURNING LAST_SEEN_AT;
+";
+pub const FIND_PRESENCE_VISIBILITY_STMT: &str = "
+SELECT ID AS USER_ID, PRESENCE_VISIBILITY FROM PUBLIC.USER WHERE ID = ANY($1);
+";
+pub const FIND_CONVERSATION_PARTNER_IDS_STMT: &str = "
+SELECT PARTNER_ID FROM PUBLIC.CONVERSATION_PARTNER WHERE USER_ID = $1;
+";
+pub const FIND_PRESENCE_STATE_STMT: &str = "
+SELECT PRESENCE_STATE FROM PUBLIC.USER WHERE ID = $1;
+";
+pub const UPDATE_PRESENCE_STATE_STMT: &str = "
+UPDATE PUBLIC.USER SET PRESENCE_STATE = $2 WHERE ID = $1;
+";
+pub const UPDATE_PRESENCE_VISIBILITY_STMT: &str = "
+UPDATE PUBLIC.USER SET PRESENCE_VISIBILITY = $2 WHERE ID = $1;
+";
@@ -0,0 +1,29 @@
+use chrono::NaiveDateTime;
+
+#[derive(sqlx::FromRow)]
+pub struct PushSubscriptionRepositoryModel {
+    pub id: i32,
+    pub user_id: i32,
+    pub endpoint: String,
+    pub p256dh: String,
+    pub auth: String,
+    pub user_agent: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+/// A subscription to push to, with the presence state its user chose.
+#[derive(sqlx::FromRow)]
+pub struct PushTargetRepositoryModel {
+    pub id: i32,
+    pub user_id: i32,
+    pub endpoint: String,
+    pub p256dh: String,
+    pub auth: String,
+    pub presence_state: String,
+}
+
+#[derive(sqlx::FromRow)]
+pub struct PushUsernameRepositoryModel {
+    pub id: i32,
+    pub username: String,
+}
@@ -0,0 +1,118 @@
+use sqlx::Pool;
+use sqlx::Postgres;
+
+use super::PushSubscriptionRepositoryModel;
+use super::PushTargetRepositoryModel;
+use super::PushUsernameRepositoryModel;
+use super::DELETE_GONE_PUSH_SUBSCRIPTION_STMT;
+use super::DELETE_PUSH_SUBSCRIPTION_STMT;
+use super::FIND_PUSH_GROUP_NAME_STMT;
+use super::FIND_PUSH_SUBSCRIPTIONS_STMT;
+use super::FIND_PUSH_TARGETS_STMT;
+use super::FIND_PUSH_USERNAMES_STMT;
+use super::UPSERT_PUSH_SUBSCRIPTION_STMT;
+
+#[derive(Clone)]
+pub struct PushRepository {
+    conn: Pool<Postgres>,
+}
+
+impl PushRepository {
+    pub fn new(conn: Pool<Postgres>) -> Self {
+        PushRepository { conn }
+    }
+
+    /// Stores a subscription, taking it over when its endpoint was registered before,
+    /// e.g. by another user of the same browser.
+    pub async fn upsert_subscription(
+        &self,
+        user_id: i32,
+        endpoint: String,
+        p256dh: String,
+        auth: String,
+        user_agent: Option<String>,
+    ) -> Result<PushSubscriptionRepositoryModel, String> {
+        sqlx::query_as::<_, PushSubscriptionRepositoryModel>(UPSERT_PUSH_SUBSCRIPTION_STMT)
+            .bind(user_id)
+            .bind(endpoint)
+            .bind(p256dh)
+            .bind(auth)
+            .bind(user_agent)
+            .fetch_one(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    pub async fn find_subscriptions(
+        &self,
+        user_id: i32,
+    ) -> Result<Vec<PushSubscriptionRepositoryModel>, String> {
+        sqlx::query_as::<_, PushSubscriptionRepositoryModel>(FIND_PUSH_SUBSCRIPTIONS_STMT)
+            .bind(user_id)
+            .fetch_all(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// The subscriptions of the given users, leaving out suspended users.
+    pub async fn find_targets(
+        &self,
+        user_ids: Vec<i32>,
+    ) -> Result<Vec<PushTargetRepositoryModel>, String> {
+        sqlx::query_as::<_, PushTargetRepositoryModel>(FIND_PUSH_TARGETS_STMT)
+            .bind(user_ids)
+            .fetch_all(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    pub async fn find_usernames(
+        &self,
+        user_ids: Vec<i32>,
+    ) -> Result<Vec<PushUsernameRepositoryModel>, String> {
+        sqlx::query_as::<_, PushUsernameRepositoryModel>(FIND_PUSH_USERNAMES_STMT)
+            .bind(user_ids)
+            .fetch_all(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    pub async fn find_group_name(
+        &self,
+        group_id: i32,
+    ) -> Result<Option<String>, String> {
+        sqlx::query_as::<_, (String,)>(FIND_PUSH_GROUP_NAME_STMT)
+            .bind(group_id)
+            .fetch_optional(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.map(|t| t.0))
+    }
+
+    pub async fn delete_subscription(
+        &self,
+        subscription_id: i32,
+        user_id: i32,
+    ) -> Result<bool, String> {
+        sqlx::query(DELETE_PUSH_SUBSCRIPTION_STMT)
+            .bind(subscription_id)
+            .bind(user_id)
+            .execute(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.rows_affected() == 1)
+    }
+
+    /// Removes a subscription the push service reported as expired or unsubscribed.
+    pub async fn delete_gone_subscription(
+        &self,
+        subscription_id: i32,
+    ) -> Result<bool, String> {
+        sqlx::query(DELETE_GONE_PUSH_SUBSCRIPTION_STMT)
+            .bind(subscription_id)
+            .execute(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.rows_affected() == 1)
+    }
+}
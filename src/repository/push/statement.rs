@@ -0,0 +1,29 @@
+pub const UPSERT_PUSH_SUBSCRIPTION_STMT: &str = "
+INSERT INTO PUBLIC.PUSH_SUBSCRIPTION (USER_ID, ENDPOINT, P256DH, AUTH, USER_AGENT)
+VALUES ($1, $2, $3, $4, $5)
+ON CONFLICT (ENDPOINT) DO UPDATE
+SET USER_ID = EXCLUDED.USER_ID, P256DH = EXCLUDED.P256DH, AUTH = EXCLUDED.AUTH,
+    USER_AGENT = EXCLUDED.USER_AGENT, CREATED_AT = CURRENT_TIMESTAMP
+RETURNING *;
+";
+pub const FIND_PUSH_SUBSCRIPTIONS_STMT: &str = "
+SELECT * FROM PUBLIC.PUSH_SUBSCRIPTION WHERE USER_ID = $1 ORDER BY CREATED_AT DESC;
+";
+pub const FIND_PUSH_TARGETS_STMT: &str = "
+SELECT S.ID, S.USER_ID, S.ENDPOINT, S.P256DH, S.AUTH, U.PRESENCE_STATE
+FROM PUBLIC.PUSH_SUBSCRIPTION S
+JOIN PUBLIC.USER U ON U.ID = S.USER_ID
+WHERE S.USER_ID = ANY($1) AND U.SUSPENDED_AT IS NULL;
+";
+pub const FIND_PUSH_USERNAMES_STMT: &str = "
+SELECT ID, USERNAME FROM PUBLIC.USER WHERE ID = ANY($1);
+";
+pub const FIND_PUSH_GROUP_NAME_STMT: &str = "
+SELECT NAME FROM PUBLIC.GROUP WHERE ID = $1;
+";
+pub const DELETE_PUSH_SUBSCRIPTION_STMT: &str = "
+DELETE FROM PUBLIC.PUSH_SUBSCRIPTION WHERE ID = $1 AND USER_ID = $2;
+";
+pub const DELETE_GONE_PUSH_SUBSCRIPTION_STMT: &str = "
+DELETE FROM PUBLIC.PUSH_SUBSCRIPTION WHERE ID = $1;
+";
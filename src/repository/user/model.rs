@@ -1,5 +1,25 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
 #[derive(sqlx::FromRow)]
 pub struct UserAvatar {
     pub user_id: i32,
     pub avatar_image: Vec<u8>,
 }
+
+#[derive(sqlx::FromRow)]
+pub struct PublicUserRepositoryModel {
+    pub id: i32,
+    pub username: String,
+}
+
+/// A row of the `user_profile` view, which already drops an expired status.
+#[derive(sqlx::FromRow, Serialize, Clone)]
+pub struct UserProfileRepositoryModel {
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub pronouns: Option<String>,
+    pub status_text: Option<String>,
+    pub status_emoji: Option<String>,
+    pub status_expires_at: Option<NaiveDateTime>,
+}
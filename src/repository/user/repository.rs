@@ -1,5 +1,12 @@
+use super::PublicUserRepositoryModel;
 use super::UserAvatar;
+use super::UserProfileRepositoryModel;
+use super::FIND_PUBLIC_USER_STMT;
+use super::FIND_USER_PROFILE_STMT;
 use super::GET_USER_PROFILE;
+use super::SEARCH_USERS_STMT;
+use super::UPDATE_DISCOVERABLE_STMT;
+use super::UPDATE_USER_PROFILE_STMT;
 use super::USER_PROFILE_UPSERT_STATEMENT;
 use sqlx::Pool;
 use sqlx::Postgres;
@@ -38,4 +45,83 @@ impl UserRepository {
             .await
             .map_err(|e| e.to_string())
     }
+
+    pub async fn search_users(
+        &self,
+        searcher_id: i32,
+        term: String,
+        prefix_pattern: String,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<PublicUserRepositoryModel>, String> {
+        sqlx::query_as::<_, PublicUserRepositoryModel>(SEARCH_USERS_STMT)
+            .bind(searcher_id)
+            .bind(term)
+            .bind(prefix_pattern)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    pub async fn find_public_user(
+        &self,
+        viewer_id: i32,
+        user_id: i32,
+    ) -> Result<Option<PublicUserRepositoryModel>, String> {
+        sqlx::query_as::<_, PublicUserRepositoryModel>(FIND_PUBLIC_USER_STMT)
+            .bind(viewer_id)
+            .bind(user_id)
+            .fetch_optional(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    pub async fn update_discoverable(
+        &self,
+        user_id: i32,
+        discoverable: bool,
+    ) -> Result<bool, String> {
+        sqlx::query(UPDATE_DISCOVERABLE_STMT)
+            .bind(user_id)
+            .bind(discoverable)
+            .execute(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.rows_affected() == 1)
+    }
+
+    pub async fn find_profile(
+        &self,
+        user_id: i32,
+    ) -> Result<Option<UserProfileRepositoryModel>, String> {
+        sqlx::query_as::<_, UserProfileRepositoryModel>(FIND_USER_PROFILE_STMT)
+            .bind(user_id)
+            .fetch_optional(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// Replaces every profile field. The status expires `status_expires_in_mins`
+    /// minutes from now, or never when it is `None`.
+    pub async fn update_profile(
+        &self,
+        user_id: i32,
+        profile: UserProfileRepositoryModel,
+        status_expires_in_mins: Option<i64>,
+    ) -> Result<bool, String> {
+        sqlx::query(UPDATE_USER_PROFILE_STMT)
+            .bind(user_id)
+            .bind(profile.display_name)
+            .bind(profile.bio)
+            .bind(profile.pronouns)
+            .bind(profile.status_text)
+            .bind(profile.status_emoji)
+            .bind(status_expires_in_mins)
+            .execute(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.rows_affected() == 1)
+    }
 }
@@ -7,3 +7,50 @@ pub const USER_PROFILE_UPSERT_STATEMENT: &str = "
 pub const GET_USER_PROFILE: &str = "
     SELECT * FROM PUBLIC.USER_AVATAR WHERE USER_ID = $1
 ";
+
+/// Users visible to `$1`: active, not blocked in either direction. `$2` is the
+/// lowercased search term and `$3` the escaped `LIKE` prefix pattern for it.
+pub const SEARCH_USERS_STMT: &str = "
+    SELECT U.ID, U.USERNAME FROM PUBLIC.USER U
+    WHERE U.ID <> $1
+        AND U.SUSPENDED_AT IS NULL
+        AND U.DISCOVERABLE
+        AND NOT EXISTS (
+            SELECT 1 FROM PUBLIC.USER_BLOCK B
+            WHERE (B.BLOCKER_ID = $1 AND B.BLOCKED_ID = U.ID)
+                OR (B.BLOCKER_ID = U.ID AND B.BLOCKED_ID = $1)
+        )
+        AND (LOWER(U.USERNAME) LIKE $3 OR LOWER(U.USERNAME) % $2 OR LOWER(U.EMAIL) = $2)
+    ORDER BY LOWER(U.USERNAME) LIKE $3 DESC, SIMILARITY(LOWER(U.USERNAME), $2) DESC, U.USERNAME
+    LIMIT $4 OFFSET $5
+";
+
+pub const FIND_PUBLIC_USER_STMT: &str = "
+    SELECT U.ID, U.USERNAME FROM PUBLIC.USER U
+    WHERE U.ID = $2
+        AND U.SUSPENDED_AT IS NULL
+        AND NOT EXISTS (
+            SELECT 1 FROM PUBLIC.USER_BLOCK B
+            WHERE (B.BLOCKER_ID = $1 AND B.BLOCKED_ID = U.ID)
+                OR (B.BLOCKER_ID = U.ID AND B.BLOCKED_ID = $1)
+        )
+";
+
+pub const UPDATE_DISCOVERABLE_STMT: &str = "
+    UPDATE PUBLIC.USER SET DISCOVERABLE = $2 WHERE ID = $1
+";
+
+pub const FIND_USER_PROFILE_STMT: &str = "
+    SELECT * FROM PUBLIC.USER_PROFILE WHERE USER_ID = $1
+";
+
+pub const UPDATE_USER_PROFILE_STMT: &str = "
+    UPDATE PUBLIC.USER SET
+        DISPLAY_NAME = $2,
+        BIO = $3,
+        PRONOUNS = $4,
+        STATUS_TEXT = $5,
+        STATUS_EMOJI = $6,
+        STATUS_EXPIRES_AT = CURRENT_TIMESTAMP + $7 * INTERVAL '1 MINUTE'
+    WHERE ID = $1
+";
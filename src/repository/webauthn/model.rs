@@ -0,0 +1,22 @@
+use chrono::NaiveDateTime;
+
+#[derive(sqlx::FromRow, Clone)]
+pub struct WebauthnCredentialRepositoryModel {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub credential_id: String,
+    pub passkey: String,
+    pub sign_count: i64,
+    pub created_at: NaiveDateTime,
+    pub last_used_at: Option<NaiveDateTime>,
+}
+
+#[derive(sqlx::FromRow)]
+pub struct WebauthnCeremonyRepositoryModel {
+    pub id: String,
+    pub user_id: i32,
+    pub kind: String,
+    pub state: String,
+    pub created_at: NaiveDateTime,
+}
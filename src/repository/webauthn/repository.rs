@@ -0,0 +1,144 @@
+use sqlx::Pool;
+use sqlx::Postgres;
+
+use super::WebauthnCeremonyRepositoryModel;
+use super::WebauthnCredentialRepositoryModel;
+use super::CREATE_WEBAUTHN_CEREMONY_STMT;
+use super::CREATE_WEBAUTHN_CREDENTIAL_STMT;
+use super::DELETE_EXPIRED_WEBAUTHN_CEREMONY_STMT;
+use super::DELETE_WEBAUTHN_CREDENTIAL_STMT;
+use super::FIND_WEBAUTHN_CREDENTIALS_BY_USER_ID_STMT;
+use super::FIND_WEBAUTHN_CREDENTIAL_BY_CREDENTIAL_ID_STMT;
+use super::TAKE_WEBAUTHN_CEREMONY_STMT;
+use super::UPDATE_WEBAUTHN_CREDENTIAL_USAGE_STMT;
+
+#[derive(Clone)]
+pub struct WebauthnRepository {
+    conn: Pool<Postgres>,
+}
+
+impl WebauthnRepository {
+    pub fn new(conn: Pool<Postgres>) -> Self {
+        WebauthnRepository { conn }
+    }
+
+    pub async fn create_credential(
+        &self,
+        user_id: i32,
+        name: String,
+        credential_id: String,
+        passkey: String,
+    ) -> Result<WebauthnCredentialRepositoryModel, String> {
+        sqlx::query_as::<_, WebauthnCredentialRepositoryModel>(CREATE_WEBAUTHN_CREDENTIAL_STMT)
+            .bind(user_id)
+            .bind(name)
+            .bind(credential_id)
+            .bind(passkey)
+            .fetch_one(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    pub async fn find_credentials_by_user_id(
+        &self,
+        user_id: i32,
+    ) -> Result<Vec<WebauthnCredentialRepositoryModel>, String> {
+        sqlx::query_as::<_, WebauthnCredentialRepositoryModel>(
+            FIND_WEBAUTHN_CREDENTIALS_BY_USER_ID_STMT,
+        )
+        .bind(user_id)
+        .fetch_all(&self.conn)
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    pub async fn find_credential_by_credential_id(
+        &self,
+        credential_id: String,
+    ) -> Result<Option<WebauthnCredentialRepositoryModel>, String> {
+        sqlx::query_as::<_, WebauthnCredentialRepositoryModel>(
+            FIND_WEBAUTHN_CREDENTIAL_BY_CREDENTIAL_ID_STMT,
+        )
+        .bind(credential_id)
+        .fetch_optional(&self.conn)
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    pub async fn update_credential_usage(
+        &self,
+        id: i32,
+        passkey: String,
+        sign_count: i64,
+    ) -> Result<bool, String> {
+        sqlx::query(UPDATE_WEBAUTHN_CREDENTIAL_USAGE_STMT)
+            .bind(id)
+            .bind(passkey)
+            .bind(sign_count)
+            .execute(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.rows_affected() == 1)
+    }
+
+    pub async fn delete_credential(
+        &self,
+        id: i32,
+        user_id: i32,
+    ) -> Result<bool, String> {
+        sqlx::query(DELETE_WEBAUTHN_CREDENTIAL_STMT)
+            .bind(id)
+            .bind(user_id)
+            .execute(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.rows_affected() == 1)
+    }
+
+    pub async fn create_ceremony(
+        &self,
+        id: String,
+        user_id: i32,
+        kind: &str,
+        state: String,
+    ) -> Result<bool, String> {
+        sqlx::query(CREATE_WEBAUTHN_CEREMONY_STMT)
+            .bind(id)
+            .bind(user_id)
+            .bind(kind)
+            .bind(state)
+            .execute(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.rows_affected() == 1)
+    }
+
+    /// Removes and returns a ceremony of the given kind younger than `max_age_secs`,
+    /// so every challenge can only be answered once.
+    pub async fn take_ceremony(
+        &self,
+        id: String,
+        kind: &str,
+        max_age_secs: f64,
+    ) -> Result<Option<WebauthnCeremonyRepositoryModel>, String> {
+        sqlx::query_as::<_, WebauthnCeremonyRepositoryModel>(TAKE_WEBAUTHN_CEREMONY_STMT)
+            .bind(id)
+            .bind(kind)
+            .bind(max_age_secs)
+            .fetch_optional(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    pub async fn delete_expired_ceremonies(
+        &self,
+        max_age_secs: f64,
+    ) -> Result<u64, String> {
+        sqlx::query(DELETE_EXPIRED_WEBAUTHN_CEREMONY_STMT)
+            .bind(max_age_secs)
+            .execute(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.rows_affected())
+    }
+}
@@ -0,0 +1,33 @@
+pub const CREATE_WEBAUTHN_CREDENTIAL_STMT: &str = "
+INSERT INTO PUBLIC.WEBAUTHN_CREDENTIAL (USER_ID, NAME, CREDENTIAL_ID, PASSKEY)
+VALUES ($1, $2, $3, $4)
+RETURNING *;
+";
+pub const FIND_WEBAUTHN_CREDENTIALS_BY_USER_ID_STMT: &str = "
+SELECT * FROM PUBLIC.WEBAUTHN_CREDENTIAL
+WHERE USER_ID = $1
+ORDER BY CREATED_AT DESC;
+";
+pub const FIND_WEBAUTHN_CREDENTIAL_BY_CREDENTIAL_ID_STMT: &str = "
+SELECT * FROM PUBLIC.WEBAUTHN_CREDENTIAL WHERE CREDENTIAL_ID = $1;
+";
+pub const UPDATE_WEBAUTHN_CREDENTIAL_USAGE_STMT: &str = "
+UPDATE PUBLIC.WEBAUTHN_CREDENTIAL
+    SET PASSKEY = $2, SIGN_COUNT = $3, LAST_USED_AT = CURRENT_TIMESTAMP
+WHERE ID = $1;
+";
+pub const DELETE_WEBAUTHN_CREDENTIAL_STMT: &str = "
+DELETE FROM PUBLIC.WEBAUTHN_CREDENTIAL WHERE ID = $1 AND USER_ID = $2;
+";
+pub const CREATE_WEBAUTHN_CEREMONY_STMT: &str = "
+INSERT INTO PUBLIC.WEBAUTHN_CEREMONY (ID, USER_ID, KIND, STATE) VALUES ($1, $2, $3, $4);
+";
+pub const TAKE_WEBAUTHN_CEREMONY_STMT: &str = "
+DELETE FROM PUBLIC.WEBAUTHN_CEREMONY
+WHERE ID = $1 AND KIND = $2 AND CREATED_AT > CURRENT_TIMESTAMP - MAKE_INTERVAL(SECS => $3)
+RETURNING *;
+";
+pub const DELETE_EXPIRED_WEBAUTHN_CEREMONY_STMT: &str = "
+DELETE FROM PUBLIC.WEBAUTHN_CEREMONY
+WHERE CREATED_AT <= CURRENT_TIMESTAMP - MAKE_INTERVAL(SECS => $1);
+";
@@ -0,0 +1,10 @@
+use chrono::NaiveDateTime;
+
+#[derive(sqlx::FromRow)]
+pub struct WsTicketRepositoryModel {
+    pub ticket_hash: String,
+    pub user_id: i32,
+    pub token_expires_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
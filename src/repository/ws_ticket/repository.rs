@@ -0,0 +1,57 @@
+use chrono::NaiveDateTime;
+use sqlx::Pool;
+use sqlx::Postgres;
+
+use super::WsTicketRepositoryModel;
+use super::CREATE_WS_TICKET_STMT;
+use super::DELETE_EXPIRED_WS_TICKET_STMT;
+use super::TAKE_WS_TICKET_STMT;
+
+#[derive(Clone)]
+pub struct WsTicketRepository {
+    conn: Pool<Postgres>,
+}
+
+impl WsTicketRepository {
+    pub fn new(conn: Pool<Postgres>) -> Self {
+        WsTicketRepository { conn }
+    }
+
+    pub async fn create_ticket(
+        &self,
+        ticket_hash: String,
+        user_id: i32,
+        token_expires_at: NaiveDateTime,
+        ttl_secs: f64,
+    ) -> Result<bool, String> {
+        sqlx::query(CREATE_WS_TICKET_STMT)
+            .bind(ticket_hash)
+            .bind(user_id)
+            .bind(token_expires_at)
+            .bind(ttl_secs)
+            .execute(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.rows_affected() == 1)
+    }
+
+    /// Removes the ticket so it cannot be used twice.
+    pub async fn take_ticket(
+        &self,
+        ticket_hash: String,
+    ) -> Result<Option<WsTicketRepositoryModel>, String> {
+        sqlx::query_as::<_, WsTicketRepositoryModel>(TAKE_WS_TICKET_STMT)
+            .bind(ticket_hash)
+            .fetch_optional(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    pub async fn delete_expired_tickets(&self) -> Result<u64, String> {
+        sqlx::query(DELETE_EXPIRED_WS_TICKET_STMT)
+            .execute(&self.conn)
+            .await
+            .map_err(|e| e.to_string())
+            .map(|r| r.rows_affected())
+    }
+}
@@ -0,0 +1,12 @@
+pub const CREATE_WS_TICKET_STMT: &str = "
+INSERT INTO PUBLIC.WS_TICKET (TICKET_HASH, USER_ID, TOKEN_EXPIRES_AT, EXPIRES_AT)
+VALUES ($1, $2, $3, CURRENT_TIMESTAMP + MAKE_INTERVAL(SECS => $4));
+";
+pub const TAKE_WS_TICKET_STMT: &str = "
+DELETE FROM PUBLIC.WS_TICKET
+WHERE TICKET_HASH = $1 AND EXPIRES_AT > CURRENT_TIMESTAMP
+RETURNING *;
+";
+pub const DELETE_EXPIRED_WS_TICKET_STMT: &str = "
+DELETE FROM PUBLIC.WS_TICKET WHERE EXPIRES_AT <= CURRENT_TIMESTAMP;
+";
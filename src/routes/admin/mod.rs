@@ -0,0 +1,3 @@
+mod route;
+
+pub use route::*;
@@ -0,0 +1,196 @@
+use anyhow::anyhow;
+use axum::extract::rejection::JsonRejection;
+use axum::extract::rejection::QueryRejection;
+use axum::extract::Path;
+use axum::extract::Query;
+use axum::extract::State;
+use axum::routing::delete;
+use axum::routing::get;
+use axum::routing::post;
+use axum::routing::put;
+use axum::Json;
+use axum::Router;
+
+use crate::app::AppState;
+use crate::routes::AdminUser;
+use crate::routes::ServerResponse;
+use crate::routes::ServerResponse::*;
+use crate::service::AdminDeleteMessageSuccess;
+use crate::service::AdminUserModel;
+use crate::service::AdminUserQuery;
+use crate::service::AuditLogEntry;
+use crate::service::AuditLogQuery;
+use crate::service::ForcedPasswordReset;
+use crate::service::ServerStats;
+use crate::service::SuspendUserForm;
+use crate::service::SuspendUserSuccess;
+use crate::service::UnsuspendUserSuccess;
+use crate::service::UpdateRoleForm;
+use crate::service::UpdateRoleSuccess;
+
+pub fn admin_route(state: AppState) -> Router {
+    Router::new()
+        .route("/users", get(search_users))
+        .route("/users/:user_id", get(find_user))
+        .route("/users/:user_id/role", put(update_role))
+        .route("/users/:user_id/suspend", post(suspend_user))
+        .route("/users/:user_id/unsuspend", post(unsuspend_user))
+        .route("/users/:user_id/password-reset", post(force_password_reset))
+        .route("/messages/:message_id", delete(delete_direct_message))
+        .route("/group/messages/:message_id", delete(delete_group_message))
+        .route("/stats", get(server_stats))
+        .route("/audit-log", get(find_audit_log))
+        .with_state(state)
+}
+
+async fn search_users(
+    _: AdminUser,
+    State(state): State<AppState>,
+    query: Result<Query<AdminUserQuery>, QueryRejection>,
+) -> ServerResponse<Vec<AdminUserModel>> {
+    let Query(query) = match query {
+        Ok(q) => q,
+        Err(e) => return Failed(anyhow!(e.to_string())),
+    };
+    let res = state.admin_service.search_users(query).await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+async fn find_user(
+    _: AdminUser,
+    State(state): State<AppState>,
+    Path(user_id): Path<i32>,
+) -> ServerResponse<AdminUserModel> {
+    let res = state.admin_service.find_user(user_id).await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+async fn update_role(
+    AdminUser { user_id: admin_id }: AdminUser,
+    State(state): State<AppState>,
+    Path(user_id): Path<i32>,
+    body: Result<Json<UpdateRoleForm>, JsonRejection>,
+) -> ServerResponse<UpdateRoleSuccess> {
+    let Json(form) = match body {
+        Ok(payload) => payload,
+        Err(e) => return Failed(anyhow!(e.to_string())),
+    };
+    let res = state
+        .admin_service
+        .update_role(admin_id, user_id, form)
+        .await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+async fn suspend_user(
+    AdminUser { user_id: admin_id }: AdminUser,
+    State(state): State<AppState>,
+    Path(user_id): Path<i32>,
+    body: Result<Json<SuspendUserForm>, JsonRejection>,
+) -> ServerResponse<SuspendUserSuccess> {
+    let Json(form) = match body {
+        Ok(payload) => payload,
+        Err(e) => return Failed(anyhow!(e.to_string())),
+    };
+    let res = state
+        .admin_service
+        .suspend_user(admin_id, user_id, form)
+        .await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+async fn unsuspend_user(
+    AdminUser { user_id: admin_id }: AdminUser,
+    State(state): State<AppState>,
+    Path(user_id): Path<i32>,
+) -> ServerResponse<UnsuspendUserSuccess> {
+    let res = state.admin_service.unsuspend_user(admin_id, user_id).await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+async fn force_password_reset(
+    AdminUser { user_id: admin_id }: AdminUser,
+    State(state): State<AppState>,
+    Path(user_id): Path<i32>,
+) -> ServerResponse<ForcedPasswordReset> {
+    let res = state
+        .admin_service
+        .force_password_reset(admin_id, user_id)
+        .await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+async fn delete_direct_message(
+    AdminUser { user_id: admin_id }: AdminUser,
+    State(state): State<AppState>,
+    Path(message_id): Path<i32>,
+) -> ServerResponse<AdminDeleteMessageSuccess> {
+    let res = state
+        .admin_service
+        .delete_direct_message(admin_id, message_id)
+        .await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+async fn delete_group_message(
+    AdminUser { user_id: admin_id }: AdminUser,
+    State(state): State<AppState>,
+    Path(message_id): Path<i32>,
+) -> ServerResponse<AdminDeleteMessageSuccess> {
+    let res = state
+        .admin_service
+        .delete_group_message(admin_id, message_id)
+        .await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+async fn server_stats(
+    _: AdminUser,
+    State(state): State<AppState>,
+) -> ServerResponse<ServerStats> {
+    let res = state.admin_service.server_stats().await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+async fn find_audit_log(
+    _: AdminUser,
+    State(state): State<AppState>,
+    query: Result<Query<AuditLogQuery>, QueryRejection>,
+) -> ServerResponse<Vec<AuditLogEntry>> {
+    let Query(query) = match query {
+        Ok(q) => q,
+        Err(e) => return Failed(anyhow!(e.to_string())),
+    };
+    let res = state.admin_service.find_audit_log(query).await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
@@ -1,22 +1,41 @@
 use anyhow::anyhow;
 use axum::extract::rejection::JsonRejection;
+use axum::extract::rejection::QueryRejection;
+use axum::extract::Query;
 use axum::extract::State;
+use axum::http::header::COOKIE;
+use axum::http::header::SET_COOKIE;
+use axum::http::HeaderMap;
+use axum::response::IntoResponse;
+use axum::response::Redirect;
+use axum::response::Response;
 use axum::routing::get;
 use axum::routing::post;
 use axum::routing::put;
 use axum::Json;
 use axum::Router;
+use webauthn_rs::prelude::CreationChallengeResponse;
+use webauthn_rs::prelude::RequestChallengeResponse;
 
 use crate::app::AppState;
 use crate::routes::AuthorizedUser;
+use crate::routes::FailedResponse;
+use crate::routes::PasswordChangeUser;
 use crate::routes::ServerResponse;
 use crate::routes::ServerResponse::*;
 use crate::service::AuthenticationToken;
 use crate::service::ChangePasswordForm;
 use crate::service::ChangePasswordSuccess;
+use crate::service::FinishPasskeyLoginForm;
+use crate::service::FinishPasskeyRegistrationForm;
 use crate::service::LoginForm;
+use crate::service::OidcCallbackQuery;
+use crate::service::OidcService;
+use crate::service::PasskeyModel;
 use crate::service::RegisterForm;
 use crate::service::RegisterSuccess;
+use crate::service::StartPasskeyLoginForm;
+use crate::service::WebauthnChallenge;
 
 use super::ValidTokenSuccess;
 
@@ -26,6 +45,12 @@ pub fn auth_route(state: AppState) -> Router {
         .route("/register", post(register))
         .route("/change-password", put(change_password))
         .route("/valid-token", get(valid_token))
+        .route("/oidc/start", get(oidc_start))
+        .route("/oidc/callback", get(oidc_callback))
+        .route("/webauthn/register/start", post(passkey_register_start))
+        .route("/webauthn/register/finish", post(passkey_register_finish))
+        .route("/webauthn/login/start", post(passkey_login_start))
+        .route("/webauthn/login/finish", post(passkey_login_finish))
         .with_state(state)
 }
 
@@ -61,9 +86,12 @@ async fn register(
 
 async fn change_password(
     State(state): State<AppState>,
-    AuthorizedUser { user_id }: AuthorizedUser,
+    PasswordChangeUser { user_id, scopes }: PasswordChangeUser,
     body: Result<Json<ChangePasswordForm>, JsonRejection>,
 ) -> ServerResponse<ChangePasswordSuccess> {
+    if let Err(e) = scopes.require_session() {
+        return Failed(e.into());
+    }
     let Json(change_password_form) = match body {
         Ok(payload) => payload,
         Err(e) => return Failed(anyhow!(e.to_string())),
@@ -81,3 +109,107 @@ async fn change_password(
 async fn valid_token(_: AuthorizedUser) -> ServerResponse<ValidTokenSuccess> {
     Success(ValidTokenSuccess)
 }
+
+async fn oidc_start(State(state): State<AppState>) -> Response {
+    let res = state.oidc_service.start_login().await;
+    match res {
+        Ok(login) => (
+            [(SET_COOKIE, state.oidc_service.state_cookie(&login.state))],
+            Redirect::to(&login.authorization_url),
+        )
+            .into_response(),
+        Err(e) => FailedResponse(e).into_response(),
+    }
+}
+
+async fn oidc_callback(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    query: Result<Query<OidcCallbackQuery>, QueryRejection>,
+) -> Response {
+    let Query(query) = match query {
+        Ok(q) => q,
+        Err(e) => return FailedResponse(anyhow!(e.to_string())).into_response(),
+    };
+    let browser_state = headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .find_map(OidcService::state_from_cookies);
+    let res = state.oidc_service.finish_login(query, browser_state).await;
+    let clear_cookie = [(SET_COOKIE, state.oidc_service.clear_state_cookie())];
+    let AuthenticationToken(token) = match res {
+        Ok(t) => t,
+        Err(e) => return (clear_cookie, FailedResponse(e)).into_response(),
+    };
+    match state.oidc_service.post_login_redirect() {
+        Some(url) => (clear_cookie, Redirect::to(&format!("{url}#token={token}"))).into_response(),
+        None => (clear_cookie, Success(token)).into_response(),
+    }
+}
+
+async fn passkey_register_start(
+    State(state): State<AppState>,
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
+) -> ServerResponse<WebauthnChallenge<CreationChallengeResponse>> {
+    if let Err(e) = scopes.require_session() {
+        return Failed(e.into());
+    }
+    let res = state.webauthn_service.start_registration(user_id).await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+async fn passkey_register_finish(
+    State(state): State<AppState>,
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
+    body: Result<Json<FinishPasskeyRegistrationForm>, JsonRejection>,
+) -> ServerResponse<PasskeyModel> {
+    if let Err(e) = scopes.require_session() {
+        return Failed(e.into());
+    }
+    let Json(form) = match body {
+        Ok(payload) => payload,
+        Err(e) => return Failed(anyhow!(e.to_string())),
+    };
+    let res = state
+        .webauthn_service
+        .finish_registration(user_id, form)
+        .await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+async fn passkey_login_start(
+    State(state): State<AppState>,
+    body: Result<Json<StartPasskeyLoginForm>, JsonRejection>,
+) -> ServerResponse<WebauthnChallenge<RequestChallengeResponse>> {
+    let Json(form) = match body {
+        Ok(payload) => payload,
+        Err(e) => return Failed(anyhow!(e.to_string())),
+    };
+    let res = state.webauthn_service.start_login(form).await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+async fn passkey_login_finish(
+    State(state): State<AppState>,
+    body: Result<Json<FinishPasskeyLoginForm>, JsonRejection>,
+) -> ServerResponse<String> {
+    let Json(form) = match body {
+        Ok(payload) => payload,
+        Err(e) => return Failed(anyhow!(e.to_string())),
+    };
+    let res = state.webauthn_service.finish_login(form).await;
+    match res {
+        Ok(AuthenticationToken(token)) => Success(token),
+        Err(e) => Failed(e),
+    }
+}
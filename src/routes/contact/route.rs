@@ -1,15 +1,27 @@
+use anyhow::anyhow;
+use axum::extract::rejection::JsonRejection;
+use axum::extract::rejection::QueryRejection;
+use axum::extract::Path;
+use axum::extract::Query;
 use axum::extract::State;
 use axum::routing::get;
+use axum::routing::put;
+use axum::Json;
 use axum::Router;
 
 use crate::app::AppState;
 use crate::routes::AuthorizedUser;
 use crate::routes::ServerResponse;
 use crate::routes::ServerResponse::*;
+use crate::service::ConversationListQuery;
+use crate::service::ConversationSettingsModel;
+use crate::service::ConversationType;
 use crate::service::DirectContact;
 use crate::service::DirectConversation;
 use crate::service::GroupContact;
 use crate::service::GroupConversation;
+use crate::service::Scope;
+use crate::service::UpdateConversationSettingsForm;
 
 pub fn contact_route(state: AppState) -> Router {
     Router::new()
@@ -17,13 +29,24 @@ pub fn contact_route(state: AppState) -> Router {
         .route("/direct/recent", get(find_direct_conversation_for_user))
         .route("/group", get(find_group_contact_for_user))
         .route("/group/recent", get(find_group_conversation_for_user))
+        .route(
+            "/direct/:contact_id/settings",
+            put(update_direct_conversation_settings),
+        )
+        .route(
+            "/group/:group_id/settings",
+            put(update_group_conversation_settings),
+        )
         .with_state(state)
 }
 
 async fn find_direct_contact_for_user(
-    AuthorizedUser { user_id }: AuthorizedUser,
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
     State(state): State<AppState>,
 ) -> ServerResponse<Vec<DirectContact>> {
+    if let Err(e) = scopes.require(Scope::ContactsRead) {
+        return Failed(e.into());
+    }
     let res = state
         .contact_service
         .find_direct_contacts_for_user(user_id)
@@ -35,9 +58,12 @@ async fn find_direct_contact_for_user(
 }
 
 async fn find_group_contact_for_user(
-    AuthorizedUser { user_id }: AuthorizedUser,
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
     State(state): State<AppState>,
 ) -> ServerResponse<Vec<GroupContact>> {
+    if let Err(e) = scopes.require(Scope::ContactsRead) {
+        return Failed(e.into());
+    }
     let res = state
         .contact_service
         .find_group_contacts_for_user(user_id)
@@ -49,12 +75,20 @@ async fn find_group_contact_for_user(
 }
 
 async fn find_direct_conversation_for_user(
-    AuthorizedUser { user_id }: AuthorizedUser,
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
     State(state): State<AppState>,
+    query: Result<Query<ConversationListQuery>, QueryRejection>,
 ) -> ServerResponse<Vec<DirectConversation>> {
+    if let Err(e) = scopes.require(Scope::MessagesRead) {
+        return Failed(e.into());
+    }
+    let Query(query) = match query {
+        Ok(q) => q,
+        Err(e) => return Failed(anyhow!(e.to_string())),
+    };
     let res = state
         .contact_service
-        .find_direct_conversations_for_user(user_id)
+        .find_direct_conversations_for_user(user_id, query)
         .await;
 
     match res {
@@ -64,14 +98,68 @@ async fn find_direct_conversation_for_user(
 }
 
 async fn find_group_conversation_for_user(
-    AuthorizedUser { user_id }: AuthorizedUser,
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
     State(state): State<AppState>,
+    query: Result<Query<ConversationListQuery>, QueryRejection>,
 ) -> ServerResponse<Vec<GroupConversation>> {
+    if let Err(e) = scopes.require(Scope::MessagesRead) {
+        return Failed(e.into());
+    }
+    let Query(query) = match query {
+        Ok(q) => q,
+        Err(e) => return Failed(anyhow!(e.to_string())),
+    };
     let res = state
         .contact_service
-        .find_group_conversations_for_user(user_id)
+        .find_group_conversations_for_user(user_id, query)
+        .await;
+
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+async fn update_direct_conversation_settings(
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
+    Path(contact_id): Path<i32>,
+    State(state): State<AppState>,
+    body: Result<Json<UpdateConversationSettingsForm>, JsonRejection>,
+) -> ServerResponse<ConversationSettingsModel> {
+    if let Err(e) = scopes.require(Scope::MessagesWrite) {
+        return Failed(e.into());
+    }
+    let Json(form) = match body {
+        Ok(form) => form,
+        Err(e) => return Failed(e.into()),
+    };
+    let res = state
+        .conversation_service
+        .update_settings(user_id, ConversationType::Direct, contact_id, form)
         .await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
 
+async fn update_group_conversation_settings(
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
+    Path(group_id): Path<i32>,
+    State(state): State<AppState>,
+    body: Result<Json<UpdateConversationSettingsForm>, JsonRejection>,
+) -> ServerResponse<ConversationSettingsModel> {
+    if let Err(e) = scopes.require(Scope::MessagesWrite) {
+        return Failed(e.into());
+    }
+    let Json(form) = match body {
+        Ok(form) => form,
+        Err(e) => return Failed(e.into()),
+    };
+    let res = state
+        .conversation_service
+        .update_settings(user_id, ConversationType::Group, group_id, form)
+        .await;
     match res {
         Ok(r) => Success(r),
         Err(e) => Failed(e),
@@ -79,7 +167,7 @@ async fn find_group_conversation_for_user(
 }
 
 async fn add_contact(
-    AuthorizedUser { user_id }: AuthorizedUser,
+    AuthorizedUser { user_id, .. }: AuthorizedUser,
     State(state): State<AppState>
 ) -> ServerResponse<String> {
 
@@ -0,0 +1,42 @@
+use anyhow::anyhow;
+use axum::extract::rejection::QueryRejection;
+use axum::extract::Query;
+use axum::extract::State;
+use axum::routing::get;
+use axum::Router;
+
+use crate::app::AppState;
+use crate::routes::AuthorizedUser;
+use crate::routes::ServerResponse;
+use crate::routes::ServerResponse::*;
+use crate::service::ConversationPage;
+use crate::service::ConversationPageQuery;
+use crate::service::Scope;
+
+pub fn conversation_route(state: AppState) -> Router {
+    Router::new()
+        .route("/", get(find_conversations))
+        .with_state(state)
+}
+
+async fn find_conversations(
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
+    State(state): State<AppState>,
+    query: Result<Query<ConversationPageQuery>, QueryRejection>,
+) -> ServerResponse<ConversationPage> {
+    if let Err(e) = scopes.require(Scope::MessagesRead) {
+        return Failed(e.into());
+    }
+    let Query(query) = match query {
+        Ok(q) => q,
+        Err(e) => return Failed(anyhow!(e.to_string())),
+    };
+    let res = state
+        .conversation_service
+        .find_conversations(user_id, query)
+        .await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
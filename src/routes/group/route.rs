@@ -11,6 +11,7 @@ use crate::routes::ServerResponse;
 use crate::routes::ServerResponse::*;
 use crate::service::CreateGroupForm;
 use crate::service::GroupModel;
+use crate::service::Scope;
 
 pub fn group_route(state: AppState) -> Router {
     Router::new()
@@ -20,10 +21,13 @@ pub fn group_route(state: AppState) -> Router {
 }
 
 async fn create_group(
-    AuthorizedUser { user_id }: AuthorizedUser,
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
     State(state): State<AppState>,
     form: CreateGroupForm,
 ) -> ServerResponse<GroupModel> {
+    if let Err(e) = scopes.require(Scope::GroupsManage) {
+        return Failed(e.into());
+    }
     let res = state.group_service.create_group(user_id, form).await;
     match res {
         Ok(res) => Success(res),
@@ -0,0 +1,9 @@
+use axum::extract::State;
+use axum::Json;
+use jsonwebtoken::jwk::JwkSet;
+
+use crate::app::AppState;
+
+pub async fn jwks(State(state): State<AppState>) -> Json<JwkSet> {
+    Json(state.jwt_service.jwks())
+}
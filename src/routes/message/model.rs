@@ -8,6 +8,7 @@ use axum::response::Response;
 use serde::Deserialize;
 
 use crate::routes::FailedResponse;
+use crate::websocket::MessageAttachment;
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -55,3 +56,21 @@ where
         Ok(res)
     }
 }
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SendDirectMessageForm {
+    pub receiver_uid: i32,
+    pub message: String,
+    #[serde(default)]
+    pub attachments: Vec<MessageAttachment>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SendGroupMessageForm {
+    pub group_id: i32,
+    pub message: String,
+    #[serde(default)]
+    pub attachments: Vec<MessageAttachment>,
+}
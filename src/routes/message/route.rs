@@ -1,5 +1,7 @@
+use axum::extract::rejection::JsonRejection;
 use axum::extract::State;
 use axum::routing::get;
+use axum::Json;
 use axum::Router;
 
 use crate::app::AppState;
@@ -8,22 +10,30 @@ use crate::routes::ServerResponse;
 use crate::routes::ServerResponse::*;
 use crate::service::DirectMessageModel;
 use crate::service::GroupMessageModel;
+use crate::service::Scope;
+use crate::websocket::SentMessage;
+use crate::websocket::WsRequest;
 
 use super::GroupIdQuery;
 use super::ReceiverUidQuery;
+use super::SendDirectMessageForm;
+use super::SendGroupMessageForm;
 
 pub fn message_route(state: AppState) -> Router {
     Router::new()
-        .route("/", get(find_direct_message))
-        .route("/group", get(find_group_message))
+        .route("/", get(find_direct_message).post(send_direct_message))
+        .route("/group", get(find_group_message).post(send_group_message))
         .with_state(state)
 }
 
 pub async fn find_direct_message(
-    AuthorizedUser { user_id }: AuthorizedUser,
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
     ReceiverUidQuery { receiver_uid }: ReceiverUidQuery,
     State(state): State<AppState>,
 ) -> ServerResponse<Vec<DirectMessageModel>> {
+    if let Err(e) = scopes.require(Scope::MessagesRead) {
+        return Failed(e.into());
+    }
     let res = state
         .message_service
         .find_direct_message(user_id, receiver_uid)
@@ -35,10 +45,13 @@ pub async fn find_direct_message(
 }
 
 pub async fn find_group_message(
-    AuthorizedUser { user_id }: AuthorizedUser,
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
     GroupIdQuery { group_id }: GroupIdQuery,
     State(state): State<AppState>,
 ) -> ServerResponse<Vec<GroupMessageModel>> {
+    if let Err(e) = scopes.require(Scope::MessagesRead) {
+        return Failed(e.into());
+    }
     let res = state
         .message_service
         .find_group_message(user_id, group_id)
@@ -48,3 +61,57 @@ pub async fn find_group_message(
         Err(e) => Failed(e),
     }
 }
+
+pub async fn send_direct_message(
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
+    State(state): State<AppState>,
+    body: Result<Json<SendDirectMessageForm>, JsonRejection>,
+) -> ServerResponse<SentMessage> {
+    if let Err(e) = scopes.require(Scope::MessagesWrite) {
+        return Failed(e.into());
+    }
+    let Json(form) = match body {
+        Ok(form) => form,
+        Err(e) => return Failed(e.into()),
+    };
+    let request = WsRequest::SendMessage {
+        receiver_uid: form.receiver_uid,
+        message: form.message,
+        attachments: form.attachments,
+    };
+    let res = state
+        .session_factory
+        .send_message(user_id, request)
+        .await;
+    match res {
+        Ok(res) => Success(res),
+        Err(e) => Failed(e),
+    }
+}
+
+pub async fn send_group_message(
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
+    State(state): State<AppState>,
+    body: Result<Json<SendGroupMessageForm>, JsonRejection>,
+) -> ServerResponse<SentMessage> {
+    if let Err(e) = scopes.require(Scope::MessagesWrite) {
+        return Failed(e.into());
+    }
+    let Json(form) = match body {
+        Ok(form) => form,
+        Err(e) => return Failed(e.into()),
+    };
+    let request = WsRequest::SendGroupMessage {
+        group_id: form.group_id,
+        message: form.message,
+        attachments: form.attachments,
+    };
+    let res = state
+        .session_factory
+        .send_message(user_id, request)
+        .await;
+    match res {
+        Ok(res) => Success(res),
+        Err(e) => Failed(e),
+    }
+}
@@ -1,18 +1,24 @@
+mod admin;
 mod attachment;
 mod auth;
 mod contact;
+mod conversation;
 mod group;
 mod healthcheck;
+mod jwks;
 mod message;
 mod model;
 mod user;
 mod websocket;
 
+pub use admin::*;
 pub use attachment::*;
 pub use auth::*;
 pub use contact::*;
+pub use conversation::*;
 pub use group::*;
 pub use healthcheck::*;
+pub use jwks::*;
 pub use message::*;
 pub use model::*;
 pub use user::*;
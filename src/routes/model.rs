@@ -1,7 +1,3 @@
-use std::collections::BTreeMap;
-use std::time::SystemTime;
-use std::time::UNIX_EPOCH;
-
 use anyhow::anyhow;
 use axum::async_trait;
 use axum::extract::FromRequestParts;
@@ -12,17 +8,18 @@ use axum::http::StatusCode;
 use axum::response::IntoResponse;
 use axum::response::Response;
 use axum::Json;
-use hmac::digest::KeyInit;
-use hmac::Hmac;
-use jwt::VerifyWithKey;
 use serde::ser::SerializeStruct;
 use serde::Deserialize;
 use serde::Serialize;
-use sha2::Sha256;
 use thiserror::Error;
 
 use crate::app::AppState;
 use crate::repository::AttachmentFileType;
+use crate::service::AccessTokenService;
+use crate::service::JwtError;
+use crate::service::JwtService;
+use crate::service::Role;
+use crate::service::TokenScopes;
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -72,44 +69,89 @@ where
 }
 
 #[derive(Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct TokenQuery {
-    pub token: String,
+pub struct WebSocketAuthQuery {
+    pub ticket: Option<String>,
+    pub token: Option<String>,
 }
 
-#[async_trait]
-impl<S> FromRequestParts<S> for TokenQuery
-where
-    S: Send + Sync,
-{
-    type Rejection = Response;
-    async fn from_request_parts(
-        parts: &mut Parts,
-        _state: &S,
-    ) -> Result<Self, Self::Rejection> {
-        let Query(res): Query<TokenQuery> = Query::try_from_uri(&parts.uri).map_err(|_| {
-            FailedResponse(anyhow!("token query parameter missing")).into_response()
-        })?;
-        Ok(res)
-    }
-}
+const WEBSOCKET_BEARER_PROTOCOL_PREFIX: &str = "bearer.";
 
-pub struct AuthorizedUserFromTokenQuery {
-    pub user_id: i32,
+/// How a realtime connection is authenticated: with a ticket from
+/// `POST /api/ws/ticket`, a session token in `Authorization`, a `bearer.<token>`
+/// subprotocol or, if allowed, a `token` query parameter. Without any of them a
+/// websocket client must send an `AUTH` frame first.
+pub enum WebSocketAuth {
+    Authenticated { user_id: i32, token_expires_at: u64 },
+    FirstFrame,
 }
 
 #[async_trait]
-impl FromRequestParts<AppState> for AuthorizedUserFromTokenQuery {
+impl FromRequestParts<AppState> for WebSocketAuth {
     type Rejection = Response;
     async fn from_request_parts(
         parts: &mut Parts,
         state: &AppState,
     ) -> Result<Self, Self::Rejection> {
-        let TokenQuery { token } = TokenQuery::from_request_parts(parts, state).await?;
-        let secret = &state.env_jwt_secret;
-        let AuthorizedUser { user_id } =
-            AuthorizedUser::authenticate(&token, secret).map_err(|e| e.into_response())?;
-        Ok(AuthorizedUserFromTokenQuery { user_id })
+        let Query(query): Query<WebSocketAuthQuery> = Query::try_from_uri(&parts.uri)
+            .map_err(|e| FailedResponse(anyhow!(e.to_string())).into_response())?;
+        if let Some(ticket) = query.ticket {
+            let ticket = state
+                .ws_ticket_service
+                .redeem_ticket(&ticket)
+                .await
+                .map_err(|e| FailedResponse(e).into_response())?;
+            return Ok(WebSocketAuth::Authenticated {
+                user_id: ticket.user_id,
+                token_expires_at: ticket.token_expires_at,
+            });
+        }
+        let header_token = parts
+            .headers
+            .get("Authorization")
+            .and_then(|v| v.to_str().ok())
+            .or_else(|| {
+                parts
+                    .headers
+                    .get_all("Sec-WebSocket-Protocol")
+                    .iter()
+                    .filter_map(|v| v.to_str().ok())
+                    .flat_map(|v| v.split(','))
+                    .find_map(|p| p.trim().strip_prefix(WEBSOCKET_BEARER_PROTOCOL_PREFIX))
+            });
+        let token = match (header_token, query.token) {
+            (Some(token), _) => token.to_string(),
+            (None, Some(_)) if !state.ws_allow_query_token => {
+                return Err(FailedResponse(anyhow!(
+                    "token query parameter is disabled, use a websocket ticket instead"
+                ))
+                .into_response());
+            }
+            (None, Some(token)) => token,
+            (None, None) => return Ok(WebSocketAuth::FirstFrame),
+        };
+        let (user_id, token_expires_at) = Self::authenticate(&token, state)
+            .await
+            .map_err(|e| e.into_response())?;
+        Ok(WebSocketAuth::Authenticated {
+            user_id,
+            token_expires_at,
+        })
+    }
+}
+
+impl WebSocketAuth {
+    /// Authenticates a websocket with a session token, returning the user and the
+    /// `exp` claim the session expires with.
+    pub async fn authenticate(
+        token: &str,
+        state: &AppState,
+    ) -> Result<(i32, u64), TokenAuthenticationError> {
+        let claims = state
+            .jwt_service
+            .verify(token.trim_start_matches("Bearer "))?;
+        let user_id = claims.user_id()?;
+        AuthorizedUser::ensure_active(user_id, state).await?;
+        Ok((user_id, claims.exp))
     }
 }
 
@@ -228,8 +270,11 @@ impl IntoResponse for FailedResponse {
     }
 }
 
+/// An authenticated user whose account is active. Guests only get the scopes that
+/// read, whatever their token allows.
 pub struct AuthorizedUser {
     pub user_id: i32,
+    pub scopes: TokenScopes,
 }
 
 #[async_trait]
@@ -242,6 +287,24 @@ impl FromRequestParts<AppState> for AuthorizedUser
         parts: &mut Parts,
         state: &AppState,
     ) -> Result<Self, Self::Rejection> {
+        let AuthorizedUser { user_id, scopes } =
+            Self::from_authorization_header(parts, state).await?;
+        let role = Self::ensure_active(user_id, state).await?;
+        let scopes = if role.is_read_only() {
+            scopes.read_only()
+        } else {
+            scopes
+        };
+        Ok(AuthorizedUser { user_id, scopes })
+    }
+}
+
+impl AuthorizedUser {
+    /// Authenticates the request without checking the state of the account.
+    async fn from_authorization_header(
+        parts: &Parts,
+        state: &AppState,
+    ) -> Result<Self, TokenAuthenticationError> {
         let auth_header = parts.headers.get("Authorization");
         let auth_header = match auth_header {
             Some(value) => value,
@@ -252,40 +315,105 @@ impl FromRequestParts<AppState> for AuthorizedUser
             Err(_) => return Err(TokenAuthenticationError::InvalidToken),
         };
         let auth_token = auth_token.trim_start_matches("Bearer ");
-        Self::authenticate(&auth_token, &state.env_jwt_secret)
+        let user = if AccessTokenService::is_access_token(auth_token) {
+            Self::authenticate_access_token(auth_token, &state.access_token_service).await?
+        } else {
+            Self::authenticate(auth_token, &state.jwt_service)?
+        };
+        Ok(user)
     }
-}
 
-impl AuthorizedUser {
     fn authenticate(
         token: &str,
-        secret: &str,
+        jwt_service: &JwtService,
     ) -> Result<AuthorizedUser, TokenAuthenticationError> {
-        let key: Hmac<Sha256> = match Hmac::new_from_slice(secret.as_bytes()) {
-            Ok(key) => key,
-            Err(e) => return Err(TokenAuthenticationError::Other(e.into())),
-        };
-        let claims: BTreeMap<String, u64> = match token.verify_with_key(&key) {
-            Ok(claims) => claims,
-            Err(_) => return Err(TokenAuthenticationError::InvalidToken),
-        };
-        let (user_id, expiration) = match (claims.get("uid"), claims.get("expiration")) {
-            (Some(uid), Some(expiration)) => (uid, expiration),
-            _ => return Err(TokenAuthenticationError::InvalidToken),
-        };
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        if *expiration < now {
-            return Err(TokenAuthenticationError::TokenExpired);
+        let claims = jwt_service.verify(token)?;
+        let user_id = claims.user_id()?;
+        Ok(AuthorizedUser {
+            user_id,
+            scopes: TokenScopes::Session,
+        })
+    }
+
+    async fn ensure_active(
+        user_id: i32,
+        state: &AppState,
+    ) -> Result<Role, TokenAuthenticationError> {
+        state
+            .auth_service
+            .ensure_active(user_id)
+            .await
+            .map_err(TokenAuthenticationError::Other)
+    }
+
+    async fn authenticate_access_token(
+        token: &str,
+        access_token_service: &AccessTokenService,
+    ) -> Result<AuthorizedUser, TokenAuthenticationError> {
+        let res = access_token_service.authenticate(token).await;
+        match res {
+            Ok(Some(access_token)) => Ok(AuthorizedUser {
+                user_id: access_token.user_id,
+                scopes: TokenScopes::Scoped(access_token.scopes),
+            }),
+            Ok(None) => Err(TokenAuthenticationError::InvalidToken),
+            Err(e) => Err(TokenAuthenticationError::Other(e)),
         }
-        let user_id = match i32::try_from(*user_id) {
-            Ok(v) => v,
-            Err(_) => return Err(TokenAuthenticationError::InvalidToken),
-        };
+    }
+}
 
-        Ok(AuthorizedUser { user_id })
+/// An authenticated user who may still have to change the password an admin reset.
+/// Only the password change accepts it, every other route rejects such users.
+pub struct PasswordChangeUser {
+    pub user_id: i32,
+    pub scopes: TokenScopes,
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for PasswordChangeUser {
+    type Rejection = TokenAuthenticationError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let AuthorizedUser { user_id, scopes } =
+            AuthorizedUser::from_authorization_header(parts, state).await?;
+        state
+            .auth_service
+            .ensure_can_sign_in(user_id)
+            .await
+            .map_err(TokenAuthenticationError::Other)?;
+        Ok(PasswordChangeUser { user_id, scopes })
+    }
+}
+
+/// An authenticated user with the admin role. Admin routes cannot be reached with
+/// personal access tokens.
+pub struct AdminUser {
+    pub user_id: i32,
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for AdminUser {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let AuthorizedUser { user_id, scopes } = AuthorizedUser::from_request_parts(parts, state)
+            .await
+            .map_err(|e| e.into_response())?;
+        scopes
+            .require_session()
+            .map_err(|e| FailedResponse(e.into()).into_response())?;
+        state
+            .admin_service
+            .require_admin(user_id)
+            .await
+            .map_err(|e| FailedResponse(e).into_response())?;
+        Ok(AdminUser { user_id })
     }
 }
 
@@ -301,6 +429,16 @@ pub enum TokenAuthenticationError {
     Other(anyhow::Error),
 }
 
+impl From<JwtError> for TokenAuthenticationError {
+    fn from(value: JwtError) -> Self {
+        match value {
+            JwtError::TokenExpired => Self::TokenExpired,
+            JwtError::InvalidToken | JwtError::UnknownKey { .. } => Self::InvalidToken,
+            JwtError::Other(e) => Self::Other(e),
+        }
+    }
+}
+
 impl IntoResponse for TokenAuthenticationError {
     fn into_response(self) -> axum::response::Response {
         FailedResponse(self.into()).into_response()
@@ -1,7 +1,13 @@
+use anyhow::anyhow;
 use axum::body::Bytes;
 use axum::extract::rejection::JsonRejection;
+use axum::extract::rejection::QueryRejection;
 use axum::extract::Path;
+use axum::extract::Query;
 use axum::extract::State;
+use axum::http::header::USER_AGENT;
+use axum::http::HeaderMap;
+use axum::routing::delete;
 use axum::routing::get;
 use axum::routing::post;
 use axum::routing::put;
@@ -13,8 +19,33 @@ use crate::routes::AuthorizedUser;
 use crate::routes::ImageResponse;
 use crate::routes::ServerResponse;
 use crate::routes::ServerResponse::*;
+use crate::service::AccessTokenModel;
+use crate::service::BlockUserForm;
+use crate::service::BlockUserSuccess;
+use crate::service::BlockedUserModel;
+use crate::service::CreateAccessTokenForm;
+use crate::service::CreatePushSubscriptionForm;
+use crate::service::CreatedAccessToken;
+use crate::service::DeletePasskeySuccess;
+use crate::service::DeletePushSubscriptionSuccess;
+use crate::service::DigestPreference;
+use crate::service::PasskeyModel;
+use crate::service::PublicUserModel;
+use crate::service::PushSubscriptionModel;
+use crate::service::RevokeAccessTokenSuccess;
+use crate::service::Scope;
 use crate::service::SuccessfullyUpdateUser;
+use crate::service::UnblockUserSuccess;
+use crate::service::UnsubscribeDigestQuery;
+use crate::service::UnsubscribeDigestSuccess;
+use crate::service::UpdateDiscoverableForm;
+use crate::service::UpdatePresenceVisibilityForm;
+use crate::service::UpdatePresenceVisibilitySuccess;
+use crate::service::UpdateProfileForm;
 use crate::service::UserDetail;
+use crate::service::UserProfile;
+use crate::service::UserSearchQuery;
+use crate::service::VapidPublicKey;
 
 use super::ChangeUsernameForm;
 
@@ -22,16 +53,43 @@ pub fn user_route(state: AppState) -> Router {
     Router::new()
         .route("/details", get(find_user_details))
         .route("/details", put(update_username))
+        .route("/profile", put(update_profile))
+        .route("/discoverable", put(update_discoverable))
+        .route("/presence", put(update_presence_visibility))
+        .route("/search", get(search_users))
+        .route("/:user_id", get(find_public_user))
         .route("/avatar/:user_id", get(find_user_profile))
         .route("/avatar", post(update_avatar))
+        .route("/tokens", get(find_access_tokens))
+        .route("/tokens", post(create_access_token))
+        .route("/tokens/:token_id", delete(revoke_access_token))
+        .route("/passkeys", get(find_passkeys))
+        .route("/passkeys/:passkey_id", delete(delete_passkey))
+        .route("/blocks", get(find_blocked_users))
+        .route("/blocks/:user_id", put(block_user))
+        .route("/blocks/:user_id", delete(unblock_user))
+        .route("/push/vapid-key", get(find_vapid_public_key))
+        .route("/push/subscriptions", get(find_push_subscriptions))
+        .route("/push/subscriptions", post(create_push_subscription))
+        .route(
+            "/push/subscriptions/:subscription_id",
+            delete(delete_push_subscription),
+        )
+        .route("/digest", get(find_digest_preference))
+        .route("/digest", put(update_digest_preference))
+        .route("/digest/unsubscribe", get(unsubscribe_digest))
+        .route("/digest/unsubscribe", post(unsubscribe_digest))
         .with_state(state)
 }
 
 pub async fn update_username(
-    AuthorizedUser { user_id }: AuthorizedUser,
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
     State(state): State<AppState>,
     body: Result<Json<ChangeUsernameForm>, JsonRejection>,
 ) -> ServerResponse<SuccessfullyUpdateUser> {
+    if let Err(e) = scopes.require(Scope::ProfileWrite) {
+        return Failed(e.into());
+    }
     let Json(ChangeUsernameForm { username }) = match body {
         Ok(form) => form,
         Err(e) => return Failed(e.into()),
@@ -44,9 +102,12 @@ pub async fn update_username(
 }
 
 pub async fn find_user_details(
-    AuthorizedUser { user_id }: AuthorizedUser,
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
     State(state): State<AppState>,
 ) -> ServerResponse<UserDetail> {
+    if let Err(e) = scopes.require(Scope::ProfileRead) {
+        return Failed(e.into());
+    }
     let res = state.user_service.find_user_details(user_id).await;
 
     match res {
@@ -68,10 +129,13 @@ pub async fn find_user_profile(
 }
 
 pub async fn update_avatar(
-    AuthorizedUser { user_id }: AuthorizedUser,
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
     State(state): State<AppState>,
     body: Bytes,
 ) -> ServerResponse<SuccessfullyUpdateUser> {
+    if let Err(e) = scopes.require(Scope::ProfileWrite) {
+        return Failed(e.into());
+    }
     let profile_picture = body.into_iter().collect::<Vec<_>>();
     let res = state
         .user_service
@@ -82,3 +146,360 @@ pub async fn update_avatar(
         Err(e) => Failed(e),
     }
 }
+
+pub async fn find_access_tokens(
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
+    State(state): State<AppState>,
+) -> ServerResponse<Vec<AccessTokenModel>> {
+    if let Err(e) = scopes.require_session() {
+        return Failed(e.into());
+    }
+    let res = state
+        .access_token_service
+        .find_access_tokens_for_user(user_id)
+        .await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+pub async fn create_access_token(
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
+    State(state): State<AppState>,
+    body: Result<Json<CreateAccessTokenForm>, JsonRejection>,
+) -> ServerResponse<CreatedAccessToken> {
+    if let Err(e) = scopes.require_writable_session() {
+        return Failed(e.into());
+    }
+    let Json(form) = match body {
+        Ok(form) => form,
+        Err(e) => return Failed(e.into()),
+    };
+    let res = state
+        .access_token_service
+        .create_access_token(user_id, form)
+        .await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+pub async fn revoke_access_token(
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
+    Path(token_id): Path<i32>,
+    State(state): State<AppState>,
+) -> ServerResponse<RevokeAccessTokenSuccess> {
+    if let Err(e) = scopes.require_session() {
+        return Failed(e.into());
+    }
+    let res = state
+        .access_token_service
+        .revoke_access_token(user_id, token_id)
+        .await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+pub async fn find_passkeys(
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
+    State(state): State<AppState>,
+) -> ServerResponse<Vec<PasskeyModel>> {
+    if let Err(e) = scopes.require_session() {
+        return Failed(e.into());
+    }
+    let res = state.webauthn_service.find_passkeys_for_user(user_id).await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+pub async fn delete_passkey(
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
+    Path(passkey_id): Path<i32>,
+    State(state): State<AppState>,
+) -> ServerResponse<DeletePasskeySuccess> {
+    if let Err(e) = scopes.require_session() {
+        return Failed(e.into());
+    }
+    let res = state
+        .webauthn_service
+        .delete_passkey(user_id, passkey_id)
+        .await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+pub async fn find_blocked_users(
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
+    State(state): State<AppState>,
+) -> ServerResponse<Vec<BlockedUserModel>> {
+    if let Err(e) = scopes.require(Scope::ContactsRead) {
+        return Failed(e.into());
+    }
+    let res = state.block_service.find_blocked_users(user_id).await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+pub async fn block_user(
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
+    Path(blocked_id): Path<i32>,
+    State(state): State<AppState>,
+    body: Result<Json<BlockUserForm>, JsonRejection>,
+) -> ServerResponse<BlockUserSuccess> {
+    if let Err(e) = scopes.require_writable_session() {
+        return Failed(e.into());
+    }
+    let Json(form) = match body {
+        Ok(form) => form,
+        Err(e) => return Failed(e.into()),
+    };
+    let res = state
+        .block_service
+        .block_user(user_id, blocked_id, form)
+        .await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+pub async fn unblock_user(
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
+    Path(blocked_id): Path<i32>,
+    State(state): State<AppState>,
+) -> ServerResponse<UnblockUserSuccess> {
+    if let Err(e) = scopes.require_writable_session() {
+        return Failed(e.into());
+    }
+    let res = state.block_service.unblock_user(user_id, blocked_id).await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+pub async fn update_discoverable(
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
+    State(state): State<AppState>,
+    body: Result<Json<UpdateDiscoverableForm>, JsonRejection>,
+) -> ServerResponse<SuccessfullyUpdateUser> {
+    if let Err(e) = scopes.require(Scope::ProfileWrite) {
+        return Failed(e.into());
+    }
+    let Json(form) = match body {
+        Ok(form) => form,
+        Err(e) => return Failed(e.into()),
+    };
+    let res = state.user_service.update_discoverable(user_id, form).await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+pub async fn search_users(
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
+    State(state): State<AppState>,
+    query: Result<Query<UserSearchQuery>, QueryRejection>,
+) -> ServerResponse<Vec<PublicUserModel>> {
+    if let Err(e) = scopes.require(Scope::ContactsRead) {
+        return Failed(e.into());
+    }
+    let Query(query) = match query {
+        Ok(q) => q,
+        Err(e) => return Failed(anyhow!(e.to_string())),
+    };
+    let res = state.user_service.search_users(user_id, query).await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+pub async fn find_public_user(
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
+    Path(target_id): Path<i32>,
+    State(state): State<AppState>,
+) -> ServerResponse<PublicUserModel> {
+    if let Err(e) = scopes.require(Scope::ContactsRead) {
+        return Failed(e.into());
+    }
+    let res = state
+        .user_service
+        .find_public_user(user_id, target_id)
+        .await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+pub async fn update_profile(
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
+    State(state): State<AppState>,
+    body: Result<Json<UpdateProfileForm>, JsonRejection>,
+) -> ServerResponse<UserProfile> {
+    if let Err(e) = scopes.require(Scope::ProfileWrite) {
+        return Failed(e.into());
+    }
+    let Json(form) = match body {
+        Ok(form) => form,
+        Err(e) => return Failed(e.into()),
+    };
+    let res = state.user_service.update_profile(user_id, form).await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+pub async fn update_presence_visibility(
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
+    State(state): State<AppState>,
+    body: Result<Json<UpdatePresenceVisibilityForm>, JsonRejection>,
+) -> ServerResponse<UpdatePresenceVisibilitySuccess> {
+    if let Err(e) = scopes.require(Scope::ProfileWrite) {
+        return Failed(e.into());
+    }
+    let Json(form) = match body {
+        Ok(form) => form,
+        Err(e) => return Failed(e.into()),
+    };
+    let res = state
+        .presence_service
+        .update_visibility(user_id, form)
+        .await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+pub async fn find_vapid_public_key(
+    AuthorizedUser { scopes, .. }: AuthorizedUser,
+    State(state): State<AppState>,
+) -> ServerResponse<VapidPublicKey> {
+    if let Err(e) = scopes.require_session() {
+        return Failed(e.into());
+    }
+    Success(state.push_service.vapid_public_key())
+}
+
+pub async fn find_push_subscriptions(
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
+    State(state): State<AppState>,
+) -> ServerResponse<Vec<PushSubscriptionModel>> {
+    if let Err(e) = scopes.require_session() {
+        return Failed(e.into());
+    }
+    let res = state.push_service.find_subscriptions(user_id).await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+pub async fn create_push_subscription(
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Result<Json<CreatePushSubscriptionForm>, JsonRejection>,
+) -> ServerResponse<PushSubscriptionModel> {
+    if let Err(e) = scopes.require_writable_session() {
+        return Failed(e.into());
+    }
+    let Json(form) = match body {
+        Ok(form) => form,
+        Err(e) => return Failed(e.into()),
+    };
+    let user_agent = headers
+        .get(USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string());
+    let res = state
+        .push_service
+        .subscribe(user_id, user_agent, form)
+        .await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+pub async fn delete_push_subscription(
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
+    Path(subscription_id): Path<i32>,
+    State(state): State<AppState>,
+) -> ServerResponse<DeletePushSubscriptionSuccess> {
+    if let Err(e) = scopes.require_session() {
+        return Failed(e.into());
+    }
+    let res = state
+        .push_service
+        .unsubscribe(user_id, subscription_id)
+        .await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+pub async fn find_digest_preference(
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
+    State(state): State<AppState>,
+) -> ServerResponse<DigestPreference> {
+    if let Err(e) = scopes.require_session() {
+        return Failed(e.into());
+    }
+    let res = state.digest_service.find_preference(user_id).await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+pub async fn update_digest_preference(
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
+    State(state): State<AppState>,
+    body: Result<Json<DigestPreference>, JsonRejection>,
+) -> ServerResponse<DigestPreference> {
+    if let Err(e) = scopes.require_writable_session() {
+        return Failed(e.into());
+    }
+    let Json(form) = match body {
+        Ok(form) => form,
+        Err(e) => return Failed(e.into()),
+    };
+    let res = state.digest_service.update_preference(user_id, form).await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+/// Opened from the link in a digest email, so it is not authorized; the token
+/// identifies the user.
+pub async fn unsubscribe_digest(
+    State(state): State<AppState>,
+    query: Result<Query<UnsubscribeDigestQuery>, QueryRejection>,
+) -> ServerResponse<UnsubscribeDigestSuccess> {
+    let Query(query) = match query {
+        Ok(q) => q,
+        Err(e) => return Failed(anyhow!(e.to_string())),
+    };
+    let res = state.digest_service.unsubscribe(query.token).await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
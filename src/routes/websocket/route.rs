@@ -1,23 +1,223 @@
+use std::time::Duration;
+
+use axum::extract::ws::CloseFrame;
+use axum::extract::ws::Message;
+use axum::extract::ws::WebSocket;
+use axum::extract::Path;
 use axum::extract::State;
 use axum::extract::WebSocketUpgrade;
+use axum::http::header::AUTHORIZATION;
+use axum::http::header::USER_AGENT;
+use axum::http::HeaderMap;
+use axum::response::sse::KeepAlive;
+use axum::response::sse::Sse;
 use axum::response::IntoResponse;
+use axum::response::Response;
 use axum::routing::get;
+use axum::routing::post;
 use axum::Router;
+use chrono::NaiveDateTime;
 
 use crate::app::AppState;
-use crate::routes::AuthorizedUserFromTokenQuery;
-use crate::routes::TokenQuery;
+use crate::routes::AuthorizedUser;
+use crate::routes::ServerResponse;
+use crate::routes::ServerResponse::*;
+use crate::routes::TokenAuthenticationError;
+use crate::routes::WebSocketAuth;
+use crate::service::WsTicket;
+use crate::websocket::FallbackRequestSent;
+use crate::websocket::OpenedPollSession;
+use crate::websocket::PolledEvents;
+use crate::websocket::WsAuthRequest;
+use crate::websocket::WsEncoding;
+use crate::websocket::WsResponse;
+use crate::websocket::AUTHENTICATION_FAILED_CLOSE_CODE;
+use crate::websocket::JSON_PROTOCOL;
+use crate::websocket::MSGPACK_PROTOCOL;
+
+/// How long a websocket opened without credentials may take to send its `AUTH` frame.
+const AUTH_FRAME_TIMEOUT: Duration = Duration::from_secs(10);
 
 pub fn ws_route(state: AppState) -> Router {
-    Router::new().route("/", get(ws_handler)).with_state(state)
+    Router::new()
+        .route("/", get(ws_handler))
+        .route("/ticket", post(create_ws_ticket))
+        .route("/events", get(sse_handler))
+        .route("/poll", post(open_poll_session))
+        .route("/poll/:session_id", get(poll_session))
+        .route("/sessions/:session_id", post(send_session_request))
+        .with_state(state)
+}
+
+pub async fn create_ws_ticket(
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> ServerResponse<WsTicket> {
+    if let Err(e) = scopes.require_session() {
+        return Failed(e.into());
+    }
+    let token = headers
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim_start_matches("Bearer "))
+        .unwrap_or_default();
+    let res = state.ws_ticket_service.issue_ticket(user_id, token).await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e),
+    }
+}
+
+fn user_agent(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string())
+}
+
+/// Streams the events of a new session for clients that cannot open a websocket.
+pub async fn sse_handler(
+    auth: WebSocketAuth,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Response {
+    let WebSocketAuth::Authenticated {
+        user_id,
+        token_expires_at,
+    } = auth
+    else {
+        return TokenAuthenticationError::TokenIsMissing.into_response();
+    };
+    let session = state.session_factory.create_session(
+        user_id,
+        token_expires_at,
+        user_agent(&headers),
+        WsEncoding::Json,
+    );
+    let events = state.fallback_sessions.open_stream(user_id, session);
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+pub async fn open_poll_session(
+    auth: WebSocketAuth,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> ServerResponse<OpenedPollSession> {
+    let WebSocketAuth::Authenticated {
+        user_id,
+        token_expires_at,
+    } = auth
+    else {
+        return Failed(TokenAuthenticationError::TokenIsMissing.into());
+    };
+    let session = state.session_factory.create_session(
+        user_id,
+        token_expires_at,
+        user_agent(&headers),
+        WsEncoding::Json,
+    );
+    Success(state.fallback_sessions.open_poll(user_id, session))
+}
+
+pub async fn poll_session(
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
+    State(state): State<AppState>,
+    Path(session_id): Path<u64>,
+) -> ServerResponse<PolledEvents> {
+    if let Err(e) = scopes.require_session() {
+        return Failed(e.into());
+    }
+    let res = state.fallback_sessions.poll(user_id, session_id).await;
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e.into()),
+    }
+}
+
+/// Passes a websocket request to a server-sent events or long-polling session.
+pub async fn send_session_request(
+    AuthorizedUser { user_id, scopes }: AuthorizedUser,
+    State(state): State<AppState>,
+    Path(session_id): Path<u64>,
+    body: String,
+) -> ServerResponse<FallbackRequestSent> {
+    if let Err(e) = scopes.require_session() {
+        return Failed(e.into());
+    }
+    let res = state.fallback_sessions.send(user_id, session_id, body);
+    match res {
+        Ok(r) => Success(r),
+        Err(e) => Failed(e.into()),
+    }
 }
 
 pub async fn ws_handler(
-    TokenQuery { token }: TokenQuery,
-    AuthorizedUserFromTokenQuery { user_id }: AuthorizedUserFromTokenQuery,
+    auth: WebSocketAuth,
     State(state): State<AppState>,
+    headers: HeaderMap,
     ws: WebSocketUpgrade,
 ) -> impl IntoResponse {
-    let session = state.session_factory.create_session(token, user_id);
-    ws.on_upgrade(move |socket| session.run(socket))
+    let user_agent = user_agent(&headers);
+    // MessagePack wins when a client offers both
+    ws.protocols([MSGPACK_PROTOCOL, JSON_PROTOCOL])
+        .on_upgrade(move |mut socket| async move {
+            let encoding =
+                WsEncoding::from_protocol(socket.protocol().and_then(|p| p.to_str().ok()));
+            let (user_id, token_expires_at) = match auth {
+                WebSocketAuth::Authenticated {
+                    user_id,
+                    token_expires_at,
+                } => (user_id, token_expires_at),
+                WebSocketAuth::FirstFrame => {
+                    match authenticate_first_frame(&mut socket, encoding, &state).await {
+                        Ok(authenticated) => authenticated,
+                        Err(reason) => {
+                            let frame = CloseFrame {
+                                code: AUTHENTICATION_FAILED_CLOSE_CODE,
+                                reason: reason.into(),
+                            };
+                            let _ = socket.send(Message::Close(Some(frame))).await;
+                            return;
+                        }
+                    }
+                }
+            };
+            let session = state.session_factory.create_session(
+                user_id,
+                token_expires_at,
+                user_agent,
+                encoding,
+            );
+            session.run(socket).await
+        })
+}
+
+/// Waits for the `AUTH` frame of a websocket opened without credentials, acknowledges
+/// it with `AUTHENTICATED` and returns the user and when their token expires.
+async fn authenticate_first_frame(
+    socket: &mut WebSocket,
+    encoding: WsEncoding,
+    state: &AppState,
+) -> Result<(i32, u64), String> {
+    let frame = tokio::time::timeout(AUTH_FRAME_TIMEOUT, socket.recv())
+        .await
+        .map_err(|_| "Authentication timed out".to_string())?;
+    let Some(Ok(frame)) = frame else {
+        return Err("Expected an AUTH frame".to_string());
+    };
+    let WsAuthRequest::Auth { token } =
+        WsEncoding::decode(&frame).map_err(|_| "Expected an AUTH frame".to_string())?;
+    let (user_id, token_expires_at) = WebSocketAuth::authenticate(&token, state)
+        .await
+        .map_err(|e| e.to_string())?;
+    let expires_at =
+        NaiveDateTime::from_timestamp_opt(token_expires_at as i64, 0).unwrap_or(NaiveDateTime::MIN);
+    let reply = encoding
+        .encode(&WsResponse::Authenticated { expires_at })
+        .inspect_err(|e| log::error!("encoding error: {e}"))?;
+    socket.send(reply).await.map_err(|e| e.to_string())?;
+    Ok((user_id, token_expires_at))
 }
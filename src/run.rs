@@ -3,9 +3,11 @@ use std::net::SocketAddr;
 
 use crate::app::AppState;
 use crate::routes;
+use crate::routes::admin_route;
 use crate::routes::attachment_route;
 use crate::routes::auth_route;
 use crate::routes::contact_route;
+use crate::routes::conversation_route;
 use crate::routes::group_route;
 use crate::routes::message_route;
 use crate::routes::user_route;
@@ -17,8 +19,10 @@ use tokio::spawn;
 use tower_http::cors::CorsLayer;
 
 pub async fn axum_run() {
+    env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
     let (state, ws_server) = AppState::default().await;
     let ws_server = spawn(ws_server.run());
+    state.digest_service.start();
     let port: u16 = env::var("PORT")
         .expect("PORT environment variable undefined")
         .parse()
@@ -26,15 +30,20 @@ pub async fn axum_run() {
 
     let app = Router::new()
         .route("/api/healthcheck", get(routes::healthcheck))
+        .route(
+            "/.well-known/jwks.json",
+            get(routes::jwks).with_state(state.clone()),
+        )
         .nest("/api/auth", auth_route(state.clone()))
         .nest("/api/contact", contact_route(state.clone()))
+        .nest("/api/conversations", conversation_route(state.clone()))
         .nest("/api/message", message_route(state.clone()))
         .nest("/api/group", group_route(state.clone()))
         .nest("/api/user", user_route(state.clone()))
         .nest("/api/attachment", attachment_route(state.clone()))
         .nest("/api/ws", ws_route(state.clone()))
+        .nest("/api/admin", admin_route(state.clone()))
         .layer(CorsLayer::permissive());
-    env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
     let addr = SocketAddr::from(([0, 0, 0, 0], port));
 
     log::info!("Chatbyte BE running on http://0.0.0.0:{port}");
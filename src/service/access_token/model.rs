@@ -0,0 +1,209 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::repository::AccessTokenRepositoryModel;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Scope {
+    #[serde(rename = "messages:read")]
+    MessagesRead,
+    #[serde(rename = "messages:write")]
+    MessagesWrite,
+    #[serde(rename = "groups:manage")]
+    GroupsManage,
+    #[serde(rename = "contacts:read")]
+    ContactsRead,
+    #[serde(rename = "profile:read")]
+    ProfileRead,
+    #[serde(rename = "profile:write")]
+    ProfileWrite,
+}
+
+impl Scope {
+    /// Whether the scope only allows reading, which is all guests may do.
+    pub fn is_read(&self) -> bool {
+        matches!(
+            self,
+            Scope::MessagesRead | Scope::ContactsRead | Scope::ProfileRead
+        )
+    }
+}
+
+impl Display for Scope {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        use Scope::*;
+        let s = match self {
+            MessagesRead => "messages:read",
+            MessagesWrite => "messages:write",
+            GroupsManage => "groups:manage",
+            ContactsRead => "contacts:read",
+            ProfileRead => "profile:read",
+            ProfileWrite => "profile:write",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use Scope::*;
+        match s {
+            "messages:read" => Ok(MessagesRead),
+            "messages:write" => Ok(MessagesWrite),
+            "groups:manage" => Ok(GroupsManage),
+            "contacts:read" => Ok(ContactsRead),
+            "profile:read" => Ok(ProfileRead),
+            "profile:write" => Ok(ProfileWrite),
+            _ => Err(format!("Unsupported scope '{s}'")),
+        }
+    }
+}
+
+/// What an authenticated request is allowed to do. Logging in grants a `Session`
+/// with full access, or a `GuestSession` that may only read, while personal access
+/// tokens only carry the scopes they were created with.
+#[derive(Clone, Debug)]
+pub enum TokenScopes {
+    Session,
+    GuestSession,
+    Scoped(Vec<Scope>),
+}
+
+impl TokenScopes {
+    /// Narrows the scopes to those that only read, for guests.
+    pub fn read_only(self) -> Self {
+        match self {
+            Self::Session | Self::GuestSession => Self::GuestSession,
+            Self::Scoped(scopes) => {
+                Self::Scoped(scopes.into_iter().filter(Scope::is_read).collect())
+            }
+        }
+    }
+
+    pub fn allows(
+        &self,
+        scope: Scope,
+    ) -> bool {
+        match self {
+            Self::Session => true,
+            Self::GuestSession => scope.is_read(),
+            Self::Scoped(scopes) => scopes.contains(&scope),
+        }
+    }
+
+    pub fn require(
+        &self,
+        scope: Scope,
+    ) -> Result<(), AccessTokenError> {
+        if !self.allows(scope) {
+            return Err(AccessTokenError::MissingScope { scope });
+        }
+        Ok(())
+    }
+
+    pub fn require_session(&self) -> Result<(), AccessTokenError> {
+        match self {
+            Self::Session | Self::GuestSession => Ok(()),
+            Self::Scoped(_) => Err(AccessTokenError::SessionRequired),
+        }
+    }
+
+    /// Like `require_session`, for session-only requests that change something.
+    pub fn require_writable_session(&self) -> Result<(), AccessTokenError> {
+        match self {
+            Self::Session => Ok(()),
+            Self::GuestSession => Err(AccessTokenError::ReadOnly),
+            Self::Scoped(_) => Err(AccessTokenError::SessionRequired),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateAccessTokenForm {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub expires_in_days: Option<i32>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessTokenModel {
+    pub id: i32,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: NaiveDateTime,
+    pub expires_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+}
+
+impl From<AccessTokenRepositoryModel> for AccessTokenModel {
+    fn from(value: AccessTokenRepositoryModel) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+            scopes: value
+                .scopes
+                .iter()
+                .filter_map(|s| Scope::from_str(s).ok())
+                .collect(),
+            created_at: value.created_at,
+            expires_at: value.expires_at,
+            last_used_at: value.last_used_at,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedAccessToken {
+    #[serde(flatten)]
+    pub details: AccessTokenModel,
+    pub token: String,
+}
+
+pub struct AuthenticatedAccessToken {
+    pub user_id: i32,
+    pub scopes: Vec<Scope>,
+}
+
+pub struct RevokeAccessTokenSuccess;
+
+impl Serialize for RevokeAccessTokenSuccess {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str("Successfully revoked access token")
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum AccessTokenError {
+    #[error("Token is missing the '{scope}' scope")]
+    MissingScope { scope: Scope },
+    #[error("This action cannot be performed with an access token")]
+    SessionRequired,
+    #[error("Guests can only read")]
+    ReadOnly,
+    #[error("Access token name must not be empty")]
+    NameIsEmpty,
+    #[error("Access token must have at least one scope")]
+    NoScopes,
+    #[error("Access token expiry must be a positive number of days")]
+    InvalidExpiry,
+    #[error("Access token with id {token_id} not found")]
+    NotFound { token_id: i32 },
+}
@@ -0,0 +1,153 @@
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rand::RngCore;
+use sha2::Digest;
+use sha2::Sha256;
+
+use crate::repository::AccessTokenRepository;
+use crate::websocket::message::AppMessage;
+use crate::websocket::message::AppTx;
+
+use super::AccessTokenError;
+use super::AccessTokenModel;
+use super::AuthenticatedAccessToken;
+use super::CreateAccessTokenForm;
+use super::CreatedAccessToken;
+use super::RevokeAccessTokenSuccess;
+use super::Scope;
+
+pub const ACCESS_TOKEN_PREFIX: &str = "cbpat_";
+
+#[derive(Clone)]
+pub struct AccessTokenService {
+    access_token_repository: AccessTokenRepository,
+    app_tx: AppTx,
+}
+
+impl AccessTokenService {
+    pub fn new(
+        access_token_repository: AccessTokenRepository,
+        app_tx: AppTx,
+    ) -> Self {
+        Self {
+            access_token_repository,
+            app_tx,
+        }
+    }
+
+    pub fn is_access_token(token: &str) -> bool {
+        token.starts_with(ACCESS_TOKEN_PREFIX)
+    }
+
+    fn hash_token(token: &str) -> String {
+        format!("{:x}", Sha256::digest(token.as_bytes()))
+    }
+
+    fn generate_token() -> String {
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        format!("{ACCESS_TOKEN_PREFIX}{}", URL_SAFE_NO_PAD.encode(bytes))
+    }
+
+    pub async fn create_access_token(
+        &self,
+        user_id: i32,
+        CreateAccessTokenForm {
+            name,
+            mut scopes,
+            expires_in_days,
+        }: CreateAccessTokenForm,
+    ) -> Result<CreatedAccessToken, anyhow::Error> {
+        use AccessTokenError::*;
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            bail!(NameIsEmpty);
+        }
+        if scopes.is_empty() {
+            bail!(NoScopes);
+        }
+        if matches!(expires_in_days, Some(days) if days <= 0) {
+            bail!(InvalidExpiry);
+        }
+        scopes.sort_by_key(Scope::to_string);
+        scopes.dedup();
+        let token = Self::generate_token();
+        let res = self
+            .access_token_repository
+            .create_access_token(
+                user_id,
+                name,
+                Self::hash_token(&token),
+                scopes.iter().map(Scope::to_string).collect(),
+                expires_in_days,
+            )
+            .await;
+        let created = match res {
+            Ok(t) => t,
+            Err(e) => bail!(e),
+        };
+        Ok(CreatedAccessToken {
+            details: created.into(),
+            token,
+        })
+    }
+
+    pub async fn find_access_tokens_for_user(
+        &self,
+        user_id: i32,
+    ) -> Result<Vec<AccessTokenModel>, anyhow::Error> {
+        let tokens = self
+            .access_token_repository
+            .find_access_tokens_by_user_id(user_id)
+            .await
+            .map_err(|e| anyhow!(e))?;
+        Ok(tokens.into_iter().map(AccessTokenModel::from).collect())
+    }
+
+    pub async fn revoke_access_token(
+        &self,
+        user_id: i32,
+        token_id: i32,
+    ) -> Result<RevokeAccessTokenSuccess, anyhow::Error> {
+        let res = self
+            .access_token_repository
+            .revoke_access_token(token_id, user_id)
+            .await;
+        match res {
+            Ok(succ) if succ => {
+                let _ = self
+                    .app_tx
+                    .send(AppMessage::DisconnectUser { user_id })
+                    .await;
+                Ok(RevokeAccessTokenSuccess)
+            }
+            Ok(_) => bail!(AccessTokenError::NotFound { token_id }),
+            Err(e) => bail!(e),
+        }
+    }
+
+    /// Resolves a raw `cbpat_` token to its owner and scopes. Returns `None` when the
+    /// token is unknown, revoked or expired.
+    pub async fn authenticate(
+        &self,
+        token: &str,
+    ) -> Result<Option<AuthenticatedAccessToken>, anyhow::Error> {
+        let res = self
+            .access_token_repository
+            .find_valid_access_token_by_hash(Self::hash_token(token))
+            .await
+            .map_err(|e| anyhow!(e))?;
+        Ok(res.map(|t| AuthenticatedAccessToken {
+            user_id: t.user_id,
+            scopes: t
+                .scopes
+                .iter()
+                .filter_map(|s| Scope::from_str(s).ok())
+                .collect(),
+        }))
+    }
+}
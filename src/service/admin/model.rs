@@ -0,0 +1,201 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::repository::AuditLogRepositoryModel;
+use crate::repository::UserModelRepository;
+use crate::service::Role;
+use crate::websocket::OnlineStats;
+
+#[derive(Deserialize)]
+pub struct AdminUserQuery {
+    pub query: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminUserModel {
+    pub id: i32,
+    pub username: String,
+    pub email: String,
+    pub role: Role,
+    pub suspended_at: Option<NaiveDateTime>,
+    pub password_reset_required: bool,
+}
+
+impl From<UserModelRepository> for AdminUserModel {
+    fn from(value: UserModelRepository) -> Self {
+        Self {
+            id: value.id,
+            username: value.username,
+            email: value.email,
+            role: Role::from_str(&value.role).unwrap_or(Role::Guest),
+            suspended_at: value.suspended_at,
+            password_reset_required: value.password_reset_required,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct UpdateRoleForm {
+    pub role: Role,
+}
+
+#[derive(Deserialize)]
+pub struct SuspendUserForm {
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedPasswordReset {
+    pub temporary_password: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerStats {
+    pub users: i64,
+    pub suspended_users: i64,
+    pub direct_messages: i64,
+    pub group_messages: i64,
+    pub groups: i64,
+    #[serde(flatten)]
+    pub online: OnlineStats,
+}
+
+#[derive(Deserialize)]
+pub struct AuditLogQuery {
+    pub before: Option<i32>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditLogEntry {
+    pub id: i32,
+    pub admin_id: i32,
+    pub action: String,
+    pub target_user_id: Option<i32>,
+    pub details: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<AuditLogRepositoryModel> for AuditLogEntry {
+    fn from(value: AuditLogRepositoryModel) -> Self {
+        Self {
+            id: value.id,
+            admin_id: value.admin_id,
+            action: value.action,
+            target_user_id: value.target_user_id,
+            details: value.details,
+            created_at: value.created_at,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum AdminAction {
+    UpdateRole,
+    SuspendUser,
+    UnsuspendUser,
+    ForcePasswordReset,
+    DeleteDirectMessage,
+    DeleteGroupMessage,
+}
+
+impl Display for AdminAction {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        use AdminAction::*;
+        let s = match self {
+            UpdateRole => "update_role",
+            SuspendUser => "suspend_user",
+            UnsuspendUser => "unsuspend_user",
+            ForcePasswordReset => "force_password_reset",
+            DeleteDirectMessage => "delete_direct_message",
+            DeleteGroupMessage => "delete_group_message",
+        };
+        f.write_str(s)
+    }
+}
+
+pub struct UpdateRoleSuccess;
+
+impl Serialize for UpdateRoleSuccess {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str("Successfully updated role")
+    }
+}
+
+pub struct SuspendUserSuccess;
+
+impl Serialize for SuspendUserSuccess {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str("Successfully suspended user")
+    }
+}
+
+pub struct UnsuspendUserSuccess;
+
+impl Serialize for UnsuspendUserSuccess {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str("Successfully unsuspended user")
+    }
+}
+
+pub struct AdminDeleteMessageSuccess;
+
+impl Serialize for AdminDeleteMessageSuccess {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str("Successfully deleted message")
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum AdminError {
+    #[error("Admin role is required")]
+    Forbidden,
+    #[error("User with id {user_id} not found")]
+    UserNotFound { user_id: i32 },
+    #[error("Admins cannot perform this action on their own account")]
+    CannotModifySelf,
+    #[error("User is already suspended")]
+    AlreadySuspended,
+    #[error("User is not suspended")]
+    NotSuspended,
+    #[error("Message with id {message_id} not found")]
+    MessageNotFound { message_id: i32 },
+}
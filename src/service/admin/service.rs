@@ -0,0 +1,365 @@
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rand::RngCore;
+use tokio::sync::oneshot;
+
+use crate::repository::AdminRepository;
+use crate::repository::AuthRepository;
+use crate::repository::GroupRepository;
+use crate::repository::MessageRepository;
+use crate::service::Role;
+use crate::websocket::message::AppMessage;
+use crate::websocket::message::AppTx;
+use crate::websocket::WsResponse;
+
+use super::AdminAction;
+use super::AdminDeleteMessageSuccess;
+use super::AdminError;
+use super::AdminUserModel;
+use super::AdminUserQuery;
+use super::AuditLogEntry;
+use super::AuditLogQuery;
+use super::ForcedPasswordReset;
+use super::ServerStats;
+use super::SuspendUserForm;
+use super::SuspendUserSuccess;
+use super::UnsuspendUserSuccess;
+use super::UpdateRoleForm;
+use super::UpdateRoleSuccess;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+#[derive(Clone)]
+pub struct AdminService {
+    admin_repository: AdminRepository,
+    auth_repository: AuthRepository,
+    message_repository: MessageRepository,
+    group_repository: GroupRepository,
+    app_tx: AppTx,
+}
+
+impl AdminService {
+    pub fn new(
+        admin_repository: AdminRepository,
+        auth_repository: AuthRepository,
+        message_repository: MessageRepository,
+        group_repository: GroupRepository,
+        app_tx: AppTx,
+    ) -> Self {
+        Self {
+            admin_repository,
+            auth_repository,
+            message_repository,
+            group_repository,
+            app_tx,
+        }
+    }
+
+    fn page_size(limit: Option<i64>) -> i64 {
+        limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+    }
+
+    pub async fn require_admin(
+        &self,
+        user_id: i32,
+    ) -> Result<(), anyhow::Error> {
+        let user = self
+            .auth_repository
+            .find_user_by_id(user_id)
+            .await
+            .map_err(|e| anyhow!(e))?
+            .ok_or(AdminError::Forbidden)?;
+        match Role::from_str(&user.role) {
+            Ok(Role::Admin) => Ok(()),
+            _ => bail!(AdminError::Forbidden),
+        }
+    }
+
+    async fn audit(
+        &self,
+        admin_id: i32,
+        action: AdminAction,
+        target_user_id: Option<i32>,
+        details: Option<String>,
+    ) -> Result<(), anyhow::Error> {
+        log::info!("Admin {admin_id} performed {action} on {target_user_id:?}");
+        self.admin_repository
+            .create_audit_log(admin_id, action.to_string(), target_user_id, details)
+            .await
+            .map_err(|e| anyhow!(e))?;
+        Ok(())
+    }
+
+    pub async fn search_users(
+        &self,
+        AdminUserQuery {
+            query,
+            limit,
+            offset,
+        }: AdminUserQuery,
+    ) -> Result<Vec<AdminUserModel>, anyhow::Error> {
+        let query = query
+            .map(|q| q.trim().to_string())
+            .filter(|q| !q.is_empty());
+        let users = self
+            .admin_repository
+            .search_users(query, Self::page_size(limit), offset.unwrap_or(0).max(0))
+            .await
+            .map_err(|e| anyhow!(e))?;
+        Ok(users.into_iter().map(AdminUserModel::from).collect())
+    }
+
+    pub async fn find_user(
+        &self,
+        user_id: i32,
+    ) -> Result<AdminUserModel, anyhow::Error> {
+        let user = self
+            .auth_repository
+            .find_user_by_id(user_id)
+            .await
+            .map_err(|e| anyhow!(e))?
+            .ok_or(AdminError::UserNotFound { user_id })?;
+        Ok(user.into())
+    }
+
+    pub async fn update_role(
+        &self,
+        admin_id: i32,
+        user_id: i32,
+        UpdateRoleForm { role }: UpdateRoleForm,
+    ) -> Result<UpdateRoleSuccess, anyhow::Error> {
+        if admin_id == user_id {
+            bail!(AdminError::CannotModifySelf);
+        }
+        let res = self
+            .admin_repository
+            .update_role(user_id, role.to_string())
+            .await;
+        match res {
+            Ok(succ) if succ => {}
+            Ok(_) => bail!(AdminError::UserNotFound { user_id }),
+            Err(e) => bail!(e),
+        };
+        self.audit(
+            admin_id,
+            AdminAction::UpdateRole,
+            Some(user_id),
+            Some(format!("role: {role}")),
+        )
+        .await?;
+        Ok(UpdateRoleSuccess)
+    }
+
+    /// Suspends the account and closes its live sessions. Existing tokens are rejected
+    /// from then on because every request checks the account status.
+    pub async fn suspend_user(
+        &self,
+        admin_id: i32,
+        user_id: i32,
+        SuspendUserForm { reason }: SuspendUserForm,
+    ) -> Result<SuspendUserSuccess, anyhow::Error> {
+        if admin_id == user_id {
+            bail!(AdminError::CannotModifySelf);
+        }
+        self.find_user(user_id).await?;
+        let res = self.admin_repository.suspend_user(user_id).await;
+        match res {
+            Ok(succ) if succ => {}
+            Ok(_) => bail!(AdminError::AlreadySuspended),
+            Err(e) => bail!(e),
+        };
+        let _ = self
+            .app_tx
+            .send(AppMessage::DisconnectUser { user_id })
+            .await;
+        self.audit(admin_id, AdminAction::SuspendUser, Some(user_id), reason)
+            .await?;
+        Ok(SuspendUserSuccess)
+    }
+
+    pub async fn unsuspend_user(
+        &self,
+        admin_id: i32,
+        user_id: i32,
+    ) -> Result<UnsuspendUserSuccess, anyhow::Error> {
+        self.find_user(user_id).await?;
+        let res = self.admin_repository.unsuspend_user(user_id).await;
+        match res {
+            Ok(succ) if succ => {}
+            Ok(_) => bail!(AdminError::NotSuspended),
+            Err(e) => bail!(e),
+        };
+        self.audit(admin_id, AdminAction::UnsuspendUser, Some(user_id), None)
+            .await?;
+        Ok(UnsuspendUserSuccess)
+    }
+
+    /// Replaces the password with a random one that the admin hands over out of band.
+    /// The user is asked to choose a new password after signing in with it.
+    pub async fn force_password_reset(
+        &self,
+        admin_id: i32,
+        user_id: i32,
+    ) -> Result<ForcedPasswordReset, anyhow::Error> {
+        let mut bytes = [0u8; 12];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let temporary_password = URL_SAFE_NO_PAD.encode(bytes);
+        let res = self
+            .admin_repository
+            .force_password_reset(user_id, temporary_password.clone())
+            .await;
+        match res {
+            Ok(succ) if succ => {}
+            Ok(_) => bail!(AdminError::UserNotFound { user_id }),
+            Err(e) => bail!(e),
+        };
+        let _ = self
+            .app_tx
+            .send(AppMessage::DisconnectUser { user_id })
+            .await;
+        self.audit(
+            admin_id,
+            AdminAction::ForcePasswordReset,
+            Some(user_id),
+            None,
+        )
+        .await?;
+        Ok(ForcedPasswordReset { temporary_password })
+    }
+
+    pub async fn delete_direct_message(
+        &self,
+        admin_id: i32,
+        message_id: i32,
+    ) -> Result<AdminDeleteMessageSuccess, anyhow::Error> {
+        let message = self
+            .message_repository
+            .find_message_by_id(message_id)
+            .await
+            .map_err(|e| anyhow!(e))?
+            .ok_or(AdminError::MessageNotFound { message_id })?;
+        if message.deleted {
+            bail!(AdminError::MessageNotFound { message_id });
+        }
+        let res = self.message_repository.delete_message(message_id).await;
+        match res {
+            Ok(succ) if succ => {}
+            Ok(_) => bail!(AdminError::MessageNotFound { message_id }),
+            Err(e) => bail!(e),
+        };
+        let notifications = [
+            (message.sender_id, message.receiver_id),
+            (message.receiver_id, message.sender_id),
+        ];
+        for (user_id, contact_id) in notifications {
+            let _ = self
+                .app_tx
+                .send(AppMessage::Notify {
+                    user_ids: vec![user_id],
+                    message: WsResponse::DeleteMessageNotification {
+                        contact_id,
+                        message_id,
+                    },
+                })
+                .await;
+        }
+        self.audit(
+            admin_id,
+            AdminAction::DeleteDirectMessage,
+            Some(message.sender_id),
+            Some(format!("message_id: {message_id}")),
+        )
+        .await?;
+        Ok(AdminDeleteMessageSuccess)
+    }
+
+    pub async fn delete_group_message(
+        &self,
+        admin_id: i32,
+        message_id: i32,
+    ) -> Result<AdminDeleteMessageSuccess, anyhow::Error> {
+        let message = self
+            .group_repository
+            .find_message_by_id(message_id)
+            .await
+            .map_err(|e| anyhow!(e))?
+            .ok_or(AdminError::MessageNotFound { message_id })?;
+        if message.deleted {
+            bail!(AdminError::MessageNotFound { message_id });
+        }
+        let res = self
+            .group_repository
+            .set_message_to_delete(message_id)
+            .await;
+        match res {
+            Ok(succ) if succ => {}
+            Ok(_) => bail!(AdminError::MessageNotFound { message_id }),
+            Err(e) => bail!(e),
+        };
+        let members = self
+            .group_repository
+            .find_group_members(message.group_id)
+            .await
+            .map_err(|e| anyhow!(e))?;
+        let _ = self
+            .app_tx
+            .send(AppMessage::Notify {
+                user_ids: members,
+                message: WsResponse::DeleteGroupMessageNotification {
+                    group_id: message.group_id,
+                    message_id,
+                },
+            })
+            .await;
+        self.audit(
+            admin_id,
+            AdminAction::DeleteGroupMessage,
+            Some(message.sender_id),
+            Some(format!(
+                "message_id: {message_id}, group_id: {}",
+                message.group_id
+            )),
+        )
+        .await?;
+        Ok(AdminDeleteMessageSuccess)
+    }
+
+    pub async fn server_stats(&self) -> Result<ServerStats, anyhow::Error> {
+        let (reply, online) = oneshot::channel();
+        self.app_tx
+            .send(AppMessage::OnlineStats { reply })
+            .await
+            .map_err(|_| anyhow!("Websocket server is not running"))?;
+        let online = online.await?;
+        let stats = self
+            .admin_repository
+            .server_stats()
+            .await
+            .map_err(|e| anyhow!(e))?;
+        Ok(ServerStats {
+            users: stats.users,
+            suspended_users: stats.suspended_users,
+            direct_messages: stats.direct_messages,
+            group_messages: stats.group_messages,
+            groups: stats.groups,
+            online,
+        })
+    }
+
+    pub async fn find_audit_log(
+        &self,
+        AuditLogQuery { before, limit }: AuditLogQuery,
+    ) -> Result<Vec<AuditLogEntry>, anyhow::Error> {
+        let entries = self
+            .admin_repository
+            .find_audit_log(before, Self::page_size(limit))
+            .await
+            .map_err(|e| anyhow!(e))?;
+        Ok(entries.into_iter().map(AuditLogEntry::from).collect())
+    }
+}
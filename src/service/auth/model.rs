@@ -1,3 +1,6 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
 use serde::Deserialize;
 use serde::Serialize;
 use thiserror::Error;
@@ -16,12 +19,62 @@ pub struct RegisterForm {
 
 pub struct AuthenticationToken(pub String);
 
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Admin,
+    User,
+    /// May only read: guests cannot send or change messages, create or manage groups,
+    /// change their profile or issue personal access tokens.
+    Guest,
+}
+
+impl Role {
+    pub fn is_read_only(&self) -> bool {
+        matches!(self, Role::Guest)
+    }
+}
+
+impl Display for Role {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        let s = match self {
+            Role::Admin => "admin",
+            Role::User => "user",
+            Role::Guest => "guest",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "admin" => Ok(Role::Admin),
+            "user" => Ok(Role::User),
+            "guest" => Ok(Role::Guest),
+            _ => Err(format!("Unsupported role '{s}'")),
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum AuthError {
     #[error("Email '{email:?}' not found")]
     EmailNotFound { email: String },
     #[error("Incorrect password")]
     IncorrectPassword,
+    #[error("Account is suspended")]
+    AccountSuspended,
+    #[error("Account not found")]
+    AccountNotFound,
+    #[error("Password was reset by an admin and must be changed first")]
+    PasswordResetRequired,
+    #[error("Guests can only read")]
+    ReadOnly,
     #[error("{0}")]
     Other(anyhow::Error),
 }
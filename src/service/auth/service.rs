@@ -1,15 +1,15 @@
-use std::collections::BTreeMap;
+use std::str::FromStr;
 use std::time::Duration;
-use std::time::SystemTime;
-use std::time::UNIX_EPOCH;
 
+use anyhow::anyhow;
 use anyhow::bail;
-use hmac::Hmac;
-use hmac::Mac;
 use regex::Regex;
-use sha2::Sha256;
 
 use crate::repository::AuthRepository;
+use crate::repository::UserModelRepository;
+use crate::service::JwtService;
+use crate::websocket::message::AppMessage;
+use crate::websocket::message::AppTx;
 
 use super::AuthError;
 use super::AuthenticationToken;
@@ -18,25 +18,28 @@ use super::ChangePasswordSuccess;
 use super::LoginForm;
 use super::RegisterForm;
 use super::RegisterSuccess;
-use jwt::SignWithKey;
+use super::Role;
 
 #[derive(Clone)]
 pub struct AuthService {
     auth_repository: AuthRepository,
-    jwt_secret: String,
+    jwt_service: JwtService,
     jwt_duration: u64,
+    app_tx: AppTx,
 }
 
 impl AuthService {
     pub fn new(
         auth_repository: AuthRepository,
-        jwt_secret: String,
+        jwt_service: JwtService,
         jwt_duration: u64,
+        app_tx: AppTx,
     ) -> Self {
         Self {
             auth_repository,
-            jwt_secret,
+            jwt_service,
             jwt_duration,
+            app_tx,
         }
     }
 
@@ -72,14 +75,65 @@ impl AuthService {
         if !password_match {
             bail!(AuthError::IncorrectPassword)
         }
-        let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
-
-        let timestamp_secs = (since_epoch + Duration::from_secs(self.jwt_duration * 60)).as_secs();
-        let key: Hmac<Sha256> = Hmac::new_from_slice(self.jwt_secret.as_bytes()).unwrap();
-        let mut claims = BTreeMap::new();
-        claims.insert("uid".to_string(), u64::try_from(user.id).unwrap());
-        claims.insert("expiration".to_string(), timestamp_secs);
-        let payload = claims.sign_with_key(&key).unwrap();
+        self.issue_token(user.id).await
+    }
+
+    /// Fails when the account no longer exists or has been suspended, and returns
+    /// the user otherwise. Tokens stay cryptographically valid after a suspension, so
+    /// this is checked on every request.
+    async fn check_account(
+        &self,
+        user_id: i32,
+    ) -> Result<UserModelRepository, anyhow::Error> {
+        let res = self.auth_repository.find_user_by_id(user_id).await;
+        match res {
+            Ok(Some(user)) if user.suspended_at.is_some() => bail!(AuthError::AccountSuspended),
+            Ok(Some(user)) => Ok(user),
+            Ok(None) => bail!(AuthError::AccountNotFound),
+            Err(e) => bail!(e),
+        }
+    }
+
+    /// Allows signing in and changing the password, even while a reset is pending.
+    pub async fn ensure_can_sign_in(
+        &self,
+        user_id: i32,
+    ) -> Result<(), anyhow::Error> {
+        self.check_account(user_id).await.map(|_| ())
+    }
+
+    /// Allows every other request, which fail until a reset password was changed.
+    /// Returns the role of the user, since guests may only read.
+    pub async fn ensure_active(
+        &self,
+        user_id: i32,
+    ) -> Result<Role, anyhow::Error> {
+        let user = self.check_account(user_id).await?;
+        if user.password_reset_required {
+            bail!(AuthError::PasswordResetRequired);
+        }
+        Role::from_str(&user.role).map_err(|e| anyhow!(e))
+    }
+
+    /// Allows requests that change something, which guests cannot make.
+    pub async fn ensure_can_write(
+        &self,
+        user_id: i32,
+    ) -> Result<(), anyhow::Error> {
+        if self.ensure_active(user_id).await?.is_read_only() {
+            bail!(AuthError::ReadOnly);
+        }
+        Ok(())
+    }
+
+    pub async fn issue_token(
+        &self,
+        user_id: i32,
+    ) -> Result<AuthenticationToken, anyhow::Error> {
+        self.ensure_can_sign_in(user_id).await?;
+        let payload = self
+            .jwt_service
+            .sign(user_id, Duration::from_secs(self.jwt_duration * 60))?;
         Ok(AuthenticationToken(payload))
     }
 
@@ -149,7 +203,14 @@ impl AuthService {
             .update_password(user_id, new_password)
             .await;
         match res {
-            Ok(succ) if succ => Ok(ChangePasswordSuccess),
+            Ok(succ) if succ => {
+                // live sessions were opened with the old password
+                let _ = self
+                    .app_tx
+                    .send(AppMessage::DisconnectUser { user_id })
+                    .await;
+                Ok(ChangePasswordSuccess)
+            }
             Ok(_) => bail!(FailedToChangePasswordInternalServerError),
             Err(e) => bail!(e),
         }
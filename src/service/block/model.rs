@@ -0,0 +1,74 @@
+use chrono::NaiveDateTime;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::repository::BlockedUserRepositoryModel;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockUserForm {
+    /// Hide the content of the blocked user's messages in shared groups.
+    #[serde(default)]
+    pub collapse_group_messages: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockedUserModel {
+    pub user_id: i32,
+    pub username: String,
+    pub collapse_group_messages: bool,
+    pub blocked_at: NaiveDateTime,
+}
+
+impl From<BlockedUserRepositoryModel> for BlockedUserModel {
+    fn from(value: BlockedUserRepositoryModel) -> Self {
+        Self {
+            user_id: value.user_id,
+            username: value.username,
+            collapse_group_messages: value.collapse_group_messages,
+            blocked_at: value.blocked_at,
+        }
+    }
+}
+
+pub struct BlockUserSuccess;
+
+impl Serialize for BlockUserSuccess {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str("Successfully blocked user")
+    }
+}
+
+pub struct UnblockUserSuccess;
+
+impl Serialize for UnblockUserSuccess {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str("Successfully unblocked user")
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum BlockError {
+    #[error("Cannot block yourself")]
+    CannotBlockSelf,
+    #[error("User with id {user_id} not found")]
+    UserNotFound { user_id: i32 },
+    #[error("User with id {user_id} is not blocked")]
+    NotBlocked { user_id: i32 },
+    #[error("Cannot send messages to this user")]
+    MessagingBlocked,
+}
@@ -0,0 +1,106 @@
+use anyhow::anyhow;
+use anyhow::bail;
+
+use crate::repository::AuthRepository;
+use crate::repository::BlockRepository;
+use crate::websocket::message::AppMessage;
+use crate::websocket::message::AppTx;
+
+use super::BlockError;
+use super::BlockUserForm;
+use super::BlockUserSuccess;
+use super::BlockedUserModel;
+use super::UnblockUserSuccess;
+
+#[derive(Clone)]
+pub struct BlockService {
+    block_repository: BlockRepository,
+    auth_repository: AuthRepository,
+    app_tx: AppTx,
+}
+
+impl BlockService {
+    pub fn new(
+        block_repository: BlockRepository,
+        auth_repository: AuthRepository,
+        app_tx: AppTx,
+    ) -> Self {
+        Self {
+            block_repository,
+            auth_repository,
+            app_tx,
+        }
+    }
+
+    /// Blocks `blocked_id`, or updates the options of an existing block. Both users
+    /// stop seeing each other's presence right away.
+    pub async fn block_user(
+        &self,
+        user_id: i32,
+        blocked_id: i32,
+        BlockUserForm {
+            collapse_group_messages,
+        }: BlockUserForm,
+    ) -> Result<BlockUserSuccess, anyhow::Error> {
+        if user_id == blocked_id {
+            bail!(BlockError::CannotBlockSelf);
+        }
+        self.auth_repository
+            .find_user_by_id(blocked_id)
+            .await
+            .map_err(|e| anyhow!(e))?
+            .ok_or(BlockError::UserNotFound {
+                user_id: blocked_id,
+            })?;
+        self.block_repository
+            .block_user(user_id, blocked_id, collapse_group_messages)
+            .await
+            .map_err(|e| anyhow!(e))?;
+        let _ = self
+            .app_tx
+            .send(AppMessage::BlockChanged {
+                user_id,
+                other_id: blocked_id,
+            })
+            .await;
+        Ok(BlockUserSuccess)
+    }
+
+    pub async fn unblock_user(
+        &self,
+        user_id: i32,
+        blocked_id: i32,
+    ) -> Result<UnblockUserSuccess, anyhow::Error> {
+        let res = self
+            .block_repository
+            .unblock_user(user_id, blocked_id)
+            .await;
+        match res {
+            Ok(succ) if succ => {}
+            Ok(_) => bail!(BlockError::NotBlocked {
+                user_id: blocked_id
+            }),
+            Err(e) => bail!(e),
+        };
+        let _ = self
+            .app_tx
+            .send(AppMessage::BlockChanged {
+                user_id,
+                other_id: blocked_id,
+            })
+            .await;
+        Ok(UnblockUserSuccess)
+    }
+
+    pub async fn find_blocked_users(
+        &self,
+        user_id: i32,
+    ) -> Result<Vec<BlockedUserModel>, anyhow::Error> {
+        let blocked = self
+            .block_repository
+            .find_blocked_users(user_id)
+            .await
+            .map_err(|e| anyhow!(e))?;
+        Ok(blocked.into_iter().map(BlockedUserModel::from).collect())
+    }
+}
@@ -3,15 +3,21 @@ use crate::repository::ConversationRecentMessageRepositoryModel;
 use crate::repository::GroupConversationDetailRepositoryModel;
 use crate::repository::GroupConversationRepositoryModel;
 use crate::repository::GroupRepositoryModel;
+use crate::service::ConversationSettingsModel;
+use crate::service::UserProfile;
 
 use chrono::NaiveDateTime;
 use serde::Serialize;
 
 #[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct DirectContact {
     pub id: i32,
     pub email: String,
     pub username: String,
+    /// Hidden by the contact's presence visibility.
+    pub last_seen_at: Option<NaiveDateTime>,
+    pub profile: UserProfile,
 }
 
 impl From<&ContactRepositoryModel> for DirectContact {
@@ -20,6 +26,8 @@ impl From<&ContactRepositoryModel> for DirectContact {
             id: value.id,
             email: value.email.clone(),
             username: value.username.clone(),
+            last_seen_at: value.last_seen_at,
+            profile: UserProfile::from(&value.profile),
         }
     }
 }
@@ -34,6 +42,8 @@ pub struct DirectConversation {
     pub username: String,
     pub deleted: bool,
     pub sent_at: NaiveDateTime,
+    pub profile: UserProfile,
+    pub settings: ConversationSettingsModel,
 }
 
 impl From<&ConversationRecentMessageRepositoryModel> for DirectConversation {
@@ -46,6 +56,8 @@ impl From<&ConversationRecentMessageRepositoryModel> for DirectConversation {
             username: value.username.clone(),
             deleted: value.deleted,
             sent_at: value.sent_at,
+            profile: UserProfile::from(&value.profile),
+            settings: ConversationSettingsModel::default(),
         }
     }
 }
@@ -76,6 +88,7 @@ pub struct GroupConversation {
     pub unread_message: i64,
     pub group_name: String,
     pub detail: Option<GroupConversationDetail>,
+    pub settings: ConversationSettingsModel,
 }
 
 #[derive(Serialize)]
@@ -125,6 +138,7 @@ impl From<GroupConversationRepositoryModel> for GroupConversation {
             unread_message,
             group_name,
             detail: detail.map(GroupConversationDetail::from),
+            settings: ConversationSettingsModel::default(),
         }
     }
 }
@@ -1,8 +1,18 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use anyhow::anyhow;
 use anyhow::bail;
 
+use crate::repository::BlockRepository;
 use crate::repository::ContactRepository;
+use crate::repository::ConversationSettingRepository;
+use crate::repository::ConversationSettingRepositoryModel;
 use crate::repository::GroupRepository;
 use crate::repository::MessageRepository;
+use crate::service::ConversationListQuery;
+use crate::service::ConversationSettingsModel;
+use crate::service::ConversationType;
 
 use super::DirectContact;
 use super::DirectConversation;
@@ -14,6 +24,8 @@ pub struct ContactService {
     contact_repository: ContactRepository,
     message_repository: MessageRepository,
     group_repository: GroupRepository,
+    block_repository: BlockRepository,
+    conversation_setting_repository: ConversationSettingRepository,
 }
 
 impl ContactService {
@@ -21,11 +33,15 @@ impl ContactService {
         contact_repository: ContactRepository,
         message_repository: MessageRepository,
         group_repository: GroupRepository,
+        block_repository: BlockRepository,
+        conversation_setting_repository: ConversationSettingRepository,
     ) -> Self {
         Self {
             contact_repository,
             message_repository,
             group_repository,
+            block_repository,
+            conversation_setting_repository,
         }
     }
 
@@ -40,16 +56,80 @@ impl ContactService {
         }
     }
 
+    /// Users that `user_id` has blocked or has been blocked by. They do not see each
+    /// other's presence.
+    pub async fn find_block_related_ids(
+        &self,
+        user_id: i32,
+    ) -> Result<HashSet<i32>, anyhow::Error> {
+        let ids = self
+            .block_repository
+            .find_block_related_ids(user_id)
+            .await
+            .map_err(|e| anyhow!(e))?;
+        Ok(ids.into_iter().collect())
+    }
+
+    pub async fn is_blocked_between(
+        &self,
+        user_id: i32,
+        other_id: i32,
+    ) -> Result<bool, anyhow::Error> {
+        self.block_repository
+            .is_blocked_between(user_id, other_id)
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    /// The user's settings of every conversation of a type, by conversation id.
+    async fn find_settings(
+        &self,
+        user_id: i32,
+        conversation_type: ConversationType,
+    ) -> Result<HashMap<i32, ConversationSettingRepositoryModel>, anyhow::Error> {
+        let settings = self
+            .conversation_setting_repository
+            .find_settings(user_id, &conversation_type.to_string())
+            .await
+            .map_err(|e| anyhow!(e))?;
+        Ok(settings
+            .into_iter()
+            .map(|s| (s.conversation_id, s))
+            .collect())
+    }
+
+    /// Conversations with users that `user_id` has blocked or has been blocked by are
+    /// left out. Pinned conversations come first; archived ones are only listed on
+    /// their own.
     pub async fn find_direct_conversations_for_user(
         &self,
         user_id: i32,
+        ConversationListQuery { archived }: ConversationListQuery,
     ) -> Result<Vec<DirectConversation>, anyhow::Error> {
         let res = self.message_repository.get_recent_messages(user_id).await;
         let conv = match res {
             Ok(conv) => conv,
             Err(e) => bail!(e),
         };
-        Ok(conv.iter().map(DirectConversation::from).collect())
+        let blocked = self.find_block_related_ids(user_id).await?;
+        let settings = self
+            .find_settings(user_id, ConversationType::Direct)
+            .await?;
+        let mut conversations: Vec<_> = conv
+            .iter()
+            .map(DirectConversation::from)
+            .filter(|c| !blocked.contains(&c.contact_id))
+            .map(|mut c| {
+                c.settings = ConversationSettingsModel::resolve(
+                    settings.get(&c.contact_id),
+                    Some(c.sent_at),
+                );
+                c
+            })
+            .filter(|c| c.settings.is_archived() == archived)
+            .collect();
+        conversations.sort_by_key(|c| (c.settings.pin_order.is_none(), c.settings.pin_order));
+        Ok(conversations)
     }
 
     pub async fn find_group_contacts_for_user(
@@ -66,9 +146,11 @@ impl ContactService {
         Ok(groups.iter().map(GroupContact::from).collect())
     }
 
+    /// Ordered and filtered like the direct conversations.
     pub async fn find_group_conversations_for_user(
         &self,
         user_id: i32,
+        ConversationListQuery { archived }: ConversationListQuery,
     ) -> Result<Vec<GroupConversation>, anyhow::Error> {
         let res = self.group_repository.find_user_group_recent(user_id).await;
 
@@ -77,7 +159,20 @@ impl ContactService {
             Err(e) => bail!(e),
         };
 
-        Ok(conversations.iter().map(GroupConversation::from).collect())
+        let settings = self.find_settings(user_id, ConversationType::Group).await?;
+        let mut conversations: Vec<_> = conversations
+            .into_iter()
+            .map(GroupConversation::from)
+            .map(|mut c| {
+                let last_activity = c.detail.as_ref().map(|d| d.sent_at);
+                c.settings =
+                    ConversationSettingsModel::resolve(settings.get(&c.group_id), last_activity);
+                c
+            })
+            .filter(|c| c.settings.is_archived() == archived)
+            .collect();
+        conversations.sort_by_key(|c| (c.settings.pin_order.is_none(), c.settings.pin_order));
+        Ok(conversations)
     }
 
     pub async fn add_user_contact(
@@ -0,0 +1,242 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+use chrono::Utc;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::repository::ConversationSettingRepositoryModel;
+use crate::repository::ConversationSummaryRepositoryModel;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum ConversationType {
+    /// Identified by the id of the other user.
+    Direct,
+    Group,
+}
+
+impl Display for ConversationType {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        let s = match self {
+            ConversationType::Direct => "direct",
+            ConversationType::Group => "group",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for ConversationType {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "direct" => Ok(ConversationType::Direct),
+            "group" => Ok(ConversationType::Group),
+            _ => Err(format!("Unsupported conversation type '{s}'")),
+        }
+    }
+}
+
+/// How long an archived conversation stays out of the conversation lists.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ArchiveMode {
+    /// Until a message arrives after it was archived.
+    UntilActivity,
+    Permanent,
+}
+
+impl Display for ArchiveMode {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        let s = match self {
+            ArchiveMode::UntilActivity => "until_activity",
+            ArchiveMode::Permanent => "permanent",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for ArchiveMode {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "until_activity" => Ok(ArchiveMode::UntilActivity),
+            "permanent" => Ok(ArchiveMode::Permanent),
+            _ => Err(format!("Unsupported archive mode '{s}'")),
+        }
+    }
+}
+
+/// Replaces every setting of a conversation.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateConversationSettingsForm {
+    #[serde(default)]
+    pub muted: bool,
+    /// Muted for good when missing.
+    pub muted_until: Option<NaiveDateTime>,
+    pub archive: Option<ArchiveMode>,
+    /// Pinned conversations come first, by ascending order.
+    pub pin_order: Option<i32>,
+}
+
+/// The settings of a conversation as they apply now: an elapsed mute and an archive
+/// ended by a new message read as unset.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationSettingsModel {
+    pub muted: bool,
+    pub muted_until: Option<NaiveDateTime>,
+    pub archive: Option<ArchiveMode>,
+    pub pin_order: Option<i32>,
+}
+
+impl ConversationSettingsModel {
+    /// `last_activity` is when the last message of the conversation was sent.
+    pub fn resolve(
+        setting: Option<&ConversationSettingRepositoryModel>,
+        last_activity: Option<NaiveDateTime>,
+    ) -> Self {
+        let Some(setting) = setting else {
+            return Self::default();
+        };
+        let now = Utc::now().naive_utc();
+        let muted = setting.muted && setting.muted_until.is_none_or(|until| until > now);
+        let archive = setting
+            .archive
+            .as_deref()
+            .and_then(|a| ArchiveMode::from_str(a).ok())
+            .filter(|archive| match archive {
+                ArchiveMode::UntilActivity => last_activity
+                    .zip(setting.archived_at)
+                    .is_none_or(|(sent_at, archived_at)| sent_at <= archived_at),
+                ArchiveMode::Permanent => true,
+            });
+        Self {
+            muted,
+            muted_until: setting.muted_until.filter(|_| muted),
+            archive,
+            pin_order: setting.pin_order,
+        }
+    }
+
+    pub fn is_archived(&self) -> bool {
+        self.archive.is_some()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationListQuery {
+    /// Lists the archived conversations instead of the others.
+    #[serde(default)]
+    pub archived: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationPageQuery {
+    /// The `nextCursor` of the previous page.
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+    /// Lists the archived conversations instead of the others.
+    #[serde(default)]
+    pub archived: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationLastMessage {
+    pub id: i32,
+    pub sender_id: i32,
+    pub sender_name: String,
+    /// Empty when the message was deleted.
+    pub preview: String,
+    pub deleted: bool,
+}
+
+/// A direct or group conversation of the inbox.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationSummary {
+    #[serde(rename = "type")]
+    pub conversation_type: ConversationType,
+    /// The id of the other user, or of the group.
+    pub id: i32,
+    pub title: String,
+    pub avatar_url: String,
+    pub last_message: Option<ConversationLastMessage>,
+    /// When the last message was sent, or the user joined a group without messages.
+    pub last_activity_at: NaiveDateTime,
+    pub unread_count: i32,
+    pub muted: bool,
+    pub pinned: bool,
+    pub pin_order: Option<i32>,
+}
+
+impl TryFrom<ConversationSummaryRepositoryModel> for ConversationSummary {
+    type Error = String;
+    fn try_from(value: ConversationSummaryRepositoryModel) -> Result<Self, Self::Error> {
+        let conversation_type = ConversationType::from_str(&value.conversation_type)?;
+        let avatar_url = match conversation_type {
+            ConversationType::Direct => format!("/api/user/avatar/{}", value.conversation_id),
+            ConversationType::Group => format!("/api/group/image/{}", value.conversation_id),
+        };
+        let last_message =
+            value
+                .last_message_id
+                .zip(value.last_sender_id)
+                .map(|(id, sender_id)| ConversationLastMessage {
+                    id,
+                    sender_id,
+                    sender_name: value.last_sender_name.unwrap_or_default(),
+                    preview: match value.last_message_deleted {
+                        true => String::new(),
+                        false => value.last_message_preview.unwrap_or_default(),
+                    },
+                    deleted: value.last_message_deleted,
+                });
+        Ok(Self {
+            conversation_type,
+            id: value.conversation_id,
+            title: value.title,
+            avatar_url,
+            last_message,
+            last_activity_at: value.last_activity_at,
+            unread_count: value.unread_count,
+            muted: value.muted,
+            pinned: value.pin_order.is_some(),
+            pin_order: value.pin_order,
+        })
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationPage {
+    pub conversations: Vec<ConversationSummary>,
+    /// Missing on the last page.
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Error, Debug)]
+pub enum ConversationError {
+    #[error("User with id {user_id} not found")]
+    UserNotFound { user_id: i32 },
+    #[error("Group with id {group_id} not found")]
+    GroupNotFound { group_id: i32 },
+    #[error("Cannot change the settings of a conversation with yourself")]
+    ConversationWithSelf,
+    #[error("Mute end must be in the future")]
+    MutedUntilInPast,
+    #[error("Cursor is invalid")]
+    InvalidCursor,
+}
@@ -0,0 +1,199 @@
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::NaiveDateTime;
+use chrono::Utc;
+
+use crate::repository::AuthRepository;
+use crate::repository::ConversationSettingRepository;
+use crate::repository::ConversationSummaryRepository;
+use crate::repository::GroupRepository;
+use crate::websocket::message::AppMessage;
+use crate::websocket::message::AppTx;
+use crate::websocket::WsResponse;
+
+use super::ConversationError;
+use super::ConversationPage;
+use super::ConversationPageQuery;
+use super::ConversationSettingsModel;
+use super::ConversationSummary;
+use super::ConversationType;
+use super::UpdateConversationSettingsForm;
+
+const DEFAULT_PAGE_SIZE: i64 = 30;
+const MAX_PAGE_SIZE: i64 = 100;
+
+#[derive(Clone)]
+pub struct ConversationService {
+    conversation_setting_repository: ConversationSettingRepository,
+    conversation_summary_repository: ConversationSummaryRepository,
+    auth_repository: AuthRepository,
+    group_repository: GroupRepository,
+    app_tx: AppTx,
+}
+
+impl ConversationService {
+    pub fn new(
+        conversation_setting_repository: ConversationSettingRepository,
+        conversation_summary_repository: ConversationSummaryRepository,
+        auth_repository: AuthRepository,
+        group_repository: GroupRepository,
+        app_tx: AppTx,
+    ) -> Self {
+        Self {
+            conversation_setting_repository,
+            conversation_summary_repository,
+            auth_repository,
+            group_repository,
+            app_tx,
+        }
+    }
+
+    async fn check_conversation(
+        &self,
+        user_id: i32,
+        conversation_type: ConversationType,
+        conversation_id: i32,
+    ) -> Result<(), anyhow::Error> {
+        match conversation_type {
+            ConversationType::Direct => {
+                if user_id == conversation_id {
+                    bail!(ConversationError::ConversationWithSelf);
+                }
+                self.auth_repository
+                    .find_user_by_id(conversation_id)
+                    .await
+                    .map_err(|e| anyhow!(e))?
+                    .ok_or(ConversationError::UserNotFound {
+                        user_id: conversation_id,
+                    })?;
+            }
+            ConversationType::Group => {
+                let members = self
+                    .group_repository
+                    .find_group_members(conversation_id)
+                    .await
+                    .map_err(|e| anyhow!(e))?;
+                if !members.contains(&user_id) {
+                    bail!(ConversationError::GroupNotFound {
+                        group_id: conversation_id
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces the mute, archive and pin settings of a conversation and sends them
+    /// to the other sessions of the user.
+    pub async fn update_settings(
+        &self,
+        user_id: i32,
+        conversation_type: ConversationType,
+        conversation_id: i32,
+        UpdateConversationSettingsForm {
+            muted,
+            muted_until,
+            archive,
+            pin_order,
+        }: UpdateConversationSettingsForm,
+    ) -> Result<ConversationSettingsModel, anyhow::Error> {
+        let muted_until = muted_until.filter(|_| muted);
+        if muted_until.is_some_and(|until| until <= Utc::now().naive_utc()) {
+            bail!(ConversationError::MutedUntilInPast);
+        }
+        self.check_conversation(user_id, conversation_type, conversation_id)
+            .await?;
+        let setting = self
+            .conversation_setting_repository
+            .upsert_setting(
+                user_id,
+                &conversation_type.to_string(),
+                conversation_id,
+                muted,
+                muted_until,
+                archive.map(|a| a.to_string()),
+                pin_order,
+            )
+            .await
+            .map_err(|e| anyhow!(e))?;
+        let settings = ConversationSettingsModel::resolve(Some(&setting), None);
+        let _ = self
+            .app_tx
+            .send(AppMessage::Notify {
+                user_ids: vec![user_id],
+                message: WsResponse::ConversationSettingsUpdated {
+                    conversation_type,
+                    conversation_id,
+                    settings: settings.clone(),
+                },
+            })
+            .await;
+        Ok(settings)
+    }
+
+    /// The cursor points at the last conversation of a page, as
+    /// `<activity in microseconds>:<type>:<id>`.
+    fn encode_cursor(conversation: &ConversationSummary) -> String {
+        let cursor = format!(
+            "{}:{}:{}",
+            conversation.last_activity_at.timestamp_micros(),
+            conversation.conversation_type,
+            conversation.id
+        );
+        URL_SAFE_NO_PAD.encode(cursor)
+    }
+
+    fn decode_cursor(cursor: &str) -> Option<(NaiveDateTime, String, i32)> {
+        let cursor = String::from_utf8(URL_SAFE_NO_PAD.decode(cursor).ok()?).ok()?;
+        let mut parts = cursor.splitn(3, ':');
+        let micros = parts.next()?.parse::<i64>().ok()?;
+        let conversation_type = ConversationType::from_str(parts.next()?).ok()?;
+        let id = parts.next()?.parse::<i32>().ok()?;
+        let activity = NaiveDateTime::from_timestamp_micros(micros)?;
+        Some((activity, conversation_type.to_string(), id))
+    }
+
+    /// The direct and group conversations of a user, most recently active first.
+    pub async fn find_conversations(
+        &self,
+        user_id: i32,
+        ConversationPageQuery {
+            cursor,
+            limit,
+            archived,
+        }: ConversationPageQuery,
+    ) -> Result<ConversationPage, anyhow::Error> {
+        let before = match cursor {
+            Some(cursor) => {
+                Some(Self::decode_cursor(&cursor).ok_or(ConversationError::InvalidCursor)?)
+            }
+            None => None,
+        };
+        let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+        // one more than asked for tells whether there is a next page
+        let mut summaries = self
+            .conversation_summary_repository
+            .find_summaries(user_id, archived, before, limit + 1)
+            .await
+            .map_err(|e| anyhow!(e))?;
+        let has_more = summaries.len() as i64 > limit;
+        summaries.truncate(limit as usize);
+        let conversations = summaries
+            .into_iter()
+            .map(ConversationSummary::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| anyhow!(e))?;
+        let next_cursor = conversations
+            .last()
+            .filter(|_| has_more)
+            .map(Self::encode_cursor);
+        Ok(ConversationPage {
+            conversations,
+            next_cursor,
+        })
+    }
+}
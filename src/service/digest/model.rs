@@ -0,0 +1,72 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+
+/// How often a user is emailed a digest of their unread conversations.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestFrequency {
+    Off,
+    Hourly,
+    Daily,
+}
+
+impl Display for DigestFrequency {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        let s = match self {
+            DigestFrequency::Off => "off",
+            DigestFrequency::Hourly => "hourly",
+            DigestFrequency::Daily => "daily",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for DigestFrequency {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(DigestFrequency::Off),
+            "hourly" => Ok(DigestFrequency::Hourly),
+            "daily" => Ok(DigestFrequency::Daily),
+            _ => Err(format!("Unsupported digest frequency '{s}'")),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DigestPreference {
+    pub frequency: DigestFrequency,
+}
+
+#[derive(Deserialize)]
+pub struct UnsubscribeDigestQuery {
+    pub token: String,
+}
+
+pub struct UnsubscribeDigestSuccess;
+
+impl Serialize for UnsubscribeDigestSuccess {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str("You will no longer receive email digests")
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum DigestError {
+    #[error("Unsubscribe link is invalid")]
+    UnknownUnsubscribeToken,
+}
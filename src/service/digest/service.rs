@@ -0,0 +1,285 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rand::RngCore;
+
+use crate::repository::ConversationSettingRepository;
+use crate::repository::DigestRepository;
+use crate::repository::DueDigestRepositoryModel;
+use crate::repository::GroupRepository;
+use crate::repository::MessageRepository;
+use crate::service::ConversationSettingsModel;
+use crate::service::ConversationType;
+use crate::service::Email;
+use crate::service::Mailer;
+use crate::service::PresenceState;
+
+use super::DigestError;
+use super::DigestFrequency;
+use super::DigestPreference;
+use super::UnsubscribeDigestSuccess;
+
+/// How many due digests are claimed at once.
+const DIGEST_BATCH_SIZE: i64 = 100;
+
+/// How much of a conversation's last message a digest quotes.
+const EXCERPT_CHARS: usize = 80;
+
+/// Emails users a digest of their unread direct and group conversations, as often
+/// as each of them chose.
+#[derive(Clone)]
+pub struct DigestService {
+    digest_repository: DigestRepository,
+    message_repository: MessageRepository,
+    group_repository: GroupRepository,
+    conversation_setting_repository: ConversationSettingRepository,
+    mailer: Mailer,
+    /// Where the API is reached from an email client, for unsubscribe links.
+    public_url: String,
+    check_interval: Duration,
+}
+
+impl DigestService {
+    pub fn new(
+        digest_repository: DigestRepository,
+        message_repository: MessageRepository,
+        group_repository: GroupRepository,
+        conversation_setting_repository: ConversationSettingRepository,
+        mailer: Mailer,
+    ) -> Self {
+        let public_url = std::env::var("PUBLIC_API_URL").unwrap_or_else(|_| {
+            let port = std::env::var("PORT").unwrap_or_else(|_| "8080".to_string());
+            format!("http://localhost:{port}")
+        });
+        let check_interval = std::env::var("DIGEST_CHECK_INTERVAL_SECS")
+            .map(|v| {
+                v.parse::<u64>()
+                    .expect("DIGEST_CHECK_INTERVAL_SECS cannot be parsed into u64")
+            })
+            .unwrap_or(300);
+        Self {
+            digest_repository,
+            message_repository,
+            group_repository,
+            conversation_setting_repository,
+            mailer,
+            public_url: public_url.trim_end_matches('/').to_string(),
+            check_interval: Duration::from_secs(check_interval),
+        }
+    }
+
+    fn generate_unsubscribe_token() -> String {
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        URL_SAFE_NO_PAD.encode(bytes)
+    }
+
+    pub async fn find_preference(
+        &self,
+        user_id: i32,
+    ) -> Result<DigestPreference, anyhow::Error> {
+        let frequency = self
+            .digest_repository
+            .find_frequency(user_id)
+            .await
+            .map_err(|e| anyhow!(e))?
+            .map(|f| DigestFrequency::from_str(&f).map_err(|e| anyhow!(e)))
+            .transpose()?
+            .unwrap_or(DigestFrequency::Off);
+        Ok(DigestPreference { frequency })
+    }
+
+    pub async fn update_preference(
+        &self,
+        user_id: i32,
+        DigestPreference { frequency }: DigestPreference,
+    ) -> Result<DigestPreference, anyhow::Error> {
+        self.digest_repository
+            .upsert_frequency(
+                user_id,
+                frequency.to_string(),
+                Self::generate_unsubscribe_token(),
+            )
+            .await
+            .map_err(|e| anyhow!(e))?;
+        Ok(DigestPreference { frequency })
+    }
+
+    /// Turns digests off for the user an unsubscribe link was sent to.
+    pub async fn unsubscribe(
+        &self,
+        token: String,
+    ) -> Result<UnsubscribeDigestSuccess, anyhow::Error> {
+        let res = self.digest_repository.unsubscribe(token).await;
+        match res {
+            Ok(true) => Ok(UnsubscribeDigestSuccess),
+            Ok(false) => bail!(DigestError::UnknownUnsubscribeToken),
+            Err(e) => bail!(e),
+        }
+    }
+
+    /// Checks for due digests every `DIGEST_CHECK_INTERVAL_SECS`. Every node may run
+    /// this, a digest is claimed by one of them.
+    pub fn start(&self) {
+        let service = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(service.check_interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = service.send_due_digests().await {
+                    log::error!("{e}");
+                }
+            }
+        });
+    }
+
+    async fn send_due_digests(&self) -> Result<(), anyhow::Error> {
+        loop {
+            let due = self
+                .digest_repository
+                .claim_due_digests(DIGEST_BATCH_SIZE)
+                .await
+                .map_err(|e| anyhow!(e))?;
+            let claimed = due.len() as i64;
+            for digest in due {
+                let user_id = digest.user_id;
+                if let Err(e) = self.send_digest(digest).await {
+                    log::error!("Failed sending the digest of user {user_id}: {e}");
+                }
+            }
+            if claimed < DIGEST_BATCH_SIZE {
+                return Ok(());
+            }
+        }
+    }
+
+    fn excerpt(content: &str) -> String {
+        match content.char_indices().nth(EXCERPT_CHARS) {
+            Some((end, _)) => format!("{}…", &content[..end]),
+            None => content.to_string(),
+        }
+    }
+
+    /// The conversations of a type the user muted, which digests leave out.
+    async fn find_muted_ids(
+        &self,
+        user_id: i32,
+        conversation_type: ConversationType,
+    ) -> Result<HashSet<i32>, anyhow::Error> {
+        let settings = self
+            .conversation_setting_repository
+            .find_settings(user_id, &conversation_type.to_string())
+            .await
+            .map_err(|e| anyhow!(e))?;
+        Ok(settings
+            .iter()
+            .filter(|s| ConversationSettingsModel::resolve(Some(s), None).muted)
+            .map(|s| s.conversation_id)
+            .collect())
+    }
+
+    /// Emails the unread conversations of a user that are not muted, unless they are
+    /// in do not disturb or nothing arrived since their previous digest.
+    async fn send_digest(
+        &self,
+        digest: DueDigestRepositoryModel,
+    ) -> Result<(), anyhow::Error> {
+        let busy = PresenceState::from_str(&digest.presence_state)
+            .is_ok_and(|state| state.suppresses_notifications());
+        if busy {
+            return Ok(());
+        }
+        let muted_contacts = self
+            .find_muted_ids(digest.user_id, ConversationType::Direct)
+            .await?;
+        let muted_groups = self
+            .find_muted_ids(digest.user_id, ConversationType::Group)
+            .await?;
+        let direct: Vec<_> = self
+            .message_repository
+            .get_recent_messages(digest.user_id)
+            .await
+            .map_err(|e| anyhow!(e))?
+            .into_iter()
+            .filter(|c| c.unread_count > 0 && !muted_contacts.contains(&c.contact_id))
+            .collect();
+        let groups: Vec<_> = self
+            .group_repository
+            .find_user_group_recent(digest.user_id)
+            .await
+            .map_err(|e| anyhow!(e))?
+            .into_iter()
+            .filter(|c| c.unread_message > 0 && !muted_groups.contains(&c.group_id))
+            .collect();
+        let last_activity = direct
+            .iter()
+            .map(|c| c.sent_at)
+            .chain(
+                groups
+                    .iter()
+                    .filter_map(|c| c.detail.as_ref().map(|d| d.sent_at)),
+            )
+            .max();
+        let Some(last_activity) = last_activity else {
+            return Ok(());
+        };
+        if digest
+            .previous_sent_at
+            .is_some_and(|sent_at| last_activity <= sent_at)
+        {
+            return Ok(());
+        }
+
+        let unread = direct.iter().map(|c| c.unread_count).sum::<i64>()
+            + groups.iter().map(|c| c.unread_message).sum::<i64>();
+        let mut body = format!(
+            "Hi {},\n\nYou have {unread} unread messages on Chatbyte.\n",
+            digest.username
+        );
+        if !direct.is_empty() {
+            body.push_str("\nDirect messages\n");
+        }
+        for c in direct.iter() {
+            let last_message = if c.deleted { "" } else { &c.last_message };
+            body.push_str(&format!(
+                "- {}: {} unread, last: \"{}\"\n",
+                c.username,
+                c.unread_count,
+                Self::excerpt(last_message)
+            ));
+        }
+        if !groups.is_empty() {
+            body.push_str("\nGroups\n");
+        }
+        for c in groups.iter() {
+            body.push_str(&format!("- {}: {} unread", c.group_name, c.unread_message));
+            if let Some(detail) = &c.detail {
+                body.push_str(&format!(
+                    ", last from {}: \"{}\"",
+                    detail.username,
+                    Self::excerpt(&detail.content)
+                ));
+            }
+            body.push('\n');
+        }
+        let unsubscribe_url = format!(
+            "{}/api/user/digest/unsubscribe?token={}",
+            self.public_url, digest.unsubscribe_token
+        );
+        body.push_str(&format!(
+            "\nTo stop receiving these emails, open {unsubscribe_url}\n"
+        ));
+        let email = Email {
+            to: digest.email,
+            subject: format!("You have {unread} unread messages"),
+            body,
+            unsubscribe_url: Some(unsubscribe_url),
+        };
+        self.mailer.send(email).await
+    }
+}
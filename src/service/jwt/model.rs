@@ -0,0 +1,28 @@
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Claims {
+    pub sub: String,
+    pub exp: u64,
+    pub iat: u64,
+}
+
+impl Claims {
+    pub fn user_id(&self) -> Result<i32, JwtError> {
+        self.sub.parse::<i32>().map_err(|_| JwtError::InvalidToken)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum JwtError {
+    #[error("Token is invalid")]
+    InvalidToken,
+    #[error("Token has expired")]
+    TokenExpired,
+    #[error("Signing key '{kid}' not found")]
+    UnknownKey { kid: String },
+    #[error("{0}")]
+    Other(anyhow::Error),
+}
@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ed25519_dalek::pkcs8::DecodePrivateKey;
+use ed25519_dalek::pkcs8::DecodePublicKey;
+use ed25519_dalek::pkcs8::EncodePrivateKey;
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::jwk::AlgorithmParameters;
+use jsonwebtoken::jwk::CommonParameters;
+use jsonwebtoken::jwk::EllipticCurve;
+use jsonwebtoken::jwk::Jwk;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::jwk::KeyAlgorithm;
+use jsonwebtoken::jwk::OctetKeyPairParameters;
+use jsonwebtoken::jwk::OctetKeyPairType;
+use jsonwebtoken::jwk::PublicKeyUse;
+use jsonwebtoken::jwk::RSAKeyParameters;
+use jsonwebtoken::jwk::RSAKeyType;
+use jsonwebtoken::Algorithm;
+use jsonwebtoken::DecodingKey;
+use jsonwebtoken::EncodingKey;
+use jsonwebtoken::Header;
+use jsonwebtoken::Validation;
+use rsa::pkcs1::DecodeRsaPrivateKey;
+use rsa::traits::PublicKeyParts;
+use rsa::RsaPrivateKey;
+use rsa::RsaPublicKey;
+
+use super::Claims;
+use super::JwtError;
+
+struct JwtKey {
+    algorithm: Algorithm,
+    encoding_key: Option<EncodingKey>,
+    decoding_key: DecodingKey,
+    jwk: Jwk,
+}
+
+impl JwtKey {
+    fn ed25519_jwk(
+        kid: &str,
+        public_key: &[u8],
+    ) -> Jwk {
+        Jwk {
+            common: Self::common_parameters(kid, KeyAlgorithm::EdDSA),
+            algorithm: AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                key_type: OctetKeyPairType::OctetKeyPair,
+                curve: EllipticCurve::Ed25519,
+                x: URL_SAFE_NO_PAD.encode(public_key),
+            }),
+        }
+    }
+
+    fn rsa_jwk(
+        kid: &str,
+        public_key: &RsaPublicKey,
+    ) -> Jwk {
+        Jwk {
+            common: Self::common_parameters(kid, KeyAlgorithm::RS256),
+            algorithm: AlgorithmParameters::RSA(RSAKeyParameters {
+                key_type: RSAKeyType::RSA,
+                n: URL_SAFE_NO_PAD.encode(public_key.n().to_bytes_be()),
+                e: URL_SAFE_NO_PAD.encode(public_key.e().to_bytes_be()),
+            }),
+        }
+    }
+
+    fn common_parameters(
+        kid: &str,
+        algorithm: KeyAlgorithm,
+    ) -> CommonParameters {
+        CommonParameters {
+            public_key_use: Some(PublicKeyUse::Signature),
+            key_algorithm: Some(algorithm),
+            key_id: Some(kid.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn from_jwk(
+        jwk: Jwk,
+        algorithm: Algorithm,
+        encoding_key: Option<EncodingKey>,
+    ) -> Result<Self, anyhow::Error> {
+        let decoding_key = DecodingKey::from_jwk(&jwk)?;
+        Ok(Self {
+            algorithm,
+            encoding_key,
+            decoding_key,
+            jwk,
+        })
+    }
+
+    fn from_private_pem(
+        kid: &str,
+        pem: &str,
+    ) -> Result<Self, anyhow::Error> {
+        if let Ok(signing_key) = ed25519_dalek::SigningKey::from_pkcs8_pem(pem) {
+            let jwk = Self::ed25519_jwk(kid, signing_key.verifying_key().as_bytes());
+            let encoding_key = EncodingKey::from_ed_pem(pem.as_bytes())?;
+            return Self::from_jwk(jwk, Algorithm::EdDSA, Some(encoding_key));
+        }
+        let private_key = RsaPrivateKey::from_pkcs8_pem(pem)
+            .or_else(|_| RsaPrivateKey::from_pkcs1_pem(pem))
+            .map_err(|_| anyhow!("Key '{kid}' is neither an Ed25519 nor an RSA private key"))?;
+        let jwk = Self::rsa_jwk(kid, &private_key.to_public_key());
+        let encoding_key = EncodingKey::from_rsa_pem(pem.as_bytes())?;
+        Self::from_jwk(jwk, Algorithm::RS256, Some(encoding_key))
+    }
+
+    fn from_public_pem(
+        kid: &str,
+        pem: &str,
+    ) -> Result<Self, anyhow::Error> {
+        if let Ok(verifying_key) = ed25519_dalek::VerifyingKey::from_public_key_pem(pem) {
+            let jwk = Self::ed25519_jwk(kid, verifying_key.as_bytes());
+            return Self::from_jwk(jwk, Algorithm::EdDSA, None);
+        }
+        let public_key = RsaPublicKey::from_public_key_pem(pem)
+            .map_err(|_| anyhow!("Key '{kid}' is neither an Ed25519 nor an RSA public key"))?;
+        let jwk = Self::rsa_jwk(kid, &public_key);
+        Self::from_jwk(jwk, Algorithm::RS256, None)
+    }
+
+    fn generate_ed25519(kid: &str) -> Result<Self, anyhow::Error> {
+        let signing_key = ed25519_dalek::SigningKey::generate(&mut rand::rngs::OsRng);
+        let der = signing_key
+            .to_pkcs8_der()
+            .map_err(|e| anyhow!(e.to_string()))?;
+        let jwk = Self::ed25519_jwk(kid, signing_key.verifying_key().as_bytes());
+        let encoding_key = EncodingKey::from_ed_der(der.as_bytes());
+        Self::from_jwk(jwk, Algorithm::EdDSA, Some(encoding_key))
+    }
+}
+
+struct JwtKeyRing {
+    active_kid: String,
+    keys: HashMap<String, JwtKey>,
+}
+
+/// Signs and verifies authentication tokens.
+///
+/// Every key is identified by the `kid` header of the tokens it signs. Only the
+/// active key signs new tokens, while every loaded key keeps verifying, so a key
+/// can be rotated out without invalidating tokens that are still in flight.
+#[derive(Clone)]
+pub struct JwtService {
+    key_ring: Arc<JwtKeyRing>,
+}
+
+impl JwtService {
+    /// Loads keys from `JWT_KEY_DIR`. Each `<kid>.pem` is a PKCS#8 (or PKCS#1 for RSA)
+    /// private key and each `<kid>.pub.pem` a verification-only public key. The signing
+    /// key is chosen with `JWT_ACTIVE_KID`, falling back to the last private key by name.
+    ///
+    /// Without `JWT_KEY_DIR` startup fails, unless `JWT_ALLOW_EPHEMERAL_KEY=true` allows
+    /// signing with a key generated on startup, which logs everyone out on restart.
+    pub fn from_env() -> Result<Self, anyhow::Error> {
+        let Ok(key_dir) = std::env::var("JWT_KEY_DIR") else {
+            let allow_ephemeral = std::env::var("JWT_ALLOW_EPHEMERAL_KEY")
+                .map(|v| v == "true")
+                .unwrap_or(false);
+            if !allow_ephemeral {
+                bail!("JWT_KEY_DIR is missing, set JWT_ALLOW_EPHEMERAL_KEY=true to sign with an ephemeral key");
+            }
+            log::warn!("JWT_KEY_DIR is missing, signing tokens with an ephemeral key");
+            let kid = format!("ephemeral-{}", Self::now());
+            let key = JwtKey::generate_ed25519(&kid)?;
+            return Ok(Self::new(kid.clone(), HashMap::from([(kid, key)])));
+        };
+        let active_kid = std::env::var("JWT_ACTIVE_KID").ok();
+        Self::from_dir(&key_dir, active_kid)
+    }
+
+    pub fn from_dir(
+        key_dir: impl AsRef<Path>,
+        active_kid: Option<String>,
+    ) -> Result<Self, anyhow::Error> {
+        let mut keys = HashMap::new();
+        let mut signing_kids = Vec::new();
+        for entry in fs::read_dir(key_dir)? {
+            let path = entry?.path();
+            let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            if let Some(kid) = file_name.strip_suffix(".pub.pem") {
+                let pem = fs::read_to_string(&path)?;
+                keys.insert(kid.to_string(), JwtKey::from_public_pem(kid, &pem)?);
+            } else if let Some(kid) = file_name.strip_suffix(".pem") {
+                let pem = fs::read_to_string(&path)?;
+                keys.insert(kid.to_string(), JwtKey::from_private_pem(kid, &pem)?);
+                signing_kids.push(kid.to_string());
+            }
+        }
+        signing_kids.sort();
+        let Some(active_kid) = active_kid.or(signing_kids.pop()) else {
+            bail!("No private signing key found in JWT_KEY_DIR");
+        };
+        match keys.get(&active_kid) {
+            Some(key) if key.encoding_key.is_some() => {}
+            Some(_) => bail!("Active key '{active_kid}' has no private key"),
+            None => bail!(JwtError::UnknownKey { kid: active_kid }),
+        };
+        log::info!("Loaded {} JWT keys, signing with '{active_kid}'", keys.len());
+        Ok(Self::new(active_kid, keys))
+    }
+
+    fn new(
+        active_kid: String,
+        keys: HashMap<String, JwtKey>,
+    ) -> Self {
+        Self {
+            key_ring: Arc::new(JwtKeyRing { active_kid, keys }),
+        }
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    pub fn sign(
+        &self,
+        user_id: i32,
+        duration: Duration,
+    ) -> Result<String, JwtError> {
+        let kid = &self.key_ring.active_kid;
+        let key = self
+            .key_ring
+            .keys
+            .get(kid)
+            .ok_or(JwtError::UnknownKey { kid: kid.clone() })?;
+        let Some(encoding_key) = &key.encoding_key else {
+            return Err(JwtError::UnknownKey { kid: kid.clone() });
+        };
+        let mut header = Header::new(key.algorithm);
+        header.kid = Some(kid.clone());
+        let iat = Self::now();
+        let claims = Claims {
+            sub: user_id.to_string(),
+            exp: iat + duration.as_secs(),
+            iat,
+        };
+        jsonwebtoken::encode(&header, &claims, encoding_key).map_err(|e| JwtError::Other(e.into()))
+    }
+
+    pub fn verify(
+        &self,
+        token: &str,
+    ) -> Result<Claims, JwtError> {
+        let header = jsonwebtoken::decode_header(token).map_err(|_| JwtError::InvalidToken)?;
+        let kid = header.kid.ok_or(JwtError::InvalidToken)?;
+        let key = self
+            .key_ring
+            .keys
+            .get(&kid)
+            .ok_or(JwtError::UnknownKey { kid })?;
+        let mut validation = Validation::new(key.algorithm);
+        validation.leeway = 0;
+        validation.set_required_spec_claims(&["exp", "sub", "iat"]);
+        let data = jsonwebtoken::decode::<Claims>(token, &key.decoding_key, &validation).map_err(
+            |e| match e.kind() {
+                ErrorKind::ExpiredSignature => JwtError::TokenExpired,
+                _ => JwtError::InvalidToken,
+            },
+        )?;
+        Ok(data.claims)
+    }
+
+    pub fn jwks(&self) -> JwkSet {
+        let mut keys = self
+            .key_ring
+            .keys
+            .iter()
+            .map(|(kid, key)| (kid.clone(), key.jwk.clone()))
+            .collect::<Vec<_>>();
+        keys.sort_by(|(a, _), (b, _)| a.cmp(b));
+        JwkSet {
+            keys: keys.into_iter().map(|(_, jwk)| jwk).collect(),
+        }
+    }
+}
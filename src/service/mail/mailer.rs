@@ -0,0 +1,95 @@
+use std::path::PathBuf;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use anyhow::anyhow;
+use chrono::Utc;
+
+use super::Email;
+
+/// Writes every email as an `.eml` file into a directory, for local development.
+#[derive(Clone)]
+pub struct FileMailer {
+    dir: PathBuf,
+    from: String,
+}
+
+impl FileMailer {
+    pub fn new(
+        dir: PathBuf,
+        from: String,
+    ) -> Self {
+        Self { dir, from }
+    }
+
+    fn render(
+        &self,
+        email: &Email,
+    ) -> String {
+        let mut message = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nContent-Type: text/plain; charset=utf-8\r\n",
+            self.from,
+            email.to,
+            email.subject,
+            Utc::now().to_rfc2822(),
+        );
+        if let Some(url) = &email.unsubscribe_url {
+            message.push_str(&format!("List-Unsubscribe: <{url}>\r\n"));
+            message.push_str("List-Unsubscribe-Post: List-Unsubscribe=One-Click\r\n");
+        }
+        message.push_str("\r\n");
+        message.push_str(&email.body.replace('\n', "\r\n"));
+        message
+    }
+
+    async fn send(
+        &self,
+        email: Email,
+    ) -> Result<(), anyhow::Error> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let path = self.dir.join(format!("{nanos}-{}.eml", email.to));
+        let message = self.render(&email);
+        let dir = self.dir.clone();
+        tokio::task::spawn_blocking(move || {
+            std::fs::create_dir_all(dir)?;
+            std::fs::write(path, message)
+        })
+        .await
+        .map_err(|e| anyhow!(e))??;
+        Ok(())
+    }
+}
+
+/// Sends the emails of the server. `MAILER` picks the transport; only `file` is
+/// supported, writing to `MAIL_DIR` (default `mail`).
+#[derive(Clone)]
+pub enum Mailer {
+    File(FileMailer),
+}
+
+impl Mailer {
+    pub fn from_env() -> Self {
+        let mailer = std::env::var("MAILER").unwrap_or_else(|_| "file".to_string());
+        let from = std::env::var("MAIL_FROM")
+            .unwrap_or_else(|_| "Chatbyte <no-reply@localhost>".to_string());
+        match mailer.as_str() {
+            "file" => {
+                let dir = std::env::var("MAIL_DIR").unwrap_or_else(|_| "mail".to_string());
+                Mailer::File(FileMailer::new(dir.into(), from))
+            }
+            _ => panic!("MAILER '{mailer}' is not supported"),
+        }
+    }
+
+    pub async fn send(
+        &self,
+        email: Email,
+    ) -> Result<(), anyhow::Error> {
+        match self {
+            Mailer::File(mailer) => mailer.send(email).await,
+        }
+    }
+}
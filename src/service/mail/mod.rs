@@ -0,0 +1,5 @@
+mod mailer;
+mod model;
+
+pub use mailer::*;
+pub use model::*;
@@ -0,0 +1,8 @@
+/// A plain text email.
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+    /// Where the recipient can stop emails like this one, sent as `List-Unsubscribe`.
+    pub unsubscribe_url: Option<String>,
+}
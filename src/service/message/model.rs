@@ -19,7 +19,7 @@ pub struct CreateAttachmentModel {
     pub attachment: Vec<u8>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct GroupMessageModel {
     pub id: i32,
@@ -30,6 +30,8 @@ pub struct GroupMessageModel {
     pub sent_at: NaiveDateTime,
     pub edited: bool,
     pub deleted: bool,
+    /// The sender is blocked by the reader, who asked for their messages to be hidden.
+    pub collapsed: bool,
     pub attachments: Vec<AttachmentModel>,
 }
 
@@ -56,9 +58,16 @@ impl GroupMessageModel {
             sent_at,
             edited,
             deleted,
+            collapsed: false,
             attachments: attachments.iter().map(|at| at.clone().into()).collect(),
         }
     }
+
+    pub fn collapse(&mut self) {
+        self.collapsed = true;
+        self.content = "".to_string();
+        self.attachments = vec![];
+    }
 }
 
 #[derive(Serialize, Clone)]
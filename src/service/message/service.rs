@@ -1,5 +1,7 @@
+use std::collections::HashSet;
 use std::io::Cursor;
 
+use anyhow::anyhow;
 use anyhow::bail;
 use bindet::FileType;
 use futures_util::future::join_all;
@@ -10,9 +12,12 @@ use sqlx::Postgres;
 
 use crate::repository::AttachmentFileType;
 use crate::repository::AttachmentRepository;
+use crate::repository::BlockRepository;
 use crate::repository::GroupRepository;
 use crate::repository::MessageRepository;
 
+use crate::service::BlockError;
+
 use super::AttachmentModel;
 use super::CreateAttachmentModel;
 use super::CreateDirectMessageModel;
@@ -26,6 +31,7 @@ pub struct MessageService {
     message_repository: MessageRepository,
     group_repository: GroupRepository,
     attachment_repository: AttachmentRepository,
+    block_repository: BlockRepository,
 }
 
 impl MessageService {
@@ -33,11 +39,13 @@ impl MessageService {
         let message_repository = MessageRepository::new(conn.clone());
         let group_repository = GroupRepository::new(conn.clone());
         let attachment_repository = AttachmentRepository::new(conn.clone());
+        let block_repository = BlockRepository::new(conn.clone());
         Self {
             conn,
             message_repository,
             group_repository,
             attachment_repository,
+            block_repository,
         }
     }
 
@@ -105,10 +113,13 @@ impl MessageService {
             sent_at: message.sent_at,
             edited: message.edited,
             deleted: message.deleted,
+            collapsed: false,
             attachments,
         })
     }
 
+    /// Rejected with [`BlockError::MessagingBlocked`] when either user has blocked
+    /// the other.
     pub async fn create_direct_message(
         &self,
         message: CreateDirectMessageModel,
@@ -120,6 +131,13 @@ impl MessageService {
             content,
             attachment,
         } = message;
+        let blocked = self
+            .block_repository
+            .is_blocked_between(sender_id, receiver_id)
+            .await?;
+        if blocked {
+            return Err(BlockError::MessagingBlocked.to_string());
+        }
         let mut tx = self.conn.begin().await.map_err(|e| e.to_string())?;
         let exec = tx.acquire().await.map_err(|e| e.to_string())?;
         let message =
@@ -209,6 +227,11 @@ impl MessageService {
             Ok(msg) => msg,
             Err(e) => bail!(e),
         };
+        let collapsed_senders = self
+            .block_repository
+            .find_collapsed_sender_ids(user_id)
+            .await
+            .map_err(|e| anyhow!(e))?;
         let messages = messages
             .iter()
             .map(|m| async {
@@ -222,12 +245,28 @@ impl MessageService {
                     res.content = "".to_string();
                     res.attachments = vec![]
                 }
+                if collapsed_senders.contains(&res.sender_id) {
+                    res.collapse();
+                }
                 res
             })
             .collect::<Vec<_>>();
         let messages = join_all(messages).await;
         Ok(messages)
     }
+
+    /// Users that want the group messages of `sender_id` collapsed.
+    pub async fn find_collapsing_reader_ids(
+        &self,
+        sender_id: i32,
+    ) -> Result<HashSet<i32>, anyhow::Error> {
+        let ids = self
+            .block_repository
+            .find_collapsing_reader_ids(sender_id)
+            .await
+            .map_err(|e| anyhow!(e))?;
+        Ok(ids.into_iter().collect())
+    }
 }
 
 pub fn detect_file_type(content: &[u8]) -> Result<AttachmentFileType, String> {
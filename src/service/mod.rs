@@ -1,13 +1,37 @@
+mod access_token;
+mod admin;
 mod attachment;
 mod auth;
+mod block;
 mod contact;
+mod conversation;
+mod digest;
 mod group;
+mod jwt;
+mod mail;
 mod message;
+mod oidc;
+mod presence;
+mod push;
 mod user;
+mod webauthn;
+mod ws_ticket;
 
+pub use access_token::*;
+pub use admin::*;
 pub use attachment::*;
 pub use auth::*;
+pub use block::*;
 pub use contact::*;
+pub use conversation::*;
+pub use digest::*;
 pub use group::*;
+pub use jwt::*;
+pub use mail::*;
 pub use message::*;
+pub use oidc::*;
+pub use presence::*;
+pub use push::*;
 pub use user::*;
+pub use webauthn::*;
+pub use ws_ticket::*;
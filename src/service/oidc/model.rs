@@ -0,0 +1,95 @@
+use serde::Deserialize;
+use thiserror::Error;
+
+#[derive(Clone)]
+pub struct OidcConfig {
+    pub issuer_url: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub redirect_uri: String,
+    pub scopes: String,
+    pub auto_provision: bool,
+    pub post_login_redirect: Option<String>,
+}
+
+impl OidcConfig {
+    /// Reads the `OIDC_*` variables. Single sign-on is disabled when `OIDC_ISSUER_URL`
+    /// is not set.
+    pub fn from_env() -> Option<Self> {
+        let issuer_url = std::env::var("OIDC_ISSUER_URL").ok()?;
+        let client_id = std::env::var("OIDC_CLIENT_ID").expect("OIDC_CLIENT_ID is missing");
+        let redirect_uri =
+            std::env::var("OIDC_REDIRECT_URI").expect("OIDC_REDIRECT_URI is missing");
+        let auto_provision = std::env::var("OIDC_AUTO_PROVISION")
+            .map(|v| v != "false")
+            .unwrap_or(true);
+        Some(Self {
+            issuer_url: issuer_url.trim_end_matches('/').to_string(),
+            client_id,
+            client_secret: std::env::var("OIDC_CLIENT_SECRET").ok(),
+            redirect_uri,
+            scopes: std::env::var("OIDC_SCOPES")
+                .unwrap_or_else(|_| "openid email profile".to_string()),
+            auto_provision,
+            post_login_redirect: std::env::var("OIDC_POST_LOGIN_REDIRECT").ok(),
+        })
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+}
+
+#[derive(Deserialize)]
+pub struct OidcTokenResponse {
+    pub id_token: String,
+}
+
+#[derive(Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    pub nonce: Option<String>,
+    pub email: Option<String>,
+    pub email_verified: Option<bool>,
+    pub preferred_username: Option<String>,
+}
+
+/// A login started by a browser: where to send it, and the state that binds the
+/// callback to it.
+pub struct OidcLoginStart {
+    pub authorization_url: String,
+    pub state: String,
+}
+
+#[derive(Deserialize)]
+pub struct OidcCallbackQuery {
+    pub code: Option<String>,
+    pub state: String,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+#[derive(Error, Debug)]
+pub enum OidcError {
+    #[error("OIDC login is not configured")]
+    NotConfigured,
+    #[error("OIDC login attempt is unknown or has expired")]
+    UnknownLoginAttempt,
+    #[error("OIDC login was not started in this browser")]
+    StateMismatch,
+    #[error("Identity provider rejected the login: {0}")]
+    ProviderError(String),
+    #[error("ID token is invalid")]
+    InvalidIdToken,
+    #[error("ID token nonce does not match the login attempt")]
+    NonceMismatch,
+    #[error("Identity provider did not return a verified email")]
+    EmailNotVerified,
+    #[error("No account is linked to this identity")]
+    AccountNotFound,
+}
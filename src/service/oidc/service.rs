@@ -0,0 +1,333 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::Algorithm;
+use jsonwebtoken::DecodingKey;
+use jsonwebtoken::Validation;
+use rand::RngCore;
+use reqwest::Url;
+use sha2::Digest;
+use sha2::Sha256;
+use tokio::sync::OnceCell;
+
+use crate::repository::AuthRepository;
+use crate::repository::OidcRepository;
+use crate::service::AuthService;
+use crate::service::AuthenticationToken;
+
+use super::IdTokenClaims;
+use super::OidcCallbackQuery;
+use super::OidcConfig;
+use super::OidcError;
+use super::OidcLoginStart;
+use super::OidcTokenResponse;
+use super::ProviderMetadata;
+
+const LOGIN_ATTEMPT_MAX_AGE_SECS: f64 = 600.0;
+
+/// Holds the state of the login a browser started, so only that browser can finish it.
+const STATE_COOKIE: &str = "chatbyte_oidc_state";
+const STATE_COOKIE_PATH: &str = "/api/auth/oidc";
+
+#[derive(Clone)]
+pub struct OidcService {
+    config: Option<OidcConfig>,
+    http: reqwest::Client,
+    metadata: Arc<OnceCell<ProviderMetadata>>,
+    oidc_repository: OidcRepository,
+    auth_repository: AuthRepository,
+    auth_service: AuthService,
+}
+
+impl OidcService {
+    pub fn new(
+        config: Option<OidcConfig>,
+        oidc_repository: OidcRepository,
+        auth_repository: AuthRepository,
+        auth_service: AuthService,
+    ) -> Self {
+        Self {
+            config,
+            http: reqwest::Client::new(),
+            metadata: Arc::new(OnceCell::new()),
+            oidc_repository,
+            auth_repository,
+            auth_service,
+        }
+    }
+
+    pub fn post_login_redirect(&self) -> Option<&str> {
+        self.config.as_ref()?.post_login_redirect.as_deref()
+    }
+
+    fn config(&self) -> Result<&OidcConfig, OidcError> {
+        self.config.as_ref().ok_or(OidcError::NotConfigured)
+    }
+
+    /// Fetches the provider's discovery document once and caches it.
+    async fn metadata(&self) -> Result<&ProviderMetadata, anyhow::Error> {
+        let config = self.config()?;
+        self.metadata
+            .get_or_try_init(|| async {
+                let url = format!("{}/.well-known/openid-configuration", config.issuer_url);
+                let metadata = self
+                    .http
+                    .get(url)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json::<ProviderMetadata>()
+                    .await?;
+                if metadata.issuer.trim_end_matches('/') != config.issuer_url {
+                    bail!(
+                        "Discovery document issuer '{}' does not match",
+                        metadata.issuer
+                    );
+                }
+                Ok(metadata)
+            })
+            .await
+    }
+
+    fn random_token() -> String {
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        URL_SAFE_NO_PAD.encode(bytes)
+    }
+
+    /// Starts an authorization-code login with PKCE and returns the provider URL the
+    /// user agent should be redirected to, along with the state to set in its
+    /// `state_cookie`.
+    pub async fn start_login(&self) -> Result<OidcLoginStart, anyhow::Error> {
+        let config = self.config()?;
+        let metadata = self.metadata().await?;
+        if let Err(e) = self
+            .oidc_repository
+            .delete_expired_login_attempts(LOGIN_ATTEMPT_MAX_AGE_SECS)
+            .await
+        {
+            log::error!("{e}");
+        }
+        let state = Self::random_token();
+        let nonce = Self::random_token();
+        let code_verifier = Self::random_token();
+        let code_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()));
+        self.oidc_repository
+            .create_login_attempt(state.clone(), code_verifier, nonce.clone())
+            .await
+            .map_err(|e| anyhow!(e))?;
+        let url = Url::parse_with_params(
+            &metadata.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", &config.client_id),
+                ("redirect_uri", &config.redirect_uri),
+                ("scope", &config.scopes),
+                ("state", &state),
+                ("nonce", &nonce),
+                ("code_challenge", &code_challenge),
+                ("code_challenge_method", "S256"),
+            ],
+        )?;
+        Ok(OidcLoginStart {
+            authorization_url: url.to_string(),
+            state,
+        })
+    }
+
+    /// A short-lived `Set-Cookie` value binding a login to the browser that started it.
+    /// It is sent along with the provider's redirect back, so `SameSite=Lax`.
+    pub fn state_cookie(
+        &self,
+        state: &str,
+    ) -> String {
+        let secure = self
+            .config
+            .as_ref()
+            .is_some_and(|c| c.redirect_uri.starts_with("https://"));
+        format!(
+            "{STATE_COOKIE}={state}; Path={STATE_COOKIE_PATH}; Max-Age={}; HttpOnly; SameSite=Lax{}",
+            LOGIN_ATTEMPT_MAX_AGE_SECS as u64,
+            if secure { "; Secure" } else { "" }
+        )
+    }
+
+    pub fn clear_state_cookie(&self) -> String {
+        format!("{STATE_COOKIE}=; Path={STATE_COOKIE_PATH}; Max-Age=0; HttpOnly; SameSite=Lax")
+    }
+
+    /// Reads the state of the login this browser started from a `Cookie` header.
+    pub fn state_from_cookies(cookies: &str) -> Option<String> {
+        cookies
+            .split(';')
+            .filter_map(|c| c.trim().split_once('='))
+            .find(|(name, _)| *name == STATE_COOKIE)
+            .map(|(_, value)| value.to_string())
+    }
+
+    /// `browser_state` is the state from the cookie of the browser the callback
+    /// arrived in, which must have started the login.
+    pub async fn finish_login(
+        &self,
+        query: OidcCallbackQuery,
+        browser_state: Option<String>,
+    ) -> Result<AuthenticationToken, anyhow::Error> {
+        let config = self.config()?;
+        if browser_state.as_deref() != Some(query.state.as_str()) {
+            bail!(OidcError::StateMismatch);
+        }
+        if let Some(error) = query.error {
+            let description = query.error_description.unwrap_or_default();
+            bail!(OidcError::ProviderError(format!("{error} {description}")));
+        }
+        let Some(code) = query.code else {
+            bail!(OidcError::ProviderError("code is missing".to_string()));
+        };
+        let attempt = self
+            .oidc_repository
+            .take_login_attempt(query.state, LOGIN_ATTEMPT_MAX_AGE_SECS)
+            .await
+            .map_err(|e| anyhow!(e))?
+            .ok_or(OidcError::UnknownLoginAttempt)?;
+        let metadata = self.metadata().await?;
+        let mut request = self.http.post(&metadata.token_endpoint).form(&[
+            ("grant_type", "authorization_code"),
+            ("code", &code),
+            ("redirect_uri", &config.redirect_uri),
+            ("client_id", &config.client_id),
+            ("code_verifier", &attempt.code_verifier),
+        ]);
+        if let Some(secret) = &config.client_secret {
+            request = request.basic_auth(&config.client_id, Some(secret));
+        }
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            let body = response.text().await.unwrap_or_default();
+            bail!(OidcError::ProviderError(body));
+        }
+        let OidcTokenResponse { id_token } = response.json().await?;
+        let claims = self
+            .verify_id_token(metadata, &id_token, &attempt.nonce)
+            .await?;
+        let user_id = self.resolve_user(claims).await?;
+        self.auth_service.issue_token(user_id).await
+    }
+
+    async fn verify_id_token(
+        &self,
+        metadata: &ProviderMetadata,
+        id_token: &str,
+        nonce: &str,
+    ) -> Result<IdTokenClaims, anyhow::Error> {
+        let config = self.config()?;
+        let header =
+            jsonwebtoken::decode_header(id_token).map_err(|_| OidcError::InvalidIdToken)?;
+        if matches!(
+            header.alg,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        ) {
+            bail!(OidcError::InvalidIdToken);
+        }
+        let jwks = self
+            .http
+            .get(&metadata.jwks_uri)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<JwkSet>()
+            .await?;
+        let jwk = match &header.kid {
+            Some(kid) => jwks.find(kid),
+            None if jwks.keys.len() == 1 => jwks.keys.first(),
+            None => None,
+        };
+        let jwk = jwk.ok_or(OidcError::InvalidIdToken)?;
+        let key = DecodingKey::from_jwk(jwk)?;
+        let mut validation = Validation::new(header.alg);
+        validation.set_audience(&[&config.client_id]);
+        validation.set_issuer(&[&metadata.issuer]);
+        let claims = jsonwebtoken::decode::<IdTokenClaims>(id_token, &key, &validation)
+            .map_err(|_| OidcError::InvalidIdToken)?
+            .claims;
+        if claims.nonce.as_deref() != Some(nonce) {
+            bail!(OidcError::NonceMismatch);
+        }
+        Ok(claims)
+    }
+
+    /// Maps the provider identity to a local user: an existing link wins, then an
+    /// account with the same verified email, and finally a freshly provisioned user.
+    async fn resolve_user(
+        &self,
+        claims: IdTokenClaims,
+    ) -> Result<i32, anyhow::Error> {
+        let IdTokenClaims {
+            iss,
+            sub,
+            email,
+            email_verified,
+            preferred_username,
+            ..
+        } = claims;
+        let linked = self
+            .oidc_repository
+            .find_user_id_by_identity(iss.clone(), sub.clone())
+            .await
+            .map_err(|e| anyhow!(e))?;
+        if let Some(user_id) = linked {
+            return Ok(user_id);
+        }
+        let email = match (email, email_verified) {
+            (Some(email), Some(true)) => email,
+            _ => bail!(OidcError::EmailNotVerified),
+        };
+        let existing = self
+            .auth_repository
+            .find_user_by_email(email.clone())
+            .await
+            .map_err(|e| anyhow!(e))?;
+        let user_id = match existing {
+            Some(user) => user.id,
+            None if self.config()?.auto_provision => {
+                self.provision_user(email, preferred_username).await?
+            }
+            None => bail!(OidcError::AccountNotFound),
+        };
+        self.oidc_repository
+            .link_identity(iss, sub, user_id)
+            .await
+            .map_err(|e| anyhow!(e))?;
+        Ok(user_id)
+    }
+
+    async fn provision_user(
+        &self,
+        email: String,
+        preferred_username: Option<String>,
+    ) -> Result<i32, anyhow::Error> {
+        let mut username = email.clone();
+        if let Some(preferred) = preferred_username {
+            let taken = self
+                .auth_repository
+                .find_user_by_username(preferred.clone())
+                .await
+                .map_err(|e| anyhow!(e))?
+                .is_some();
+            if !taken {
+                username = preferred;
+            }
+        }
+        log::info!("Provisioning user '{username}' from OIDC login");
+        // Provisioned users sign in through the provider, so the password is never handed out.
+        let user = self
+            .auth_repository
+            .create_user_with_username(username, email, Self::random_token())
+            .await
+            .map_err(|e| anyhow!(e))?;
+        Ok(user.id)
+    }
+}
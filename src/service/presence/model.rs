@@ -0,0 +1,130 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+/// Who may see a user's online status and last-seen time.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PresenceVisibility {
+    Everyone,
+    /// Only users sharing a direct conversation or a group.
+    Contacts,
+    Nobody,
+}
+
+impl PresenceVisibility {
+    pub fn visible_to(
+        &self,
+        is_conversation_partner: bool,
+    ) -> bool {
+        match self {
+            PresenceVisibility::Everyone => true,
+            PresenceVisibility::Contacts => is_conversation_partner,
+            PresenceVisibility::Nobody => false,
+        }
+    }
+}
+
+impl Display for PresenceVisibility {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        let s = match self {
+            PresenceVisibility::Everyone => "everyone",
+            PresenceVisibility::Contacts => "contacts",
+            PresenceVisibility::Nobody => "nobody",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for PresenceVisibility {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "everyone" => Ok(PresenceVisibility::Everyone),
+            "contacts" => Ok(PresenceVisibility::Contacts),
+            "nobody" => Ok(PresenceVisibility::Nobody),
+            _ => Err(format!("Unsupported presence visibility '{s}'")),
+        }
+    }
+}
+
+/// The state a user shows to their contacts. `Offline` is never chosen, it is what
+/// contacts see when the user has no session or is invisible.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PresenceState {
+    Online,
+    Away,
+    /// Do not disturb; push and email notifications are held back.
+    Busy,
+    Invisible,
+    Offline,
+}
+
+impl PresenceState {
+    /// The state as contacts see it.
+    pub fn public(self) -> Self {
+        match self {
+            PresenceState::Invisible => PresenceState::Offline,
+            state => state,
+        }
+    }
+
+    pub fn suppresses_notifications(self) -> bool {
+        self == PresenceState::Busy
+    }
+}
+
+impl Display for PresenceState {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        let s = match self {
+            PresenceState::Online => "online",
+            PresenceState::Away => "away",
+            PresenceState::Busy => "busy",
+            PresenceState::Invisible => "invisible",
+            PresenceState::Offline => "offline",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for PresenceState {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "online" => Ok(PresenceState::Online),
+            "away" => Ok(PresenceState::Away),
+            "busy" => Ok(PresenceState::Busy),
+            "invisible" => Ok(PresenceState::Invisible),
+            "offline" => Ok(PresenceState::Offline),
+            _ => Err(format!("Unsupported presence state '{s}'")),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct UpdatePresenceVisibilityForm {
+    pub visibility: PresenceVisibility,
+}
+
+pub struct UpdatePresenceVisibilitySuccess;
+
+impl Serialize for UpdatePresenceVisibilitySuccess {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str("Successfully updated presence visibility")
+    }
+}
@@ -0,0 +1,47 @@
+use anyhow::bail;
+
+use crate::repository::PresenceRepository;
+use crate::websocket::message::AppMessage;
+use crate::websocket::message::AppTx;
+
+use super::UpdatePresenceVisibilityForm;
+use super::UpdatePresenceVisibilitySuccess;
+
+#[derive(Clone)]
+pub struct PresenceService {
+    presence_repository: PresenceRepository,
+    app_tx: AppTx,
+}
+
+impl PresenceService {
+    pub fn new(
+        presence_repository: PresenceRepository,
+        app_tx: AppTx,
+    ) -> Self {
+        Self {
+            presence_repository,
+            app_tx,
+        }
+    }
+
+    pub async fn update_visibility(
+        &self,
+        user_id: i32,
+        UpdatePresenceVisibilityForm { visibility }: UpdatePresenceVisibilityForm,
+    ) -> Result<UpdatePresenceVisibilitySuccess, anyhow::Error> {
+        let res = self
+            .presence_repository
+            .update_visibility(user_id, visibility.to_string())
+            .await;
+        match res {
+            Ok(succ) if succ => {}
+            Ok(_) => bail!("User not found"),
+            Err(e) => bail!(e),
+        };
+        let _ = self
+            .app_tx
+            .send(AppMessage::PresenceChanged { user_id })
+            .await;
+        Ok(UpdatePresenceVisibilitySuccess)
+    }
+}
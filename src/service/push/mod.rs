@@ -0,0 +1,7 @@
+mod model;
+mod service;
+mod webpush;
+
+pub use model::*;
+pub use service::*;
+pub use webpush::*;
@@ -0,0 +1,116 @@
+use chrono::NaiveDateTime;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::repository::PushSubscriptionRepositoryModel;
+
+#[derive(Deserialize)]
+pub struct PushSubscriptionKeys {
+    pub p256dh: String,
+    pub auth: String,
+}
+
+/// A subscription as returned by `PushSubscription.toJSON()` in the browser.
+#[derive(Deserialize)]
+pub struct CreatePushSubscriptionForm {
+    pub endpoint: String,
+    pub keys: PushSubscriptionKeys,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PushSubscriptionModel {
+    pub id: i32,
+    pub endpoint: String,
+    pub user_agent: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<PushSubscriptionRepositoryModel> for PushSubscriptionModel {
+    fn from(value: PushSubscriptionRepositoryModel) -> Self {
+        Self {
+            id: value.id,
+            endpoint: value.endpoint,
+            user_agent: value.user_agent,
+            created_at: value.created_at,
+        }
+    }
+}
+
+/// The `applicationServerKey` to subscribe with.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VapidPublicKey {
+    pub public_key: String,
+}
+
+pub struct DeletePushSubscriptionSuccess;
+
+impl Serialize for DeletePushSubscriptionSuccess {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str("Successfully removed push subscription")
+    }
+}
+
+/// A message that is pushed to the users it concerns who have no live session.
+/// A group message concerns the members it mentions.
+#[derive(Clone, Debug)]
+pub enum PushNotification {
+    DirectMessage {
+        message_id: i32,
+        sender_id: i32,
+        content: String,
+        sent_at: NaiveDateTime,
+    },
+    GroupMention {
+        message_id: i32,
+        group_id: i32,
+        sender_id: i32,
+        sender_name: String,
+        content: String,
+        sent_at: NaiveDateTime,
+    },
+}
+
+/// The JSON a service worker receives in its `push` event.
+#[derive(Serialize)]
+#[serde(tag = "type")]
+pub enum PushPayload {
+    #[serde(rename = "DIRECT_MESSAGE")]
+    #[serde(rename_all = "camelCase")]
+    DirectMessage {
+        message_id: i32,
+        sender_id: i32,
+        sender_name: String,
+        preview: String,
+        sent_at: NaiveDateTime,
+    },
+    #[serde(rename = "GROUP_MENTION")]
+    #[serde(rename_all = "camelCase")]
+    GroupMention {
+        message_id: i32,
+        group_id: i32,
+        group_name: String,
+        sender_id: i32,
+        sender_name: String,
+        preview: String,
+        sent_at: NaiveDateTime,
+    },
+}
+
+#[derive(Error, Debug)]
+pub enum PushError {
+    #[error("Push endpoint must be an https url")]
+    InvalidEndpoint,
+    #[error("Push subscription keys are invalid")]
+    InvalidKeys,
+    #[error("Push subscription not found")]
+    SubscriptionNotFound,
+}
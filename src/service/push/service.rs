@@ -0,0 +1,362 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use futures::future::join_all;
+use reqwest::header::AUTHORIZATION;
+use reqwest::header::CONTENT_ENCODING;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::StatusCode;
+use reqwest::Url;
+
+use crate::repository::ConversationSettingRepository;
+use crate::repository::PushRepository;
+use crate::repository::PushTargetRepositoryModel;
+use crate::service::ConversationType;
+use crate::service::PresenceState;
+
+use super::CreatePushSubscriptionForm;
+use super::DeletePushSubscriptionSuccess;
+use super::PushError;
+use super::PushNotification;
+use super::PushPayload;
+use super::PushSubscriptionModel;
+use super::SubscriptionKeys;
+use super::VapidKey;
+use super::VapidPublicKey;
+
+/// How long a push service keeps a notification for a device that is offline.
+const PUSH_TTL_SECS: u64 = 24 * 60 * 60;
+
+/// How much of a message is shown in its notification.
+const PREVIEW_CHARS: usize = 120;
+
+/// Sends Web Push notifications to the subscribed browsers of users who are not
+/// connected.
+#[derive(Clone)]
+pub struct PushService {
+    push_repository: PushRepository,
+    conversation_setting_repository: ConversationSettingRepository,
+    vapid_key: Arc<VapidKey>,
+    http: reqwest::Client,
+    /// Whether subscriptions may use plain http endpoints, e.g. a local stand-in.
+    allow_http_endpoints: bool,
+}
+
+impl PushService {
+    pub fn new(
+        push_repository: PushRepository,
+        conversation_setting_repository: ConversationSettingRepository,
+        vapid_key: VapidKey,
+    ) -> Self {
+        let allow_http_endpoints = std::env::var("PUSH_ALLOW_HTTP_ENDPOINTS")
+            .map(|v| v == "true")
+            .unwrap_or(false);
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .expect("Failed building the push http client");
+        Self {
+            push_repository,
+            conversation_setting_repository,
+            vapid_key: Arc::new(vapid_key),
+            http,
+            allow_http_endpoints,
+        }
+    }
+
+    pub fn vapid_public_key(&self) -> VapidPublicKey {
+        VapidPublicKey {
+            public_key: self.vapid_key.public_key().to_string(),
+        }
+    }
+
+    pub async fn subscribe(
+        &self,
+        user_id: i32,
+        user_agent: Option<String>,
+        CreatePushSubscriptionForm { endpoint, keys }: CreatePushSubscriptionForm,
+    ) -> Result<PushSubscriptionModel, anyhow::Error> {
+        let scheme_allowed = match Url::parse(&endpoint) {
+            Ok(url) if url.scheme() == "https" => true,
+            Ok(url) => url.scheme() == "http" && self.allow_http_endpoints,
+            Err(_) => false,
+        };
+        if !scheme_allowed {
+            bail!(PushError::InvalidEndpoint);
+        }
+        SubscriptionKeys::decode(&keys.p256dh, &keys.auth)?;
+        let subscription = self
+            .push_repository
+            .upsert_subscription(user_id, endpoint, keys.p256dh, keys.auth, user_agent)
+            .await
+            .map_err(|e| anyhow!(e))?;
+        Ok(subscription.into())
+    }
+
+    pub async fn find_subscriptions(
+        &self,
+        user_id: i32,
+    ) -> Result<Vec<PushSubscriptionModel>, anyhow::Error> {
+        let subscriptions = self
+            .push_repository
+            .find_subscriptions(user_id)
+            .await
+            .map_err(|e| anyhow!(e))?;
+        Ok(subscriptions.into_iter().map(Into::into).collect())
+    }
+
+    pub async fn unsubscribe(
+        &self,
+        user_id: i32,
+        subscription_id: i32,
+    ) -> Result<DeletePushSubscriptionSuccess, anyhow::Error> {
+        let res = self
+            .push_repository
+            .delete_subscription(subscription_id, user_id)
+            .await;
+        match res {
+            Ok(true) => Ok(DeletePushSubscriptionSuccess),
+            Ok(false) => bail!(PushError::SubscriptionNotFound),
+            Err(e) => bail!(e),
+        }
+    }
+
+    /// The members mentioned as `@<username>` in a group message.
+    async fn find_mentioned_ids(
+        &self,
+        member_ids: Vec<i32>,
+        content: &str,
+    ) -> Result<Vec<i32>, anyhow::Error> {
+        if member_ids.is_empty() || !content.contains('@') {
+            return Ok(Vec::new());
+        }
+        let members = self
+            .push_repository
+            .find_usernames(member_ids)
+            .await
+            .map_err(|e| anyhow!(e))?;
+        let content = content.to_lowercase();
+        let mentioned = members
+            .into_iter()
+            .filter(|m| Self::mentions(&content, &m.username.to_lowercase()))
+            .map(|m| m.id)
+            .collect();
+        Ok(mentioned)
+    }
+
+    /// Whether `content` has `@username` that is not the start of a longer name.
+    fn mentions(
+        content: &str,
+        username: &str,
+    ) -> bool {
+        let mention = format!("@{username}");
+        content.match_indices(&mention).any(|(at, _)| {
+            content[at + mention.len()..]
+                .chars()
+                .next()
+                .is_none_or(|c| !c.is_alphanumeric() && c != '_')
+        })
+    }
+
+    /// Drops the users who muted the conversation of a notification.
+    async fn without_muted(
+        &self,
+        user_ids: Vec<i32>,
+        notification: &PushNotification,
+    ) -> Result<Vec<i32>, anyhow::Error> {
+        let (conversation_type, conversation_id) = match notification {
+            PushNotification::DirectMessage { sender_id, .. } => {
+                (ConversationType::Direct, *sender_id)
+            }
+            PushNotification::GroupMention { group_id, .. } => (ConversationType::Group, *group_id),
+        };
+        let muted = self
+            .conversation_setting_repository
+            .find_muted_user_ids(
+                user_ids.clone(),
+                &conversation_type.to_string(),
+                conversation_id,
+            )
+            .await
+            .map_err(|e| anyhow!(e))?;
+        Ok(user_ids
+            .into_iter()
+            .filter(|id| !muted.contains(id))
+            .collect())
+    }
+
+    /// Users in do not disturb get no notifications.
+    fn wants_notification(target: &PushTargetRepositoryModel) -> bool {
+        PresenceState::from_str(&target.presence_state)
+            .map(|state| !state.suppresses_notifications())
+            .unwrap_or(true)
+    }
+
+    /// Pushes a notification to every subscription of the given users in the
+    /// background. Of the members of a group, only those mentioned are notified, and
+    /// nobody who muted the conversation.
+    pub fn dispatch(
+        &self,
+        user_ids: Vec<i32>,
+        notification: PushNotification,
+    ) {
+        if user_ids.is_empty() {
+            return;
+        }
+        let service = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = service.send_notification(user_ids, notification).await {
+                log::error!("{e}");
+            }
+        });
+    }
+
+    async fn send_notification(
+        &self,
+        user_ids: Vec<i32>,
+        notification: PushNotification,
+    ) -> Result<(), anyhow::Error> {
+        let user_ids = match &notification {
+            PushNotification::DirectMessage { .. } => user_ids,
+            PushNotification::GroupMention { content, .. } => {
+                self.find_mentioned_ids(user_ids, content).await?
+            }
+        };
+        if user_ids.is_empty() {
+            return Ok(());
+        }
+        let user_ids = self.without_muted(user_ids, &notification).await?;
+        if user_ids.is_empty() {
+            return Ok(());
+        }
+        let targets = self
+            .push_repository
+            .find_targets(user_ids)
+            .await
+            .map_err(|e| anyhow!(e))?;
+        let targets: Vec<_> = targets
+            .into_iter()
+            .filter(Self::wants_notification)
+            .collect();
+        if targets.is_empty() {
+            return Ok(());
+        }
+        let payload = serde_json::to_vec(&self.payload(notification).await?)?;
+        join_all(targets.iter().map(|t| self.push(t, &payload))).await;
+        Ok(())
+    }
+
+    fn preview(content: String) -> String {
+        match content.char_indices().nth(PREVIEW_CHARS) {
+            Some((end, _)) => format!("{}…", &content[..end]),
+            None => content,
+        }
+    }
+
+    async fn payload(
+        &self,
+        notification: PushNotification,
+    ) -> Result<PushPayload, anyhow::Error> {
+        let payload = match notification {
+            PushNotification::DirectMessage {
+                message_id,
+                sender_id,
+                content,
+                sent_at,
+            } => {
+                let sender_name = self
+                    .push_repository
+                    .find_usernames(vec![sender_id])
+                    .await
+                    .map_err(|e| anyhow!(e))?
+                    .pop()
+                    .map(|u| u.username)
+                    .unwrap_or_default();
+                PushPayload::DirectMessage {
+                    message_id,
+                    sender_id,
+                    sender_name,
+                    preview: Self::preview(content),
+                    sent_at,
+                }
+            }
+            PushNotification::GroupMention {
+                message_id,
+                group_id,
+                sender_id,
+                sender_name,
+                content,
+                sent_at,
+            } => {
+                let group_name = self
+                    .push_repository
+                    .find_group_name(group_id)
+                    .await
+                    .map_err(|e| anyhow!(e))?
+                    .unwrap_or_default();
+                PushPayload::GroupMention {
+                    message_id,
+                    group_id,
+                    group_name,
+                    sender_id,
+                    sender_name,
+                    preview: Self::preview(content),
+                    sent_at,
+                }
+            }
+        };
+        Ok(payload)
+    }
+
+    /// Sends an encrypted payload to one subscription, dropping the subscription
+    /// when the push service says it is gone.
+    async fn push(
+        &self,
+        target: &PushTargetRepositoryModel,
+        payload: &[u8],
+    ) {
+        let res = self.send_push(target, payload).await;
+        match res {
+            Ok(status) if status == StatusCode::NOT_FOUND || status == StatusCode::GONE => {
+                log::info!("Removing expired push subscription {}", target.id);
+                if let Err(e) = self
+                    .push_repository
+                    .delete_gone_subscription(target.id)
+                    .await
+                {
+                    log::error!("{e}");
+                }
+            }
+            Ok(status) if !status.is_success() => {
+                log::warn!("Push to subscription {} failed with {status}", target.id);
+            }
+            Ok(_) => {}
+            Err(e) => log::warn!("Push to subscription {} failed: {e}", target.id),
+        }
+    }
+
+    async fn send_push(
+        &self,
+        target: &PushTargetRepositoryModel,
+        payload: &[u8],
+    ) -> Result<StatusCode, anyhow::Error> {
+        let body = SubscriptionKeys::decode(&target.p256dh, &target.auth)?.encrypt(payload)?;
+        let res = self
+            .http
+            .post(&target.endpoint)
+            .header(
+                AUTHORIZATION,
+                self.vapid_key.authorization(&target.endpoint)?,
+            )
+            .header(CONTENT_ENCODING, "aes128gcm")
+            .header(CONTENT_TYPE, "application/octet-stream")
+            .header("TTL", PUSH_TTL_SECS)
+            .body(body)
+            .send()
+            .await?;
+        Ok(res.status())
+    }
+}
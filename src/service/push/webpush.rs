@@ -0,0 +1,189 @@
+use std::fs;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use aes_gcm::aead::Aead;
+use aes_gcm::Aes128Gcm;
+use aes_gcm::KeyInit;
+use aes_gcm::Nonce;
+use anyhow::anyhow;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hkdf::Hkdf;
+use jsonwebtoken::Algorithm;
+use jsonwebtoken::EncodingKey;
+use jsonwebtoken::Header;
+use p256::ecdh::EphemeralSecret;
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use p256::pkcs8::DecodePrivateKey;
+use p256::pkcs8::EncodePrivateKey;
+use p256::PublicKey;
+use p256::SecretKey;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use reqwest::Url;
+use serde::Serialize;
+use sha2::Sha256;
+
+use super::PushError;
+
+/// How long the `Authorization` of a push is valid; push services refuse more than 24 hours.
+const VAPID_TOKEN_TTL: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// The record size announced in the `aes128gcm` header. Payloads are sent as a
+/// single record, so it only has to be larger than the payload.
+const RECORD_SIZE: u32 = 4096;
+
+#[derive(Serialize)]
+struct VapidClaims<'a> {
+    aud: String,
+    exp: u64,
+    sub: &'a str,
+}
+
+/// The application server key pushes are signed with (VAPID, RFC 8292). Browsers
+/// bind a subscription to the public key it was created with, so replacing the key
+/// makes every existing subscription useless.
+pub struct VapidKey {
+    encoding_key: EncodingKey,
+    /// The uncompressed public point, base64url encoded.
+    public_key: String,
+    /// A `mailto:` or `https:` contact for the push service operators.
+    subject: String,
+}
+
+impl VapidKey {
+    /// Loads the P-256 private key in `VAPID_KEY_FILE`, as PKCS#8 or SEC1 PEM.
+    /// Without it an ephemeral key is generated.
+    pub fn from_env() -> Result<Self, anyhow::Error> {
+        let subject =
+            std::env::var("VAPID_SUBJECT").unwrap_or_else(|_| "mailto:admin@localhost".to_string());
+        let Ok(key_file) = std::env::var("VAPID_KEY_FILE") else {
+            log::warn!(
+                "VAPID_KEY_FILE is missing, signing pushes with an ephemeral key that invalidates subscriptions on restart"
+            );
+            return Self::new(SecretKey::random(&mut OsRng), subject);
+        };
+        let pem = fs::read_to_string(key_file)?;
+        let secret_key = SecretKey::from_pkcs8_pem(&pem)
+            .or_else(|_| SecretKey::from_sec1_pem(&pem))
+            .map_err(|_| anyhow!("VAPID_KEY_FILE is not a P-256 private key"))?;
+        Self::new(secret_key, subject)
+    }
+
+    fn new(
+        secret_key: SecretKey,
+        subject: String,
+    ) -> Result<Self, anyhow::Error> {
+        let der = secret_key
+            .to_pkcs8_der()
+            .map_err(|e| anyhow!(e.to_string()))?;
+        let public_key = secret_key.public_key().to_encoded_point(false);
+        Ok(Self {
+            encoding_key: EncodingKey::from_ec_der(der.as_bytes()),
+            public_key: URL_SAFE_NO_PAD.encode(public_key.as_bytes()),
+            subject,
+        })
+    }
+
+    pub fn public_key(&self) -> &str {
+        &self.public_key
+    }
+
+    /// The `Authorization` header of a push to `endpoint`, with a token for the
+    /// endpoint's origin.
+    pub fn authorization(
+        &self,
+        endpoint: &str,
+    ) -> Result<String, anyhow::Error> {
+        let audience = Url::parse(endpoint)?.origin().ascii_serialization();
+        let exp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .saturating_add(VAPID_TOKEN_TTL)
+            .as_secs();
+        let claims = VapidClaims {
+            aud: audience,
+            exp,
+            sub: &self.subject,
+        };
+        let token =
+            jsonwebtoken::encode(&Header::new(Algorithm::ES256), &claims, &self.encoding_key)?;
+        Ok(format!("vapid t={token}, k={}", self.public_key))
+    }
+}
+
+fn decode_base64url(value: &str) -> Result<Vec<u8>, PushError> {
+    URL_SAFE_NO_PAD
+        .decode(value.trim_end_matches('='))
+        .map_err(|_| PushError::InvalidKeys)
+}
+
+/// The keys of a subscription: the browser's P-256 public key (`p256dh`) and the
+/// 16 byte authentication secret (`auth`).
+pub struct SubscriptionKeys {
+    public_key: PublicKey,
+    auth_secret: Vec<u8>,
+}
+
+impl SubscriptionKeys {
+    pub fn decode(
+        p256dh: &str,
+        auth: &str,
+    ) -> Result<Self, PushError> {
+        let public_key = PublicKey::from_sec1_bytes(&decode_base64url(p256dh)?)
+            .map_err(|_| PushError::InvalidKeys)?;
+        let auth_secret = decode_base64url(auth)?;
+        if auth_secret.len() != 16 {
+            return Err(PushError::InvalidKeys);
+        }
+        Ok(Self {
+            public_key,
+            auth_secret,
+        })
+    }
+
+    /// Encrypts a payload for the subscription as a single `aes128gcm` record
+    /// (RFC 8188), keyed as Web Push requires (RFC 8291).
+    pub fn encrypt(
+        &self,
+        payload: &[u8],
+    ) -> Result<Vec<u8>, anyhow::Error> {
+        let ua_public = self.public_key.to_encoded_point(false);
+        let as_secret = EphemeralSecret::random(&mut OsRng);
+        let as_public = as_secret.public_key().to_encoded_point(false);
+        let shared_secret = as_secret.diffie_hellman(&self.public_key);
+
+        let mut key_info = b"WebPush: info\0".to_vec();
+        key_info.extend_from_slice(ua_public.as_bytes());
+        key_info.extend_from_slice(as_public.as_bytes());
+        let mut ikm = [0u8; 32];
+        Hkdf::<Sha256>::new(Some(&self.auth_secret), shared_secret.raw_secret_bytes())
+            .expand(&key_info, &mut ikm)
+            .map_err(|e| anyhow!(e.to_string()))?;
+
+        let mut salt = [0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        let hkdf = Hkdf::<Sha256>::new(Some(&salt), &ikm);
+        let mut cek = [0u8; 16];
+        let mut nonce = [0u8; 12];
+        hkdf.expand(b"Content-Encoding: aes128gcm\0", &mut cek)
+            .and_then(|_| hkdf.expand(b"Content-Encoding: nonce\0", &mut nonce))
+            .map_err(|e| anyhow!(e.to_string()))?;
+
+        // the delimiter of the last record
+        let mut plaintext = payload.to_vec();
+        plaintext.push(2);
+        let ciphertext = Aes128Gcm::new_from_slice(&cek)?
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+            .map_err(|_| anyhow!("Failed encrypting push payload"))?;
+
+        let mut body = salt.to_vec();
+        body.extend_from_slice(&RECORD_SIZE.to_be_bytes());
+        body.push(as_public.len() as u8);
+        body.extend_from_slice(as_public.as_bytes());
+        body.extend_from_slice(&ciphertext);
+        Ok(body)
+    }
+}
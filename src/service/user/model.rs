@@ -1,9 +1,73 @@
+use chrono::NaiveDateTime;
+use serde::Deserialize;
 use serde::Serialize;
+use thiserror::Error;
+
+use crate::repository::PublicUserRepositoryModel;
+use crate::repository::UserProfileRepositoryModel;
+use crate::service::PresenceVisibility;
+use crate::service::Role;
 
 #[derive(Serialize)]
 pub struct UserDetail {
     pub user_id: i32,
     pub username: String,
+    pub role: Role,
+    pub password_reset_required: bool,
+    pub discoverable: bool,
+    pub presence_visibility: PresenceVisibility,
+    pub profile: UserProfile,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UserProfile {
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub pronouns: Option<String>,
+    pub status: Option<UserStatus>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UserStatus {
+    pub text: String,
+    pub emoji: Option<String>,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+impl From<UserProfileRepositoryModel> for UserProfile {
+    fn from(value: UserProfileRepositoryModel) -> Self {
+        let status = value.status_text.map(|text| UserStatus {
+            text,
+            emoji: value.status_emoji,
+            expires_at: value.status_expires_at,
+        });
+        Self {
+            display_name: value.display_name,
+            bio: value.bio,
+            pronouns: value.pronouns,
+            status,
+        }
+    }
+}
+
+impl From<&UserProfileRepositoryModel> for UserProfile {
+    fn from(value: &UserProfileRepositoryModel) -> Self {
+        UserProfile::from(value.clone())
+    }
+}
+
+/// Replaces the whole profile; omitted or blank fields are cleared.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateProfileForm {
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub pronouns: Option<String>,
+    pub status_text: Option<String>,
+    pub status_emoji: Option<String>,
+    pub status_expires_in_mins: Option<i64>,
 }
 
 pub struct SuccessfullyUpdateUser;
@@ -19,3 +83,43 @@ impl Serialize for SuccessfullyUpdateUser {
         serializer.serialize_str("Successfully updated user")
     }
 }
+
+#[derive(Deserialize)]
+pub struct UserSearchQuery {
+    pub q: String,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateDiscoverableForm {
+    pub discoverable: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicUserModel {
+    pub user_id: i32,
+    pub username: String,
+}
+
+impl From<PublicUserRepositoryModel> for PublicUserModel {
+    fn from(value: PublicUserRepositoryModel) -> Self {
+        Self {
+            user_id: value.id,
+            username: value.username,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum UserError {
+    #[error("Search query must not be empty")]
+    EmptySearchQuery,
+    #[error("User with id {user_id} not found")]
+    NotFound { user_id: i32 },
+    #[error("{field} must be at most {max} characters long")]
+    FieldTooLong { field: &'static str, max: usize },
+    #[error("Status expiry must be a positive number of minutes")]
+    InvalidStatusExpiry,
+}
@@ -1,28 +1,109 @@
+use std::str::FromStr;
+
 use anyhow::anyhow;
+use anyhow::bail;
 
 use crate::repository::AuthRepository;
+use crate::repository::UserProfileRepositoryModel;
 use crate::repository::UserRepository;
+use crate::service::PresenceVisibility;
+use crate::service::Role;
+use crate::websocket::message::AppMessage;
+use crate::websocket::message::AppTx;
+use crate::websocket::WsResponse;
 
+use super::PublicUserModel;
 use super::SuccessfullyUpdateUser;
+use super::UpdateDiscoverableForm;
+use super::UpdateProfileForm;
 use super::UserDetail;
+use super::UserError;
+use super::UserProfile;
+use super::UserSearchQuery;
+
+const DEFAULT_SEARCH_PAGE_SIZE: i64 = 20;
+const MAX_SEARCH_PAGE_SIZE: i64 = 50;
+const MAX_DISPLAY_NAME_LENGTH: usize = 50;
+const MAX_BIO_LENGTH: usize = 500;
+const MAX_PRONOUNS_LENGTH: usize = 40;
+const MAX_STATUS_TEXT_LENGTH: usize = 100;
+const MAX_STATUS_EMOJI_LENGTH: usize = 16;
 
 #[derive(Clone)]
 pub struct UserService {
     user_repository: UserRepository,
     auth_repository: AuthRepository,
+    app_tx: AppTx,
 }
 
 impl UserService {
     pub fn new(
         user_repository: UserRepository,
         auth_repository: AuthRepository,
+        app_tx: AppTx,
     ) -> Self {
         Self {
             user_repository,
             auth_repository,
+            app_tx,
+        }
+    }
+
+    /// Trims the field and clears it when blank.
+    fn profile_field(
+        value: Option<String>,
+        field: &'static str,
+        max: usize,
+    ) -> Result<Option<String>, UserError> {
+        let value = value
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty());
+        match value {
+            Some(v) if v.chars().count() > max => Err(UserError::FieldTooLong { field, max }),
+            v => Ok(v),
         }
     }
 
+    async fn find_profile(
+        &self,
+        user_id: i32,
+    ) -> Result<UserProfile, anyhow::Error> {
+        let profile = self
+            .user_repository
+            .find_profile(user_id)
+            .await
+            .map_err(|e| anyhow!(e))?
+            .ok_or(UserError::NotFound { user_id })?;
+        Ok(profile.into())
+    }
+
+    /// Pushes the current username and profile to the user's own sessions and to
+    /// their online contacts.
+    async fn notify_profile_updated(
+        &self,
+        user_id: i32,
+    ) -> Result<(), anyhow::Error> {
+        let user = self
+            .auth_repository
+            .find_user_by_id(user_id)
+            .await
+            .map_err(|e| anyhow!(e))?
+            .ok_or(UserError::NotFound { user_id })?;
+        let profile = self.find_profile(user_id).await?;
+        let _ = self
+            .app_tx
+            .send(AppMessage::NotifyContacts {
+                user_id,
+                message: WsResponse::ProfileUpdated {
+                    user_id,
+                    username: user.username,
+                    profile,
+                },
+            })
+            .await;
+        Ok(())
+    }
+
     pub async fn find_user_details(
         &self,
         user_id: i32,
@@ -36,6 +117,12 @@ impl UserService {
         Ok(UserDetail {
             user_id: res.id,
             username: res.username,
+            role: Role::from_str(&res.role).unwrap_or(Role::Guest),
+            password_reset_required: res.password_reset_required,
+            discoverable: res.discoverable,
+            presence_visibility: PresenceVisibility::from_str(&res.presence_visibility)
+                .unwrap_or(PresenceVisibility::Everyone),
+            profile: self.find_profile(user_id).await?,
         })
     }
 
@@ -65,6 +152,7 @@ impl UserService {
         if !success {
             return Err(anyhow!("Failed updating username"));
         }
+        self.notify_profile_updated(user_id).await?;
         Ok(SuccessfullyUpdateUser)
     }
 
@@ -83,4 +171,116 @@ impl UserService {
         }
         Ok(SuccessfullyUpdateUser)
     }
+
+    /// Matches usernames by prefix or trigram similarity, and emails exactly. Users who
+    /// opted out of discovery, are suspended or are blocked either way are left out.
+    pub async fn search_users(
+        &self,
+        user_id: i32,
+        UserSearchQuery { q, limit, offset }: UserSearchQuery,
+    ) -> Result<Vec<PublicUserModel>, anyhow::Error> {
+        let term = q.trim().to_lowercase();
+        if term.is_empty() {
+            bail!(UserError::EmptySearchQuery);
+        }
+        let prefix_pattern = format!(
+            "{}%",
+            term.replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        );
+        let limit = limit
+            .unwrap_or(DEFAULT_SEARCH_PAGE_SIZE)
+            .clamp(1, MAX_SEARCH_PAGE_SIZE);
+        let users = self
+            .user_repository
+            .search_users(
+                user_id,
+                term,
+                prefix_pattern,
+                limit,
+                offset.unwrap_or(0).max(0),
+            )
+            .await
+            .map_err(|e| anyhow!(e))?;
+        Ok(users.into_iter().map(PublicUserModel::from).collect())
+    }
+
+    pub async fn find_public_user(
+        &self,
+        viewer_id: i32,
+        user_id: i32,
+    ) -> Result<PublicUserModel, anyhow::Error> {
+        let user = self
+            .user_repository
+            .find_public_user(viewer_id, user_id)
+            .await
+            .map_err(|e| anyhow!(e))?
+            .ok_or(UserError::NotFound { user_id })?;
+        Ok(user.into())
+    }
+
+    pub async fn update_discoverable(
+        &self,
+        user_id: i32,
+        UpdateDiscoverableForm { discoverable }: UpdateDiscoverableForm,
+    ) -> Result<SuccessfullyUpdateUser, anyhow::Error> {
+        let success = self
+            .user_repository
+            .update_discoverable(user_id, discoverable)
+            .await
+            .map_err(|e| anyhow!(e))?;
+        if !success {
+            bail!(UserError::NotFound { user_id });
+        }
+        Ok(SuccessfullyUpdateUser)
+    }
+
+    pub async fn update_profile(
+        &self,
+        user_id: i32,
+        UpdateProfileForm {
+            display_name,
+            bio,
+            pronouns,
+            status_text,
+            status_emoji,
+            status_expires_in_mins,
+        }: UpdateProfileForm,
+    ) -> Result<UserProfile, anyhow::Error> {
+        let status_text = Self::profile_field(status_text, "Status text", MAX_STATUS_TEXT_LENGTH)?;
+        if status_expires_in_mins.is_some_and(|mins| mins <= 0) {
+            bail!(UserError::InvalidStatusExpiry);
+        }
+        // emoji and expiry only mean something together with a status text
+        let (status_emoji, status_expires_in_mins) = match status_text {
+            Some(_) => (
+                Self::profile_field(status_emoji, "Status emoji", MAX_STATUS_EMOJI_LENGTH)?,
+                status_expires_in_mins,
+            ),
+            None => (None, None),
+        };
+        let profile = UserProfileRepositoryModel {
+            display_name: Self::profile_field(
+                display_name,
+                "Display name",
+                MAX_DISPLAY_NAME_LENGTH,
+            )?,
+            bio: Self::profile_field(bio, "Bio", MAX_BIO_LENGTH)?,
+            pronouns: Self::profile_field(pronouns, "Pronouns", MAX_PRONOUNS_LENGTH)?,
+            status_text,
+            status_emoji,
+            status_expires_at: None,
+        };
+        let success = self
+            .user_repository
+            .update_profile(user_id, profile, status_expires_in_mins)
+            .await
+            .map_err(|e| anyhow!(e))?;
+        if !success {
+            bail!(UserError::NotFound { user_id });
+        }
+        self.notify_profile_updated(user_id).await?;
+        self.find_profile(user_id).await
+    }
 }
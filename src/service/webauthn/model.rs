@@ -0,0 +1,109 @@
+use chrono::NaiveDateTime;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+use webauthn_rs::prelude::PublicKeyCredential;
+use webauthn_rs::prelude::RegisterPublicKeyCredential;
+
+use crate::repository::WebauthnCredentialRepositoryModel;
+
+pub struct WebauthnConfig {
+    pub rp_id: String,
+    pub rp_origin: String,
+    pub rp_name: String,
+}
+
+impl WebauthnConfig {
+    /// Reads the relying party from `WEBAUTHN_RP_ID`, `WEBAUTHN_RP_ORIGIN` and
+    /// `WEBAUTHN_RP_NAME`. The origin must be the frontend the browser runs the
+    /// ceremony on, and the id its domain (or a parent domain).
+    pub fn from_env() -> Self {
+        Self {
+            rp_id: std::env::var("WEBAUTHN_RP_ID").unwrap_or_else(|_| "localhost".to_string()),
+            rp_origin: std::env::var("WEBAUTHN_RP_ORIGIN")
+                .unwrap_or_else(|_| "http://localhost:5173".to_string()),
+            rp_name: std::env::var("WEBAUTHN_RP_NAME").unwrap_or_else(|_| "Chatbyte".to_string()),
+        }
+    }
+}
+
+/// Challenge handed to `navigator.credentials.create()` or `.get()`. The `ceremony_id`
+/// has to be sent back with the authenticator response.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebauthnChallenge<T> {
+    pub ceremony_id: String,
+    pub options: T,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FinishPasskeyRegistrationForm {
+    pub ceremony_id: String,
+    pub name: String,
+    pub credential: RegisterPublicKeyCredential,
+}
+
+#[derive(Deserialize)]
+pub struct StartPasskeyLoginForm {
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FinishPasskeyLoginForm {
+    pub ceremony_id: String,
+    pub credential: PublicKeyCredential,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PasskeyModel {
+    pub id: i32,
+    pub name: String,
+    pub sign_count: i64,
+    pub created_at: NaiveDateTime,
+    pub last_used_at: Option<NaiveDateTime>,
+}
+
+impl From<WebauthnCredentialRepositoryModel> for PasskeyModel {
+    fn from(value: WebauthnCredentialRepositoryModel) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+            sign_count: value.sign_count,
+            created_at: value.created_at,
+            last_used_at: value.last_used_at,
+        }
+    }
+}
+
+pub struct DeletePasskeySuccess;
+
+impl Serialize for DeletePasskeySuccess {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str("Successfully removed passkey")
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum WebauthnError {
+    #[error("Passkey ceremony is unknown or has expired")]
+    UnknownCeremony,
+    #[error("Passkey name must not be empty")]
+    NameIsEmpty,
+    #[error("Passkey is already registered")]
+    AlreadyRegistered,
+    #[error("No passkey is registered for this account")]
+    NoPasskeys,
+    #[error("Passkey verification failed")]
+    VerificationFailed,
+    #[error("Passkey with id {passkey_id} not found")]
+    NotFound { passkey_id: i32 },
+}
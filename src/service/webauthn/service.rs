@@ -0,0 +1,309 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rand::RngCore;
+use webauthn_rs::prelude::CreationChallengeResponse;
+use webauthn_rs::prelude::Passkey;
+use webauthn_rs::prelude::PasskeyAuthentication;
+use webauthn_rs::prelude::PasskeyRegistration;
+use webauthn_rs::prelude::RequestChallengeResponse;
+use webauthn_rs::prelude::Url;
+use webauthn_rs::prelude::Uuid;
+use webauthn_rs::Webauthn;
+use webauthn_rs::WebauthnBuilder;
+
+use crate::repository::AuthRepository;
+use crate::repository::WebauthnRepository;
+use crate::service::AuthError;
+use crate::service::AuthService;
+use crate::service::AuthenticationToken;
+
+use super::DeletePasskeySuccess;
+use super::FinishPasskeyLoginForm;
+use super::FinishPasskeyRegistrationForm;
+use super::PasskeyModel;
+use super::StartPasskeyLoginForm;
+use super::WebauthnChallenge;
+use super::WebauthnConfig;
+use super::WebauthnError;
+
+const CEREMONY_MAX_AGE_SECS: f64 = 300.0;
+const REGISTRATION_CEREMONY: &str = "registration";
+const AUTHENTICATION_CEREMONY: &str = "authentication";
+
+#[derive(Clone)]
+pub struct WebauthnService {
+    webauthn: Arc<Webauthn>,
+    webauthn_repository: WebauthnRepository,
+    auth_repository: AuthRepository,
+    auth_service: AuthService,
+}
+
+impl WebauthnService {
+    pub fn new(
+        config: WebauthnConfig,
+        webauthn_repository: WebauthnRepository,
+        auth_repository: AuthRepository,
+        auth_service: AuthService,
+    ) -> Result<Self, anyhow::Error> {
+        let rp_origin = Url::parse(&config.rp_origin)?;
+        let webauthn = WebauthnBuilder::new(&config.rp_id, &rp_origin)?
+            .rp_name(&config.rp_name)
+            .build()?;
+        Ok(Self {
+            webauthn: Arc::new(webauthn),
+            webauthn_repository,
+            auth_repository,
+            auth_service,
+        })
+    }
+
+    fn random_token() -> String {
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        URL_SAFE_NO_PAD.encode(bytes)
+    }
+
+    /// The user handle stored on the authenticator. It only has to be stable per
+    /// account, so it is derived from the user id.
+    fn user_handle(user_id: i32) -> Uuid {
+        Uuid::from_u128(user_id as u128)
+    }
+
+    async fn find_passkeys(
+        &self,
+        user_id: i32,
+    ) -> Result<Vec<(i32, Passkey)>, anyhow::Error> {
+        let credentials = self
+            .webauthn_repository
+            .find_credentials_by_user_id(user_id)
+            .await
+            .map_err(|e| anyhow!(e))?;
+        credentials
+            .into_iter()
+            .map(|c| Ok((c.id, serde_json::from_str(&c.passkey)?)))
+            .collect()
+    }
+
+    async fn create_ceremony(
+        &self,
+        user_id: i32,
+        kind: &str,
+        state: String,
+    ) -> Result<String, anyhow::Error> {
+        if let Err(e) = self
+            .webauthn_repository
+            .delete_expired_ceremonies(CEREMONY_MAX_AGE_SECS)
+            .await
+        {
+            log::error!("{e}");
+        }
+        let ceremony_id = Self::random_token();
+        self.webauthn_repository
+            .create_ceremony(ceremony_id.clone(), user_id, kind, state)
+            .await
+            .map_err(|e| anyhow!(e))?;
+        Ok(ceremony_id)
+    }
+
+    async fn take_ceremony(
+        &self,
+        ceremony_id: String,
+        kind: &str,
+    ) -> Result<(i32, String), anyhow::Error> {
+        let ceremony = self
+            .webauthn_repository
+            .take_ceremony(ceremony_id, kind, CEREMONY_MAX_AGE_SECS)
+            .await
+            .map_err(|e| anyhow!(e))?
+            .ok_or(WebauthnError::UnknownCeremony)?;
+        Ok((ceremony.user_id, ceremony.state))
+    }
+
+    pub async fn start_registration(
+        &self,
+        user_id: i32,
+    ) -> Result<WebauthnChallenge<CreationChallengeResponse>, anyhow::Error> {
+        let user = self
+            .auth_repository
+            .find_user_by_id(user_id)
+            .await
+            .map_err(|e| anyhow!(e))?
+            .ok_or(anyhow!("User not found"))?;
+        let exclude_credentials = self
+            .find_passkeys(user_id)
+            .await?
+            .iter()
+            .map(|(_, passkey)| passkey.cred_id().clone())
+            .collect();
+        let (options, registration) = self.webauthn.start_passkey_registration(
+            Self::user_handle(user_id),
+            &user.email,
+            &user.username,
+            Some(exclude_credentials),
+        )?;
+        let ceremony_id = self
+            .create_ceremony(
+                user_id,
+                REGISTRATION_CEREMONY,
+                serde_json::to_string(&registration)?,
+            )
+            .await?;
+        Ok(WebauthnChallenge {
+            ceremony_id,
+            options,
+        })
+    }
+
+    pub async fn finish_registration(
+        &self,
+        user_id: i32,
+        FinishPasskeyRegistrationForm {
+            ceremony_id,
+            name,
+            credential,
+        }: FinishPasskeyRegistrationForm,
+    ) -> Result<PasskeyModel, anyhow::Error> {
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            bail!(WebauthnError::NameIsEmpty);
+        }
+        let (owner_id, state) = self
+            .take_ceremony(ceremony_id, REGISTRATION_CEREMONY)
+            .await?;
+        if owner_id != user_id {
+            bail!(WebauthnError::UnknownCeremony);
+        }
+        let registration: PasskeyRegistration = serde_json::from_str(&state)?;
+        let passkey = self
+            .webauthn
+            .finish_passkey_registration(&credential, &registration)
+            .map_err(|e| {
+                log::warn!("Passkey registration for user {user_id} failed: {e}");
+                WebauthnError::VerificationFailed
+            })?;
+        let credential_id = URL_SAFE_NO_PAD.encode(passkey.cred_id());
+        let existing = self
+            .webauthn_repository
+            .find_credential_by_credential_id(credential_id.clone())
+            .await
+            .map_err(|e| anyhow!(e))?;
+        if existing.is_some() {
+            bail!(WebauthnError::AlreadyRegistered);
+        }
+        let created = self
+            .webauthn_repository
+            .create_credential(
+                user_id,
+                name,
+                credential_id,
+                serde_json::to_string(&passkey)?,
+            )
+            .await
+            .map_err(|e| anyhow!(e))?;
+        Ok(created.into())
+    }
+
+    pub async fn start_login(
+        &self,
+        StartPasskeyLoginForm { email }: StartPasskeyLoginForm,
+    ) -> Result<WebauthnChallenge<RequestChallengeResponse>, anyhow::Error> {
+        let user = self
+            .auth_repository
+            .find_user_by_email(email.clone())
+            .await
+            .map_err(|e| anyhow!(e))?
+            .ok_or(AuthError::EmailNotFound { email })?;
+        let passkeys = self
+            .find_passkeys(user.id)
+            .await?
+            .into_iter()
+            .map(|(_, passkey)| passkey)
+            .collect::<Vec<_>>();
+        if passkeys.is_empty() {
+            bail!(WebauthnError::NoPasskeys);
+        }
+        let (options, authentication) = self.webauthn.start_passkey_authentication(&passkeys)?;
+        let ceremony_id = self
+            .create_ceremony(
+                user.id,
+                AUTHENTICATION_CEREMONY,
+                serde_json::to_string(&authentication)?,
+            )
+            .await?;
+        Ok(WebauthnChallenge {
+            ceremony_id,
+            options,
+        })
+    }
+
+    /// Verifies the assertion and issues a regular token. The stored sign counter is
+    /// advanced, and an assertion whose counter did not increase is rejected as a
+    /// possibly cloned authenticator.
+    pub async fn finish_login(
+        &self,
+        FinishPasskeyLoginForm {
+            ceremony_id,
+            credential,
+        }: FinishPasskeyLoginForm,
+    ) -> Result<AuthenticationToken, anyhow::Error> {
+        let (user_id, state) = self
+            .take_ceremony(ceremony_id, AUTHENTICATION_CEREMONY)
+            .await?;
+        let authentication: PasskeyAuthentication = serde_json::from_str(&state)?;
+        let result = self
+            .webauthn
+            .finish_passkey_authentication(&credential, &authentication)
+            .map_err(|e| {
+                log::warn!("Passkey login for user {user_id} failed: {e}");
+                WebauthnError::VerificationFailed
+            })?;
+        let (id, mut passkey) = self
+            .find_passkeys(user_id)
+            .await?
+            .into_iter()
+            .find(|(_, passkey)| passkey.cred_id() == result.cred_id())
+            .ok_or(WebauthnError::VerificationFailed)?;
+        passkey.update_credential(&result);
+        self.webauthn_repository
+            .update_credential_usage(
+                id,
+                serde_json::to_string(&passkey)?,
+                result.counter() as i64,
+            )
+            .await
+            .map_err(|e| anyhow!(e))?;
+        self.auth_service.issue_token(user_id).await
+    }
+
+    pub async fn find_passkeys_for_user(
+        &self,
+        user_id: i32,
+    ) -> Result<Vec<PasskeyModel>, anyhow::Error> {
+        let credentials = self
+            .webauthn_repository
+            .find_credentials_by_user_id(user_id)
+            .await
+            .map_err(|e| anyhow!(e))?;
+        Ok(credentials.into_iter().map(PasskeyModel::from).collect())
+    }
+
+    pub async fn delete_passkey(
+        &self,
+        user_id: i32,
+        passkey_id: i32,
+    ) -> Result<DeletePasskeySuccess, anyhow::Error> {
+        let res = self
+            .webauthn_repository
+            .delete_credential(passkey_id, user_id)
+            .await;
+        match res {
+            Ok(succ) if succ => Ok(DeletePasskeySuccess),
+            Ok(_) => bail!(WebauthnError::NotFound { passkey_id }),
+            Err(e) => bail!(e),
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use serde::Serialize;
+use thiserror::Error;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WsTicket {
+    pub ticket: String,
+    pub expires_in_secs: u64,
+}
+
+pub struct RedeemedWsTicket {
+    pub user_id: i32,
+    /// The `exp` claim of the token the ticket was issued for.
+    pub token_expires_at: u64,
+}
+
+#[derive(Error, Debug)]
+pub enum WsTicketError {
+    #[error("Websocket tickets can only be issued for session tokens")]
+    SessionTokenRequired,
+    #[error("Websocket ticket is unknown, used or expired")]
+    UnknownTicket,
+}
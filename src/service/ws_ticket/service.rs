@@ -0,0 +1,106 @@
+use anyhow::anyhow;
+use anyhow::bail;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::NaiveDateTime;
+use rand::RngCore;
+use sha2::Digest;
+use sha2::Sha256;
+
+use crate::repository::WsTicketRepository;
+use crate::service::AuthService;
+use crate::service::JwtService;
+
+use super::RedeemedWsTicket;
+use super::WsTicket;
+use super::WsTicketError;
+
+/// How long a ticket can be used to open a websocket.
+const WS_TICKET_TTL_SECS: u64 = 30;
+
+#[derive(Clone)]
+pub struct WsTicketService {
+    ws_ticket_repository: WsTicketRepository,
+    jwt_service: JwtService,
+    auth_service: AuthService,
+}
+
+impl WsTicketService {
+    pub fn new(
+        ws_ticket_repository: WsTicketRepository,
+        jwt_service: JwtService,
+        auth_service: AuthService,
+    ) -> Self {
+        Self {
+            ws_ticket_repository,
+            jwt_service,
+            auth_service,
+        }
+    }
+
+    fn hash_ticket(ticket: &str) -> String {
+        format!("{:x}", Sha256::digest(ticket.as_bytes()))
+    }
+
+    fn generate_ticket() -> String {
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        URL_SAFE_NO_PAD.encode(bytes)
+    }
+
+    /// Issues a single-use ticket for the session token `token` of `user_id`. The
+    /// websocket opened with it expires together with the token.
+    pub async fn issue_ticket(
+        &self,
+        user_id: i32,
+        token: &str,
+    ) -> Result<WsTicket, anyhow::Error> {
+        let claims = self
+            .jwt_service
+            .verify(token)
+            .map_err(|_| WsTicketError::SessionTokenRequired)?;
+        if claims.user_id().ok() != Some(user_id) {
+            bail!(WsTicketError::SessionTokenRequired);
+        }
+        let token_expires_at = NaiveDateTime::from_timestamp_opt(claims.exp as i64, 0)
+            .ok_or(WsTicketError::SessionTokenRequired)?;
+        if let Err(e) = self.ws_ticket_repository.delete_expired_tickets().await {
+            log::error!("{e}");
+        }
+        let ticket = Self::generate_ticket();
+        self.ws_ticket_repository
+            .create_ticket(
+                Self::hash_ticket(&ticket),
+                user_id,
+                token_expires_at,
+                WS_TICKET_TTL_SECS as f64,
+            )
+            .await
+            .map_err(|e| anyhow!(e))?;
+        Ok(WsTicket {
+            ticket,
+            expires_in_secs: WS_TICKET_TTL_SECS,
+        })
+    }
+
+    /// Consumes a ticket; it cannot be redeemed again. The account is checked again,
+    /// since it may have been suspended after the ticket was issued.
+    pub async fn redeem_ticket(
+        &self,
+        ticket: &str,
+    ) -> Result<RedeemedWsTicket, anyhow::Error> {
+        let res = self
+            .ws_ticket_repository
+            .take_ticket(Self::hash_ticket(ticket))
+            .await
+            .map_err(|e| anyhow!(e))?;
+        let Some(ticket) = res else {
+            bail!(WsTicketError::UnknownTicket);
+        };
+        self.auth_service.ensure_active(ticket.user_id).await?;
+        Ok(RedeemedWsTicket {
+            user_id: ticket.user_id,
+            token_expires_at: ticket.token_expires_at.timestamp().max(0) as u64,
+        })
+    }
+}
@@ -0,0 +1,108 @@
+mod model;
+mod postgres;
+mod redis;
+
+use std::collections::HashSet;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use rand::Rng;
+use sqlx::Pool;
+use sqlx::Postgres;
+
+pub use self::redis::*;
+pub use model::*;
+pub use postgres::*;
+
+use crate::repository::BusRepository;
+use crate::websocket::message::AppTx;
+
+/// How often a node announces itself to the others; a node that stays silent for
+/// three intervals is treated as gone, together with its sessions.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+static NODE_ID: OnceLock<u32> = OnceLock::new();
+
+/// Identifies this process among the nodes sharing a bus. Kept below 2^20 so that
+/// session ids built from it stay exact in JavaScript numbers. Nodes of a shared
+/// bus take theirs from `ws_node_id_seq`; a lone node picks one at random.
+pub fn node_id() -> u32 {
+    *NODE_ID.get_or_init(|| rand::thread_rng().gen_range(1..1 << 20))
+}
+
+/// Takes the next node id from the database, so that no two running nodes share it.
+async fn claim_node_id(bus_repository: &BusRepository) {
+    let node_id = bus_repository
+        .next_node_id()
+        .await
+        .unwrap_or_else(|e| panic!("Failed claiming a websocket node id: {e}"));
+    NODE_ID
+        .set(node_id as u32)
+        .expect("Node id is claimed before it is used");
+}
+
+/// Carries websocket work to the other nodes of a deployment, so each of them can
+/// reach the sessions it holds.
+#[derive(Clone)]
+pub enum FanoutBus {
+    /// Single node deployments; nothing leaves the process.
+    Local,
+    Postgres(PostgresBus),
+    Redis(RedisBus),
+}
+
+impl FanoutBus {
+    pub async fn from_env(conn: Pool<Postgres>) -> Self {
+        let bus = std::env::var("WS_FANOUT_BUS").unwrap_or_else(|_| "local".to_string());
+        let bus_repository = BusRepository::new(conn);
+        if bus != "local" {
+            claim_node_id(&bus_repository).await;
+        }
+        match bus.as_str() {
+            "local" => FanoutBus::Local,
+            "postgres" => FanoutBus::Postgres(PostgresBus::new(bus_repository)),
+            "redis" => {
+                let url = std::env::var("REDIS_URL")
+                    .unwrap_or_else(|_| "redis://127.0.0.1:6379".to_string());
+                let client = ::redis::Client::open(url).expect("REDIS_URL is invalid");
+                FanoutBus::Redis(RedisBus::new(client))
+            }
+            _ => panic!("WS_FANOUT_BUS '{bus}' is not supported"),
+        }
+    }
+
+    /// Starts forwarding the events of the other nodes into the websocket server.
+    pub fn start(
+        &self,
+        app_tx: AppTx,
+    ) {
+        match self {
+            FanoutBus::Local => {}
+            FanoutBus::Postgres(bus) => bus.start(app_tx),
+            FanoutBus::Redis(bus) => bus.start(app_tx),
+        }
+    }
+
+    pub fn publish(
+        &self,
+        event: BusEvent,
+    ) {
+        match self {
+            FanoutBus::Local => {}
+            FanoutBus::Postgres(bus) => bus.publish(event),
+            FanoutBus::Redis(bus) => bus.publish(event),
+        }
+    }
+
+    /// Which of the given nodes are still alive, when the bus keeps track of it;
+    /// otherwise nodes expire when their heartbeats stop.
+    pub async fn live_nodes(
+        &self,
+        node_ids: Vec<u32>,
+    ) -> Option<HashSet<u32>> {
+        match self {
+            FanoutBus::Local | FanoutBus::Postgres(_) => None,
+            FanoutBus::Redis(bus) => bus.live_nodes(node_ids).await,
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use chrono::NaiveDateTime;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::service::PresenceState;
+use crate::websocket::WsResponse;
+
+/// A session held by another node.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RemoteSession {
+    pub session_id: u64,
+    pub user_agent: Option<String>,
+    pub connected_at: NaiveDateTime,
+    pub idle: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BusEnvelope {
+    pub node_id: u32,
+    pub event: BusEvent,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type")]
+pub enum BusEvent {
+    Notify {
+        user_ids: Vec<i32>,
+        message: WsResponse,
+    },
+    NotifyContacts {
+        user_id: i32,
+        message: WsResponse,
+    },
+    DisconnectUser {
+        user_id: i32,
+    },
+    PresenceChanged {
+        user_id: i32,
+    },
+    BlockChanged {
+        user_id: i32,
+        other_id: i32,
+    },
+    /// Every session the sending node holds for a user, sent whenever they change.
+    /// `last_seen_at` is set when the user went offline on all nodes.
+    Sessions {
+        user_id: i32,
+        sessions: Vec<RemoteSession>,
+        chosen_state: PresenceState,
+        last_seen_at: Option<NaiveDateTime>,
+    },
+    /// A node joined, or lost events, and asks the others to resend their sessions.
+    Hello,
+    Heartbeat,
+}
@@ -0,0 +1,168 @@
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+
+use super::node_id;
+use super::BusEnvelope;
+use super::BusEvent;
+use super::HEARTBEAT_INTERVAL;
+use crate::repository::BusRepository;
+use crate::websocket::message::AppMessage;
+use crate::websocket::message::AppTx;
+
+const CHANNEL: &str = "chatbyte_ws";
+/// NOTIFY payloads must stay below 8000 bytes; larger events are stored in
+/// `ws_bus_payload` and only their id is sent.
+const MAX_NOTIFY_PAYLOAD: usize = 7000;
+const PAYLOAD_REF_PREFIX: &str = "ref:";
+
+/// Fans events out through PostgreSQL `LISTEN/NOTIFY`.
+#[derive(Clone)]
+pub struct PostgresBus {
+    bus_repository: BusRepository,
+    publish_tx: mpsc::UnboundedSender<BusEvent>,
+}
+
+impl PostgresBus {
+    pub fn new(bus_repository: BusRepository) -> Self {
+        let (publish_tx, publish_rx) = mpsc::unbounded_channel::<BusEvent>();
+        // a single publisher keeps the events of this node in order
+        tokio::spawn(Self::run_publisher(bus_repository.clone(), publish_rx));
+        Self {
+            bus_repository,
+            publish_tx,
+        }
+    }
+
+    pub fn publish(
+        &self,
+        event: BusEvent,
+    ) {
+        let _ = self.publish_tx.send(event);
+    }
+
+    async fn run_publisher(
+        bus_repository: BusRepository,
+        mut publish_rx: mpsc::UnboundedReceiver<BusEvent>,
+    ) {
+        while let Some(event) = publish_rx.recv().await {
+            let envelope = BusEnvelope {
+                node_id: node_id(),
+                event,
+            };
+            let mut payload = serde_json::to_string(&envelope).unwrap();
+            if payload.len() > MAX_NOTIFY_PAYLOAD {
+                payload = match bus_repository.store_payload(payload).await {
+                    Ok(id) => format!("{PAYLOAD_REF_PREFIX}{id}"),
+                    Err(e) => {
+                        log::error!("{e}");
+                        continue;
+                    }
+                };
+            }
+            if let Err(e) = bus_repository.notify(CHANNEL, payload).await {
+                log::error!("{e}");
+            }
+        }
+    }
+
+    pub fn start(
+        &self,
+        app_tx: AppTx,
+    ) {
+        tokio::spawn(Self::run_listener(self.clone(), app_tx.clone()));
+        tokio::spawn(Self::run_heartbeat(self.clone(), app_tx));
+    }
+
+    async fn run_listener(
+        self,
+        app_tx: AppTx,
+    ) {
+        let mut reconnecting = false;
+        loop {
+            let mut listener = match self.bus_repository.listen(CHANNEL).await {
+                Ok(listener) => listener,
+                Err(e) => {
+                    log::error!("{e}");
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            };
+            // whatever was sent while not listening is lost
+            self.publish(BusEvent::Hello);
+            if reconnecting && app_tx.send(AppMessage::BusReconnected).await.is_err() {
+                return;
+            }
+            reconnecting = true;
+            loop {
+                let notification = match listener.recv().await {
+                    Ok(notification) => notification,
+                    Err(e) => {
+                        log::error!("{e}");
+                        break;
+                    }
+                };
+                let Some(envelope) = self.read_envelope(notification.payload()).await else {
+                    continue;
+                };
+                if envelope.node_id == node_id() {
+                    continue;
+                }
+                let message = AppMessage::Bus {
+                    node_id: envelope.node_id,
+                    event: envelope.event,
+                };
+                if app_tx.send(message).await.is_err() {
+                    return;
+                }
+            }
+            log::error!("postgres listener lost");
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    async fn read_envelope(
+        &self,
+        payload: &str,
+    ) -> Option<BusEnvelope> {
+        let payload = match payload.strip_prefix(PAYLOAD_REF_PREFIX) {
+            Some(id) => {
+                let id = id.parse::<i64>().ok()?;
+                match self.bus_repository.find_payload(id).await {
+                    Ok(payload) => payload?,
+                    Err(e) => {
+                        log::error!("{e}");
+                        return None;
+                    }
+                }
+            }
+            None => payload.to_string(),
+        };
+        match serde_json::from_str::<BusEnvelope>(&payload) {
+            Ok(envelope) => Some(envelope),
+            Err(e) => {
+                log::error!("parsing error: {e}");
+                None
+            }
+        }
+    }
+
+    async fn run_heartbeat(
+        self,
+        app_tx: AppTx,
+    ) {
+        let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+        loop {
+            interval.tick().await;
+            self.publish(BusEvent::Heartbeat);
+            // a busy server skips a check rather than holding up the heartbeat
+            if let Err(TrySendError::Closed(_)) = app_tx.try_send(AppMessage::ExpireNodes) {
+                break;
+            }
+            if let Err(e) = self.bus_repository.delete_stale_payloads().await {
+                log::error!("{e}");
+            }
+        }
+    }
+}
@@ -0,0 +1,325 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use redis::aio::MultiplexedConnection;
+use redis::AsyncCommands;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+
+use super::node_id;
+use super::BusEnvelope;
+use super::BusEvent;
+use super::HEARTBEAT_INTERVAL;
+use crate::websocket::message::AppMessage;
+use crate::websocket::message::AppTx;
+
+/// Events that are not about a single user go to every node.
+const ALL_CHANNEL: &str = "chatbyte:ws:all";
+
+fn user_channel(user_id: i32) -> String {
+    format!("chatbyte:ws:user:{user_id}")
+}
+
+/// Holds the users with sessions on a node, and expires with the node.
+fn node_key(node_id: u32) -> String {
+    format!("chatbyte:ws:node:{node_id}")
+}
+
+enum Subscription {
+    Subscribe(i32),
+    Unsubscribe(i32),
+}
+
+/// Fans events out through Redis pub/sub. Each node subscribes to the channels of
+/// the users it holds sessions for, and keeps its set of online users under a key
+/// that expires unless the node keeps refreshing it.
+#[derive(Clone)]
+pub struct RedisBus {
+    client: redis::Client,
+    publish_tx: mpsc::UnboundedSender<BusEvent>,
+    subscription_rx: Arc<Mutex<Option<mpsc::UnboundedReceiver<Subscription>>>>,
+    /// Kept for the liveness checks, and opened again after an error.
+    conn: Arc<tokio::sync::Mutex<Option<MultiplexedConnection>>>,
+}
+
+impl RedisBus {
+    pub fn new(client: redis::Client) -> Self {
+        let (publish_tx, publish_rx) = mpsc::unbounded_channel::<BusEvent>();
+        let (subscription_tx, subscription_rx) = mpsc::unbounded_channel::<Subscription>();
+        // a single publisher keeps the events of this node in order
+        tokio::spawn(Self::run_publisher(
+            client.clone(),
+            publish_rx,
+            subscription_tx,
+        ));
+        Self {
+            client,
+            publish_tx,
+            subscription_rx: Arc::new(Mutex::new(Some(subscription_rx))),
+            conn: Arc::new(tokio::sync::Mutex::new(None)),
+        }
+    }
+
+    pub fn publish(
+        &self,
+        event: BusEvent,
+    ) {
+        let _ = self.publish_tx.send(event);
+    }
+
+    /// Splits an event into the channels it is published on.
+    fn route(event: BusEvent) -> Vec<(String, BusEvent)> {
+        match event {
+            BusEvent::Notify { user_ids, message } => user_ids
+                .into_iter()
+                .map(|user_id| {
+                    let event = BusEvent::Notify {
+                        user_ids: vec![user_id],
+                        message: message.clone(),
+                    };
+                    (user_channel(user_id), event)
+                })
+                .collect(),
+            BusEvent::DisconnectUser { user_id } => {
+                vec![(user_channel(user_id), BusEvent::DisconnectUser { user_id })]
+            }
+            event => vec![(ALL_CHANNEL.to_string(), event)],
+        }
+    }
+
+    async fn run_publisher(
+        client: redis::Client,
+        mut publish_rx: mpsc::UnboundedReceiver<BusEvent>,
+        subscription_tx: mpsc::UnboundedSender<Subscription>,
+    ) {
+        let mut local_users = HashSet::<i32>::new();
+        let mut conn = None;
+        while let Some(event) = publish_rx.recv().await {
+            let mut refresh_node = matches!(event, BusEvent::Heartbeat);
+            if let BusEvent::Sessions {
+                user_id, sessions, ..
+            } = &event
+            {
+                let changed = if sessions.is_empty() {
+                    local_users.remove(user_id)
+                } else {
+                    local_users.insert(*user_id)
+                };
+                if changed {
+                    let subscription = if sessions.is_empty() {
+                        Subscription::Unsubscribe(*user_id)
+                    } else {
+                        Subscription::Subscribe(*user_id)
+                    };
+                    let _ = subscription_tx.send(subscription);
+                    refresh_node = true;
+                }
+            }
+
+            if conn.is_none() {
+                conn = match client.get_multiplexed_async_connection().await {
+                    Ok(c) => Some(c),
+                    Err(e) => {
+                        log::error!("{e}");
+                        continue;
+                    }
+                };
+            }
+            let Some(c) = conn.as_mut() else {
+                continue;
+            };
+            if refresh_node {
+                let users = serde_json::to_string(&local_users).unwrap();
+                let ttl = (HEARTBEAT_INTERVAL * 3).as_secs();
+                let res: Result<(), _> = redis::cmd("SET")
+                    .arg(node_key(node_id()))
+                    .arg(users)
+                    .arg("EX")
+                    .arg(ttl)
+                    .query_async(c)
+                    .await;
+                if let Err(e) = res {
+                    log::error!("{e}");
+                }
+            }
+            for (channel, event) in Self::route(event) {
+                let envelope = BusEnvelope {
+                    node_id: node_id(),
+                    event,
+                };
+                let payload = serde_json::to_string(&envelope).unwrap();
+                let res: Result<i64, _> = c.publish(channel, payload).await;
+                if let Err(e) = res {
+                    log::error!("{e}");
+                    conn = None;
+                    break;
+                }
+            }
+        }
+    }
+
+    pub fn start(
+        &self,
+        app_tx: AppTx,
+    ) {
+        let Some(subscription_rx) = self.subscription_rx.lock().unwrap().take() else {
+            return;
+        };
+        tokio::spawn(Self::run_subscriber(
+            self.clone(),
+            app_tx.clone(),
+            subscription_rx,
+        ));
+        tokio::spawn(Self::run_heartbeat(self.clone(), app_tx));
+    }
+
+    async fn run_subscriber(
+        self,
+        app_tx: AppTx,
+        mut subscription_rx: mpsc::UnboundedReceiver<Subscription>,
+    ) {
+        let mut local_users = HashSet::<i32>::new();
+        let mut reconnecting = false;
+        loop {
+            let pubsub = match self.client.get_async_pubsub().await {
+                Ok(pubsub) => pubsub,
+                Err(e) => {
+                    log::error!("{e}");
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            };
+            let (mut sink, mut stream) = pubsub.split();
+            let mut channels = vec![ALL_CHANNEL.to_string()];
+            channels.extend(local_users.iter().map(|user_id| user_channel(*user_id)));
+            if let Err(e) = sink.subscribe(channels).await {
+                log::error!("{e}");
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+            // whatever was sent while not subscribed is lost
+            self.publish(BusEvent::Hello);
+            if reconnecting && app_tx.send(AppMessage::BusReconnected).await.is_err() {
+                return;
+            }
+            reconnecting = true;
+            loop {
+                tokio::select! {
+                    msg = stream.next() => {
+                        let Some(msg) = msg else {
+                            break;
+                        };
+                        let Some(envelope) = Self::read_envelope(msg) else {
+                            continue;
+                        };
+                        if envelope.node_id == node_id() {
+                            continue;
+                        }
+                        let message = AppMessage::Bus {
+                            node_id: envelope.node_id,
+                            event: envelope.event,
+                        };
+                        if app_tx.send(message).await.is_err() {
+                            return;
+                        }
+                    }
+                    subscription = subscription_rx.recv() => {
+                        let res = match subscription {
+                            Some(Subscription::Subscribe(user_id)) => {
+                                local_users.insert(user_id);
+                                sink.subscribe(user_channel(user_id)).await
+                            }
+                            Some(Subscription::Unsubscribe(user_id)) => {
+                                local_users.remove(&user_id);
+                                sink.unsubscribe(user_channel(user_id)).await
+                            }
+                            None => return,
+                        };
+                        if let Err(e) = res {
+                            log::error!("{e}");
+                        }
+                    }
+                }
+            }
+            log::error!("redis subscription lost");
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    fn read_envelope(msg: redis::Msg) -> Option<BusEnvelope> {
+        let payload = match msg.get_payload::<String>() {
+            Ok(payload) => payload,
+            Err(e) => {
+                log::error!("{e}");
+                return None;
+            }
+        };
+        match serde_json::from_str::<BusEnvelope>(&payload) {
+            Ok(envelope) => Some(envelope),
+            Err(e) => {
+                log::error!("parsing error: {e}");
+                None
+            }
+        }
+    }
+
+    async fn run_heartbeat(
+        self,
+        app_tx: AppTx,
+    ) {
+        let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+        loop {
+            interval.tick().await;
+            // also refreshes the key of this node
+            self.publish(BusEvent::Heartbeat);
+            // a busy server skips a check rather than holding up the heartbeat
+            if let Err(TrySendError::Closed(_)) = app_tx.try_send(AppMessage::ExpireNodes) {
+                break;
+            }
+        }
+    }
+
+    /// The given nodes whose key has not expired.
+    pub async fn live_nodes(
+        &self,
+        node_ids: Vec<u32>,
+    ) -> Option<HashSet<u32>> {
+        let mut conn = {
+            let mut conn = self.conn.lock().await;
+            if conn.is_none() {
+                *conn = match self.client.get_multiplexed_async_connection().await {
+                    Ok(c) => Some(c),
+                    Err(e) => {
+                        log::error!("{e}");
+                        return None;
+                    }
+                };
+            }
+            conn.clone()?
+        };
+        let keys = node_ids
+            .iter()
+            .map(|node_id| node_key(*node_id))
+            .collect::<Vec<_>>();
+        let res: Result<Vec<Option<String>>, _> =
+            redis::cmd("MGET").arg(keys).query_async(&mut conn).await;
+        match res {
+            Ok(values) => Some(
+                node_ids
+                    .into_iter()
+                    .zip(values)
+                    .filter(|(_, value)| value.is_some())
+                    .map(|(node_id, _)| node_id)
+                    .collect(),
+            ),
+            Err(e) => {
+                log::error!("{e}");
+                *self.conn.lock().await = None;
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use axum::extract::ws::Message;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::LegacyUsersOnline;
+use super::WsResponse;
+
+/// Subprotocol clients offer alongside `bearer.<token>`, so the server has one to
+/// select and never echoes the token back.
+pub const JSON_PROTOCOL: &str = "chatbyte";
+/// Subprotocol a client offers to receive MessagePack instead of JSON.
+pub const MSGPACK_PROTOCOL: &str = "chatbyte.msgpack";
+
+/// How responses are framed on a websocket: JSON in text frames by default, or
+/// MessagePack in binary frames for clients that negotiated `chatbyte.msgpack`.
+/// Both are driven by the serde types of `WsRequest` and `WsResponse`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WsEncoding {
+    Json,
+    MessagePack,
+}
+
+impl WsEncoding {
+    /// The encoding of the subprotocol the server selected.
+    pub fn from_protocol(protocol: Option<&str>) -> Self {
+        match protocol {
+            Some(MSGPACK_PROTOCOL) => WsEncoding::MessagePack,
+            _ => WsEncoding::Json,
+        }
+    }
+
+    pub fn encode<T: Serialize>(
+        self,
+        value: &T,
+    ) -> Result<Message, String> {
+        match self {
+            WsEncoding::Json => serde_json::to_string(value)
+                .map(Message::Text)
+                .map_err(|e| e.to_string()),
+            // named, so maps carry the `type` tag and field names like JSON objects
+            WsEncoding::MessagePack => rmp_serde::to_vec_named(value)
+                .map(Message::Binary)
+                .map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Encodes a response for a session, in the legacy shape of `USERS_ONLINE` when
+    /// the session did not negotiate a newer protocol.
+    pub fn encode_response(
+        self,
+        response: &WsResponse,
+        legacy: bool,
+    ) -> Result<Message, String> {
+        match response {
+            WsResponse::UsersOnline { users } if legacy => {
+                self.encode(&LegacyUsersOnline::from(users.as_slice()))
+            }
+            response => self.encode(response),
+        }
+    }
+
+    /// Decodes a data frame in the encoding it was sent in: text frames as JSON and
+    /// binary frames as MessagePack, whatever the session negotiated.
+    pub fn decode<T: DeserializeOwned>(frame: &Message) -> Result<T, String> {
+        match frame {
+            Message::Text(text) => serde_json::from_str(text).map_err(|e| e.to_string()),
+            Message::Binary(bytes) => rmp_serde::from_slice(bytes).map_err(|e| e.to_string()),
+            _ => Err("Expected a text or binary frame".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::extract::ws::Message;
+    use chrono::NaiveDateTime;
+
+    use super::WsEncoding;
+    use crate::service::PresenceState;
+    use crate::websocket::AttachmentContent;
+    use crate::websocket::MessageAttachment;
+    use crate::websocket::UserOnlineStatus;
+    use crate::websocket::WsRequest;
+    use crate::websocket::WsResponse;
+
+    const ENCODINGS: [WsEncoding; 2] = [WsEncoding::Json, WsEncoding::MessagePack];
+
+    fn send_message(content: AttachmentContent) -> WsRequest {
+        WsRequest::SendMessage {
+            receiver_uid: 2,
+            message: "hello".to_string(),
+            attachments: vec![MessageAttachment {
+                name: "a.bin".to_string(),
+                content_base64: content,
+            }],
+        }
+    }
+
+    /// The attachment of a decoded `SEND_MESSAGE`.
+    fn attachment(request: WsRequest) -> MessageAttachment {
+        match request {
+            WsRequest::SendMessage {
+                receiver_uid,
+                message,
+                mut attachments,
+            } => {
+                assert_eq!(receiver_uid, 2);
+                assert_eq!(message, "hello");
+                assert_eq!(attachments.len(), 1);
+                attachments.remove(0)
+            }
+            _ => panic!("expected SEND_MESSAGE"),
+        }
+    }
+
+    #[test]
+    fn encodings_use_their_frame_types() {
+        let json = WsEncoding::Json.encode(&WsResponse::Pong).unwrap();
+        assert!(matches!(json, Message::Text(text) if text == r#"{"type":"PONG"}"#));
+        let msgpack = WsEncoding::MessagePack.encode(&WsResponse::Pong).unwrap();
+        assert!(matches!(msgpack, Message::Binary(_)));
+        assert!(WsEncoding::decode::<WsRequest>(&Message::Ping(vec![])).is_err());
+    }
+
+    #[test]
+    fn request_round_trips() {
+        for encoding in ENCODINGS {
+            let frame = encoding
+                .encode(&send_message(AttachmentContent::Base64("AAEC".to_string())))
+                .unwrap();
+            let attachment = attachment(WsEncoding::decode(&frame).unwrap());
+            assert_eq!(attachment.name, "a.bin");
+            assert!(
+                matches!(&attachment.content_base64, AttachmentContent::Base64(c) if c == "AAEC")
+            );
+            assert_eq!(attachment.content_as_bytes().unwrap(), vec![0, 1, 2]);
+        }
+    }
+
+    #[test]
+    fn message_pack_request_carries_raw_attachment() {
+        let bytes = vec![0, 159, 146, 150, 255];
+        let frame = WsEncoding::MessagePack
+            .encode(&send_message(AttachmentContent::Raw(bytes.clone())))
+            .unwrap();
+        let attachment = attachment(WsEncoding::decode(&frame).unwrap());
+        assert!(matches!(&attachment.content_base64, AttachmentContent::Raw(raw) if *raw == bytes));
+        assert_eq!(attachment.content_as_bytes().unwrap(), bytes);
+    }
+
+    #[test]
+    fn attachment_content_accepts_base64_and_raw() {
+        let json = r#"{"name":"a.bin","contentBase64":"AAEC"}"#;
+        let attachment = serde_json::from_str::<MessageAttachment>(json).unwrap();
+        assert!(matches!(
+            attachment.content_base64,
+            AttachmentContent::Base64(_)
+        ));
+        assert_eq!(attachment.content_as_bytes().unwrap(), vec![0, 1, 2]);
+
+        let raw = rmp_serde::to_vec_named(&MessageAttachment {
+            name: "a.bin".to_string(),
+            content_base64: AttachmentContent::Raw(vec![0, 1, 2]),
+        })
+        .unwrap();
+        let attachment = rmp_serde::from_slice::<MessageAttachment>(&raw).unwrap();
+        assert!(matches!(
+            attachment.content_base64,
+            AttachmentContent::Raw(_)
+        ));
+        assert_eq!(attachment.content_as_bytes().unwrap(), vec![0, 1, 2]);
+
+        let invalid = r#"{"name":"a.bin","contentBase64":"not base64!"}"#;
+        let attachment = serde_json::from_str::<MessageAttachment>(invalid).unwrap();
+        assert!(attachment.content_as_bytes().is_err());
+    }
+
+    #[test]
+    fn legacy_sessions_get_the_legacy_wire_format() {
+        let status = |user_id, state| UserOnlineStatus {
+            user_id,
+            state,
+            last_seen_at: None,
+        };
+        let users_online = WsResponse::UsersOnline {
+            users: vec![
+                status(2, PresenceState::Away),
+                status(3, PresenceState::Offline),
+                status(4, PresenceState::Invisible),
+            ],
+        };
+        let legacy = WsEncoding::Json
+            .encode_response(&users_online, true)
+            .unwrap();
+        assert!(matches!(legacy, Message::Text(text) if text == concat!(
+            r#"{"type":"USERS_ONLINE","users":["#,
+            r#"{"userId":2,"online":true},{"userId":3,"online":false},{"userId":4,"online":false}]}"#
+        )));
+        let current = WsEncoding::Json
+            .encode_response(&users_online, false)
+            .unwrap();
+        assert!(matches!(current, Message::Text(text) if text.contains(r#""state":"away""#)));
+
+        let deleted = WsResponse::DeleteMessageNotification {
+            contact_id: 1,
+            message_id: 2,
+        };
+        let legacy = WsEncoding::Json.encode_response(&deleted, true).unwrap();
+        assert!(matches!(legacy, Message::Text(text) if text
+            == r#"{"type":"DELETE_DIRECT_MESSAGE_NOTIFICATION","contactId":1,"messageId":2}"#));
+
+        assert!(users_online.is_legacy() && deleted.is_legacy());
+        assert!(!WsResponse::Resync.is_legacy());
+        assert!(!WsResponse::SessionsChanged { sessions: vec![] }.is_legacy());
+    }
+
+    #[test]
+    fn response_round_trips() {
+        let sent_at = NaiveDateTime::from_timestamp_micros(1_700_000_000_123_456).unwrap();
+        let responses = [
+            WsResponse::MessageNotification {
+                id: 1,
+                sender_uid: 1,
+                receiver_uid: 2,
+                content: "hello".to_string(),
+                is_user: false,
+                sent_at,
+                receiver_read: false,
+                attachments: vec![],
+            },
+            WsResponse::DeleteGroupMessageNotification {
+                group_id: 3,
+                message_id: 4,
+            },
+            WsResponse::TokenExpiring {
+                expires_at: sent_at,
+            },
+            WsResponse::Resync,
+            WsResponse::Pong,
+        ];
+        for encoding in ENCODINGS {
+            for response in responses.iter() {
+                let frame = encoding.encode(response).unwrap();
+                let decoded = WsEncoding::decode::<WsResponse>(&frame).unwrap();
+                assert_eq!(
+                    serde_json::to_value(&decoded).unwrap(),
+                    serde_json::to_value(response).unwrap()
+                );
+            }
+        }
+    }
+}
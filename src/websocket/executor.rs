@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::hash::Hash;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use futures::FutureExt;
+
+type Job = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Runs jobs concurrently, except that jobs sharing a key run one after another in
+/// the order they were spawned.
+#[derive(Clone)]
+pub(crate) struct KeyedExecutor<K> {
+    queues: Arc<Mutex<HashMap<K, VecDeque<Job>>>>,
+}
+
+impl<K> KeyedExecutor<K>
+where
+    K: Eq + Hash + Clone + Send + 'static,
+{
+    pub(crate) fn new() -> Self {
+        Self {
+            queues: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub(crate) fn spawn(
+        &self,
+        key: K,
+        job: impl Future<Output = ()> + Send + 'static,
+    ) {
+        let mut queues = self.queues.lock().unwrap();
+        if let Some(queue) = queues.get_mut(&key) {
+            queue.push_back(Box::pin(job));
+            return;
+        }
+        queues.insert(key.clone(), VecDeque::new());
+        drop(queues);
+
+        let queues = self.queues.clone();
+        tokio::spawn(async move {
+            let mut job: Job = Box::pin(job);
+            loop {
+                // a panicking job must not leave its key taken, nor drop the jobs
+                // queued behind it
+                if AssertUnwindSafe(job).catch_unwind().await.is_err() {
+                    log::error!("A websocket job panicked");
+                }
+                let next = {
+                    let mut queues = queues.lock().unwrap();
+                    let next = queues.get_mut(&key).and_then(|queue| queue.pop_front());
+                    if next.is_none() {
+                        queues.remove(&key);
+                    }
+                    next
+                };
+                match next {
+                    Some(next) => job = next,
+                    None => break,
+                }
+            }
+        });
+    }
+
+    /// Keys with a job running.
+    pub(crate) fn active(&self) -> usize {
+        self.queues.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::oneshot;
+
+    use super::KeyedExecutor;
+
+    #[tokio::test]
+    async fn panicking_job_releases_its_key() {
+        let executor = KeyedExecutor::new();
+        let (tx, rx) = oneshot::channel();
+        executor.spawn(1, async { panic!("job failed") });
+        executor.spawn(1, async move {
+            let _ = tx.send(());
+        });
+        rx.await.expect("job queued behind a panicking one runs");
+        tokio::task::yield_now().await;
+        assert_eq!(executor.active(), 0);
+    }
+}
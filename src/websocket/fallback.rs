@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use axum::extract::ws::CloseFrame;
+use axum::extract::ws::Message;
+use axum::response::sse::Event;
+use futures::channel::mpsc;
+use futures_util::SinkExt;
+use futures_util::Stream;
+use futures_util::StreamExt;
+use serde::Serialize;
+use serde_json::json;
+use serde_json::Value;
+use thiserror::Error;
+
+use super::Session;
+use super::WsEncoding;
+use super::WsRequest;
+
+/// Frames a fallback session may write before its client takes them.
+const OUTBOUND_BUFFER: usize = 64;
+
+/// How long a poll waits for the first event.
+const POLL_WAIT: Duration = Duration::from_secs(25);
+
+/// Sessions of clients that cannot open a websocket, reached over server-sent
+/// events or long polling. They run the same `Session` as websockets, registered
+/// with `WsServer` like any other; frames travel over channels instead of a socket.
+#[derive(Clone, Default)]
+pub struct FallbackSessions {
+    sessions: Arc<Mutex<HashMap<u64, FallbackSession>>>,
+}
+
+struct FallbackSession {
+    user_id: i32,
+    /// Frames from the client, as if read from a websocket.
+    inbound: mpsc::UnboundedSender<Message>,
+    /// Frames for a long-polling client, taken by its next poll.
+    outbound: Option<Arc<tokio::sync::Mutex<mpsc::Receiver<Message>>>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PolledEvents {
+    pub events: Vec<Value>,
+    /// Set once the session has closed; later polls fail.
+    pub closed: Option<SessionClosed>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionClosed {
+    pub code: Option<u16>,
+    pub reason: Option<String>,
+}
+
+impl From<Option<CloseFrame<'static>>> for SessionClosed {
+    fn from(frame: Option<CloseFrame<'static>>) -> Self {
+        SessionClosed {
+            code: frame.as_ref().map(|f| f.code),
+            reason: frame.map(|f| f.reason.to_string()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenedPollSession {
+    pub session_id: u64,
+}
+
+pub struct FallbackRequestSent;
+
+impl Serialize for FallbackRequestSent {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str("Request was passed to the session")
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum FallbackSessionError {
+    #[error("Session is unknown or has closed")]
+    UnknownSession,
+    #[error("Session is not a long-polling session")]
+    NotPolling,
+    #[error("{0}")]
+    InvalidRequest(String),
+}
+
+impl FallbackSessions {
+    /// Starts `session` and streams what it writes as server-sent events. The first
+    /// event, `session`, carries the id requests are posted to; a `close` event
+    /// ends the stream.
+    pub fn open_stream(
+        &self,
+        user_id: i32,
+        session: Session,
+    ) -> impl Stream<Item = Result<Event, Infallible>> {
+        let session_id = session.session_id();
+        let (outbound_tx, outbound_rx) = mpsc::channel(OUTBOUND_BUFFER);
+        let inbound = self.start(user_id, session, outbound_tx, None);
+        let first = Event::default()
+            .event("session")
+            .data(json!({ "sessionId": session_id }).to_string());
+        let events = futures_util::stream::unfold(
+            (outbound_rx, inbound, false),
+            |(mut outbound_rx, inbound, closed)| async move {
+                if closed {
+                    return None;
+                }
+                loop {
+                    let (event, closed) = match outbound_rx.next().await? {
+                        Message::Text(text) => (Event::default().data(text), false),
+                        // the ping went out, so the client is still there
+                        Message::Ping(_) => {
+                            let _ = inbound.unbounded_send(Message::Pong(Vec::new()));
+                            (Event::default().comment("ping"), false)
+                        }
+                        Message::Close(frame) => {
+                            let closed = json!(SessionClosed::from(frame));
+                            (
+                                Event::default().event("close").data(closed.to_string()),
+                                true,
+                            )
+                        }
+                        _ => continue,
+                    };
+                    return Some((Ok(event), (outbound_rx, inbound, closed)));
+                }
+            },
+        );
+        futures_util::stream::once(std::future::ready(Ok(first))).chain(events)
+    }
+
+    /// Starts `session` for a client that polls for its events.
+    pub fn open_poll(
+        &self,
+        user_id: i32,
+        session: Session,
+    ) -> OpenedPollSession {
+        let session_id = session.session_id();
+        let (outbound_tx, outbound_rx) = mpsc::channel(OUTBOUND_BUFFER);
+        let outbound = Arc::new(tokio::sync::Mutex::new(outbound_rx));
+        self.start(user_id, session, outbound_tx, Some(outbound));
+        OpenedPollSession { session_id }
+    }
+
+    fn start(
+        &self,
+        user_id: i32,
+        session: Session,
+        outbound_tx: mpsc::Sender<Message>,
+        outbound: Option<Arc<tokio::sync::Mutex<mpsc::Receiver<Message>>>>,
+    ) -> mpsc::UnboundedSender<Message> {
+        let session_id = session.session_id();
+        let (inbound_tx, inbound_rx) = mpsc::unbounded();
+        self.sessions.lock().unwrap().insert(
+            session_id,
+            FallbackSession {
+                user_id,
+                inbound: inbound_tx.clone(),
+                outbound,
+            },
+        );
+        let sessions = self.sessions.clone();
+        tokio::spawn(async move {
+            let ws_tx = outbound_tx.sink_map_err(axum::Error::new);
+            let ws_rx = inbound_rx.map(Ok);
+            session.run_with(ws_tx, ws_rx).await;
+            sessions.lock().unwrap().remove(&session_id);
+        });
+        inbound_tx
+    }
+
+    /// Passes a request to a session of `user_id`, as if sent over its websocket.
+    pub fn send(
+        &self,
+        user_id: i32,
+        session_id: u64,
+        body: String,
+    ) -> Result<FallbackRequestSent, FallbackSessionError> {
+        let frame = Message::Text(body);
+        WsEncoding::decode::<WsRequest>(&frame).map_err(FallbackSessionError::InvalidRequest)?;
+        let sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get(&session_id)
+            .filter(|s| s.user_id == user_id)
+            .ok_or(FallbackSessionError::UnknownSession)?;
+        session
+            .inbound
+            .unbounded_send(frame)
+            .map_err(|_| FallbackSessionError::UnknownSession)?;
+        Ok(FallbackRequestSent)
+    }
+
+    /// Waits for events of a long-polling session and takes all of them.
+    pub async fn poll(
+        &self,
+        user_id: i32,
+        session_id: u64,
+    ) -> Result<PolledEvents, FallbackSessionError> {
+        let (inbound, outbound) = {
+            let sessions = self.sessions.lock().unwrap();
+            let session = sessions
+                .get(&session_id)
+                .filter(|s| s.user_id == user_id)
+                .ok_or(FallbackSessionError::UnknownSession)?;
+            let outbound = session
+                .outbound
+                .clone()
+                .ok_or(FallbackSessionError::NotPolling)?;
+            (session.inbound.clone(), outbound)
+        };
+        let mut outbound = outbound.lock().await;
+        let mut polled = PolledEvents {
+            events: Vec::new(),
+            closed: None,
+        };
+        let mut next = tokio::time::timeout(POLL_WAIT, outbound.next())
+            .await
+            .unwrap_or(None);
+        while let Some(frame) = next {
+            match frame {
+                Message::Text(text) => {
+                    if let Ok(event) = serde_json::from_str(&text) {
+                        polled.events.push(event);
+                    }
+                }
+                // the client is polling, so it is still there
+                Message::Ping(_) => {
+                    let _ = inbound.unbounded_send(Message::Pong(Vec::new()));
+                }
+                Message::Close(frame) => {
+                    polled.closed = Some(frame.into());
+                    self.sessions.lock().unwrap().remove(&session_id);
+                    break;
+                }
+                _ => {}
+            }
+            next = outbound.try_next().ok().flatten();
+        }
+        Ok(polled)
+    }
+}
@@ -0,0 +1,628 @@
+use std::collections::HashSet;
+
+use anyhow::anyhow;
+use anyhow::bail;
+
+use super::bus::BusEvent;
+use super::bus::FanoutBus;
+use super::message::AppMessage;
+use super::message::AppTx;
+use super::registry::SessionRegistry;
+use super::MessageAttachment;
+use super::MessageNotificationAttachment;
+use super::SentMessage;
+use super::SessionID;
+use super::WsRequest;
+use super::WsResponse::*;
+use super::WsResponse::{self};
+use crate::repository::group::GroupRepository;
+use crate::repository::message::MessageRepository;
+use crate::service::AuthService;
+use crate::service::CreateAttachmentModel;
+use crate::service::CreateDirectMessageModel;
+use crate::service::CreateGroupMessageModel;
+use crate::service::DirectMessageModel;
+use crate::service::GroupMessageModel;
+use crate::service::MessageService;
+use crate::service::PushNotification;
+
+/// The conversation a request belongs to. Requests with the same key are handled
+/// in the order they arrived, the rest run concurrently.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub(crate) enum ConversationKey {
+    /// The two users of a direct conversation, lowest id first.
+    Direct(i32, i32),
+    Group(i32),
+}
+
+impl ConversationKey {
+    fn direct(
+        user_id: i32,
+        other_id: i32,
+    ) -> Self {
+        Self::Direct(user_id.min(other_id), user_id.max(other_id))
+    }
+
+    /// The key of a request that names its conversation. Edits and deletes only name
+    /// their message, see `RequestHandler::conversation_key`.
+    pub(crate) fn of(
+        user_id: i32,
+        request: &WsRequest,
+    ) -> Option<Self> {
+        let key = match request {
+            WsRequest::SendMessage { receiver_uid, .. }
+            | WsRequest::ReadDirectMessage { receiver_uid } => Self::direct(user_id, *receiver_uid),
+            WsRequest::SendGroupMessage { group_id, .. }
+            | WsRequest::ReadGroupMessage { group_id } => Self::Group(*group_id),
+            WsRequest::DeleteDirectMessage { .. }
+            | WsRequest::EditDirectMessage { .. }
+            | WsRequest::DeleteGroupMessage { .. }
+            | WsRequest::EditGroupMessage { .. }
+            | WsRequest::SetPresence { .. }
+            | WsRequest::SetIdle { .. }
+            | WsRequest::Ping
+            | WsRequest::Reauthenticate { .. }
+            | WsRequest::Hello { .. } => return None,
+        };
+        Some(key)
+    }
+}
+
+/// Handles the chat requests of sessions. Unlike the server it holds no state of
+/// its own, so many requests can be handled at once.
+#[derive(Clone)]
+pub(crate) struct RequestHandler {
+    pub(crate) registry: SessionRegistry,
+    pub(crate) message_repository: MessageRepository,
+    pub(crate) group_repository: GroupRepository,
+    pub(crate) message_service: MessageService,
+    /// Keeps guests, who may only read, from changing messages.
+    pub(crate) auth_service: AuthService,
+    pub(crate) fanout_bus: FanoutBus,
+    /// Hands push notifications to the server, which knows who is online.
+    pub(crate) app_tx: AppTx,
+}
+
+impl RequestHandler {
+    /// The conversation of a request, looking up the message an edit or delete is
+    /// about. `None` when the request belongs to none, or its message is gone.
+    pub(crate) async fn conversation_key(
+        &self,
+        user_id: i32,
+        request: &WsRequest,
+    ) -> Option<ConversationKey> {
+        match request {
+            WsRequest::DeleteDirectMessage { message_id }
+            | WsRequest::EditDirectMessage { message_id, .. } => {
+                let res = self
+                    .message_repository
+                    .find_message_by_id(*message_id)
+                    .await;
+                match res {
+                    Ok(message) => {
+                        message.map(|m| ConversationKey::direct(m.sender_id, m.receiver_id))
+                    }
+                    Err(e) => {
+                        log::error!("{e}");
+                        None
+                    }
+                }
+            }
+            WsRequest::DeleteGroupMessage { message_id }
+            | WsRequest::EditGroupMessage { message_id, .. } => {
+                let res = self.group_repository.find_message_by_id(*message_id).await;
+                match res {
+                    Ok(message) => message.map(|m| ConversationKey::Group(m.group_id)),
+                    Err(e) => {
+                        log::error!("{e}");
+                        None
+                    }
+                }
+            }
+            request => ConversationKey::of(user_id, request),
+        }
+    }
+
+    pub(crate) async fn handle(
+        &self,
+        session_id: SessionID,
+        user_id: i32,
+        request: WsRequest,
+    ) {
+        if request.writes() {
+            if let Err(e) = self.auth_service.ensure_can_write(user_id).await {
+                self.send_session_error(session_id, e.to_string());
+                return;
+            }
+        }
+        match request {
+            WsRequest::SendMessage {
+                receiver_uid,
+                message,
+                attachments,
+            } => {
+                let res = self
+                    .send_direct_message(user_id, receiver_uid, message, attachments)
+                    .await;
+                if let Err(e) = res {
+                    log::error!("{e}");
+                    self.send_session_error(session_id, e.to_string());
+                }
+            }
+            WsRequest::SendGroupMessage {
+                group_id,
+                message,
+                attachments,
+            } => {
+                let res = self
+                    .send_group_message(user_id, group_id, message, attachments)
+                    .await;
+                if let Err(e) = res {
+                    log::error!("{e}");
+                }
+            }
+            WsRequest::ReadDirectMessage { receiver_uid } => {
+                self.handle_read_message(user_id, receiver_uid).await;
+            }
+            WsRequest::ReadGroupMessage { group_id } => {
+                self.handle_group_read_message(user_id, group_id).await;
+            }
+            WsRequest::DeleteDirectMessage { message_id } => {
+                self.handle_delete_direct_message(user_id, message_id).await;
+            }
+            WsRequest::DeleteGroupMessage { message_id } => {
+                self.handle_delete_group_message(user_id, message_id).await;
+            }
+            WsRequest::EditDirectMessage {
+                message_id,
+                edited_content,
+            } => {
+                self.handle_edit_direct_message(user_id, message_id, edited_content)
+                    .await;
+            }
+            WsRequest::EditGroupMessage {
+                message_id,
+                edited_content,
+            } => {
+                self.handle_edit_group_message(user_id, message_id, edited_content)
+                    .await;
+            }
+            // answered by the server or the session
+            WsRequest::SetPresence { .. }
+            | WsRequest::SetIdle { .. }
+            | WsRequest::Ping
+            | WsRequest::Reauthenticate { .. }
+            | WsRequest::Hello { .. } => {}
+        };
+    }
+
+    /// Sends a message on behalf of a user without a session. Only `SEND_MESSAGE` and
+    /// `SEND_GROUP_MESSAGE` can be sent this way.
+    pub(crate) async fn send(
+        &self,
+        user_id: i32,
+        request: WsRequest,
+    ) -> Result<SentMessage, anyhow::Error> {
+        match request {
+            WsRequest::SendMessage {
+                receiver_uid,
+                message,
+                attachments,
+            } => self
+                .send_direct_message(user_id, receiver_uid, message, attachments)
+                .await
+                .map(SentMessage::Direct),
+            WsRequest::SendGroupMessage {
+                group_id,
+                message,
+                attachments,
+            } => self
+                .send_group_message(user_id, group_id, message, attachments)
+                .await
+                .map(SentMessage::Group),
+            _ => bail!("Only messages can be sent without a session"),
+        }
+    }
+
+    /// Sends a message to the sessions of a user on every node.
+    fn deliver(
+        &self,
+        user_id: i32,
+        message: WsResponse,
+    ) {
+        self.deliver_to_all(vec![user_id], message);
+    }
+
+    /// Sends the same message to the sessions of several users on every node, with a
+    /// single registry lookup and bus event.
+    fn deliver_to_all(
+        &self,
+        user_ids: Vec<i32>,
+        message: WsResponse,
+    ) {
+        if user_ids.is_empty() {
+            return;
+        }
+        self.registry.send_to_users(&user_ids, &message);
+        self.fanout_bus
+            .publish(BusEvent::Notify { user_ids, message });
+    }
+
+    /// Pushes a notification to those of the users who are offline.
+    async fn push(
+        &self,
+        user_ids: Vec<i32>,
+        notification: PushNotification,
+    ) {
+        if user_ids.is_empty() {
+            return;
+        }
+        let _ = self
+            .app_tx
+            .send(AppMessage::Push {
+                user_ids,
+                notification,
+            })
+            .await;
+    }
+
+    fn send_session_error(
+        &self,
+        session_id: SessionID,
+        message: String,
+    ) -> Option<()> {
+        self.registry
+            .send_to_session(&session_id, &ErrorNotification { message })
+    }
+
+    fn send_new_message_notification(
+        &self,
+        user_id: i32,
+        msg: DirectMessageModel,
+    ) {
+        let message = MessageNotification {
+            id: msg.id,
+            sender_uid: msg.sender_id,
+            receiver_uid: msg.receiver_id,
+            is_user: user_id == msg.sender_id,
+            content: msg.content,
+            sent_at: msg.sent_at,
+            receiver_read: msg.read,
+            attachments: msg
+                .attachments
+                .iter()
+                .map(|at| MessageNotificationAttachment {
+                    id: at.id,
+                    file_type: at.file_type.clone(),
+                })
+                .collect(),
+        };
+        self.deliver(user_id, message);
+    }
+
+    async fn handle_read_message(
+        &self,
+        receiver_uid: i32,
+        sender_uid: i32,
+    ) -> Option<()> {
+        let message = ReadDirectNotification {
+            sender_uid,
+            receiver_uid,
+        };
+        let res = self
+            .message_repository
+            .update_message_read(receiver_uid, sender_uid)
+            .await;
+        match res {
+            Ok(_) => {}
+            Err(e) => return Some(log::info!("{}", e)),
+        };
+        self.deliver(sender_uid, message);
+        Some(())
+    }
+
+    async fn handle_group_read_message(
+        &self,
+        user_id: i32,
+        group_id: i32,
+    ) -> Option<()> {
+        let res = self
+            .group_repository
+            .read_all_message(user_id, group_id)
+            .await;
+        match res {
+            Ok(succ) if succ => (),
+            Ok(_) => log::error!("failed to update group message read"),
+            Err(e) => log::error!("{e}"),
+        };
+        Some(())
+    }
+
+    fn create_attachments(attachments: Vec<MessageAttachment>) -> Vec<CreateAttachmentModel> {
+        let mut create_attachments = Vec::<CreateAttachmentModel>::new();
+        for at in attachments {
+            let bytes = match at.content_as_bytes() {
+                Ok(b) => b,
+                Err(e) => {
+                    log::error!("{e}");
+                    continue;
+                }
+            };
+            create_attachments.push(CreateAttachmentModel {
+                name: at.name.clone(),
+                attachment: bytes,
+            });
+        }
+        create_attachments
+    }
+
+    async fn send_direct_message(
+        &self,
+        sender_uid: i32,
+        receiver_uid: i32,
+        msg: String,
+        attachments: Vec<MessageAttachment>,
+    ) -> Result<DirectMessageModel, anyhow::Error> {
+        let msg = self
+            .message_service
+            .create_direct_message(CreateDirectMessageModel {
+                receiver_id: receiver_uid,
+                sender_id: sender_uid,
+                content: msg,
+                attachment: Self::create_attachments(attachments),
+            })
+            .await
+            .map_err(|e| anyhow!(e))?;
+        let notification = PushNotification::DirectMessage {
+            message_id: msg.id,
+            sender_id: sender_uid,
+            content: msg.content.clone(),
+            sent_at: msg.sent_at,
+        };
+        self.send_new_message_notification(sender_uid, msg.clone());
+        self.send_new_message_notification(receiver_uid, msg.clone());
+        if receiver_uid != sender_uid {
+            self.push(vec![receiver_uid], notification).await;
+        }
+        Ok(msg)
+    }
+
+    async fn send_group_message(
+        &self,
+        sender_uid: i32,
+        group_id: i32,
+        message: String,
+        attachments: Vec<MessageAttachment>,
+    ) -> Result<GroupMessageModel, anyhow::Error> {
+        let member_ids = self
+            .group_repository
+            .find_group_members(group_id)
+            .await
+            .map_err(|e| anyhow!(e))?;
+        if !member_ids.contains(&sender_uid) {
+            bail!("User with id '{sender_uid}' is not part of group with id '{group_id}'");
+        }
+        let message = self
+            .message_service
+            .create_group_message(CreateGroupMessageModel {
+                group_id,
+                sender_id: sender_uid,
+                content: message,
+                attachment: Self::create_attachments(attachments),
+            })
+            .await
+            .map_err(|e| anyhow!(e))?;
+        let collapsing = self
+            .message_service
+            .find_collapsing_reader_ids(sender_uid)
+            .await
+            .unwrap_or_else(|e| {
+                log::error!("{e}");
+                HashSet::new()
+            });
+        let notification = PushNotification::GroupMention {
+            message_id: message.id,
+            group_id,
+            sender_id: sender_uid,
+            sender_name: message.username.clone(),
+            content: message.content.clone(),
+            sent_at: message.sent_at,
+        };
+        let mut collapsed = message.clone();
+        collapsed.collapse();
+        let sent = message.clone();
+        let message = WsResponse::from_group_message(message);
+        let collapsed = WsResponse::from_group_message(collapsed);
+        let (collapsed_ids, full_ids) = member_ids
+            .iter()
+            .partition::<Vec<i32>, _>(|mid| collapsing.contains(mid));
+        self.deliver_to_all(full_ids, message);
+        self.deliver_to_all(collapsed_ids, collapsed);
+        // only the members mentioned in the message are pushed to
+        let members = member_ids
+            .into_iter()
+            .filter(|mid| *mid != sender_uid && !collapsing.contains(mid))
+            .collect();
+        self.push(members, notification).await;
+        Ok(sent)
+    }
+
+    async fn handle_delete_direct_message(
+        &self,
+        sender_id: i32,
+        message_id: i32,
+    ) -> Option<()> {
+        let result = self.message_repository.find_message_by_id(message_id).await;
+        let message = match result {
+            Ok(Some(message)) => message,
+            Ok(None) => return Some(()),
+            Err(e) => return Some(log::info!("{e}")),
+        };
+        if message.sender_id != sender_id {
+            return Some(());
+        }
+        let result = self.message_repository.delete_message(message_id).await;
+        let success = match result {
+            Ok(succ) => succ,
+            Err(e) => return Some(log::error!("{e}")),
+        };
+        if !success {
+            return Some(());
+        }
+        let sender_message = WsResponse::DeleteMessageNotification {
+            contact_id: message.receiver_id,
+            message_id,
+        };
+        let receiver_message = WsResponse::DeleteMessageNotification {
+            contact_id: message.sender_id,
+            message_id,
+        };
+        self.deliver(message.sender_id, sender_message);
+        self.deliver(message.receiver_id, receiver_message);
+        Some(())
+    }
+
+    async fn handle_delete_group_message(
+        &self,
+        user_id: i32,
+        message_id: i32,
+    ) -> Option<()> {
+        use WsResponse::*;
+        let result = self.group_repository.find_message_by_id(message_id).await;
+        let message = match result {
+            Ok(Some(mess)) => mess,
+            Ok(None) => return Some(()),
+            Err(e) => return Some(log::info!("{}", e)),
+        };
+        if message.sender_id != user_id {
+            return Some(());
+        }
+        let result = self
+            .group_repository
+            .set_message_to_delete(message_id)
+            .await;
+        let success = match result {
+            Ok(succ) => succ,
+            Err(e) => return Some(log::info!("{e}")),
+        };
+        if !success {
+            return Some(());
+        }
+        let result = self
+            .group_repository
+            .find_group_members(message.group_id)
+            .await;
+        let members = match result {
+            Ok(mems) => mems,
+            Err(e) => return Some(log::info!("{e}")),
+        };
+        self.deliver_to_all(
+            members,
+            DeleteGroupMessageNotification {
+                group_id: message.group_id,
+                message_id: message.id,
+            },
+        );
+        Some(())
+    }
+
+    async fn handle_edit_direct_message(
+        &self,
+        user_id: i32,
+        message_id: i32,
+        edited_content: String,
+    ) -> Option<()> {
+        let result = self.message_repository.find_message_by_id(message_id).await;
+        let message = match result {
+            Ok(Some(mess)) => mess,
+            Ok(None) => return Some(()),
+            Err(e) => return Some(log::error!("{e}")),
+        };
+        if message.sender_id != user_id {
+            return Some(());
+        }
+        let result = self
+            .message_repository
+            .edit_message_by_id(message_id, edited_content.clone())
+            .await;
+        let message = match result {
+            Ok(mess) => mess,
+            Err(e) => return Some(log::error!("{e}")),
+        };
+
+        self.deliver(
+            message.sender_id,
+            UpdateDirectMessageNotification {
+                contact_id: message.receiver_id,
+                message_id,
+                content: edited_content.clone(),
+            },
+        );
+
+        self.deliver(
+            message.receiver_id,
+            UpdateDirectMessageNotification {
+                contact_id: message.sender_id,
+                message_id,
+                content: edited_content.clone(),
+            },
+        );
+        Some(())
+    }
+
+    async fn handle_edit_group_message(
+        &self,
+        user_id: i32,
+        message_id: i32,
+        edited_content: String,
+    ) -> Option<()> {
+        let result = self.group_repository.find_message_by_id(message_id).await;
+        let message = match result {
+            Ok(Some(mess)) => mess,
+            Ok(None) => return Some(()),
+            Err(e) => return Some(log::error!("{e}")),
+        };
+        if message.sender_id != user_id {
+            return Some(());
+        }
+        let result = self
+            .group_repository
+            .edit_message_by_id(message_id, edited_content.clone())
+            .await;
+        let _ = match result {
+            Ok(mess) => mess,
+            Err(e) => return Some(log::error!("{e}")),
+        };
+        let result = self
+            .group_repository
+            .find_group_members(message.group_id)
+            .await;
+        let members = match result {
+            Ok(mem) => mem,
+            Err(e) => return Some(log::error!("{e}")),
+        };
+        let collapsing = self
+            .message_service
+            .find_collapsing_reader_ids(message.sender_id)
+            .await
+            .unwrap_or_else(|e| {
+                log::error!("{e}");
+                HashSet::new()
+            });
+        let (collapsed_ids, full_ids) = members
+            .into_iter()
+            .partition::<Vec<i32>, _>(|member_id| collapsing.contains(member_id));
+        let contents = [(full_ids, edited_content), (collapsed_ids, String::new())];
+        for (member_ids, content) in contents {
+            self.deliver_to_all(
+                member_ids,
+                UpdateGroupMessageNotification {
+                    group_id: message.group_id,
+                    message_id,
+                    content,
+                },
+            );
+        }
+        Some(())
+    }
+}
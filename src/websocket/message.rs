@@ -1,40 +1,287 @@
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+
 use tokio::sync::mpsc;
+use tokio::sync::oneshot;
+use tokio::sync::Notify;
 
+use super::bus::BusEvent;
+use super::presence::PresenceUpdate;
+use super::Capability;
+use super::OnlineStats;
+use super::SentMessage;
 use super::SessionID;
+use super::WsRequest;
+use super::WsResponse;
+use crate::service::PushNotification;
 
-pub type AppTx = mpsc::UnboundedSender<AppMessage>;
-pub type AppRx = mpsc::UnboundedReceiver<AppMessage>;
-pub type SessionTx = mpsc::UnboundedSender<SessionMessage>;
-pub type SessionRx = mpsc::UnboundedReceiver<SessionMessage>;
+pub type AppTx = mpsc::Sender<AppMessage>;
+pub type AppRx = mpsc::Receiver<AppMessage>;
 
 pub enum AppMessage {
     Connect {
         session_id: SessionID,
         user_id: i32,
-        sess_tx: mpsc::UnboundedSender<SessionMessage>,
+        user_agent: Option<String>,
+        sess_tx: SessionTx,
     },
     Message {
         session_id: SessionID,
-        message: String,
+        request: WsRequest,
     },
     Disconnect {
         session_id: SessionID,
     },
+    /// A message sent without a session, e.g. with a personal access token. It is
+    /// ordered with the other requests of its conversation.
+    Send {
+        user_id: i32,
+        request: WsRequest,
+        reply: oneshot::Sender<Result<SentMessage, anyhow::Error>>,
+    },
+    /// The client declared its capabilities in `HELLO`.
+    SetCapabilities {
+        session_id: SessionID,
+        capabilities: HashSet<Capability>,
+    },
+    /// Closes every live session of a user, e.g. after a suspension.
+    DisconnectUser {
+        user_id: i32,
+    },
+    /// Pushes a notification that originates outside of a session to every live
+    /// session of the given users.
+    Notify {
+        user_ids: Vec<i32>,
+        message: WsResponse,
+    },
+    /// Pushes a notification to every live session of a user and of their online
+    /// contacts.
+    NotifyContacts {
+        user_id: i32,
+        message: WsResponse,
+    },
+    /// Sends a push notification to those of the users who have no live session on
+    /// any node.
+    Push {
+        user_ids: Vec<i32>,
+        notification: PushNotification,
+    },
+    /// A user changed who may see their presence; it is re-sent to their online
+    /// contacts.
+    PresenceChanged {
+        user_id: i32,
+    },
+    /// A block between two users was added, changed or lifted; their presence
+    /// towards each other is re-sent.
+    BlockChanged {
+        user_id: i32,
+        other_id: i32,
+    },
+    OnlineStats {
+        reply: oneshot::Sender<OnlineStats>,
+    },
+    /// An event published by another node.
+    Bus {
+        node_id: u32,
+        event: BusEvent,
+    },
+    /// A presence update of a user, loaded off the server loop.
+    Presence {
+        user_id: i32,
+        update: PresenceUpdate,
+    },
+    /// Checks for nodes that stopped sending heartbeats.
+    ExpireNodes,
+    /// Drops the sessions of nodes that stopped sending heartbeats.
+    NodesExpired(Vec<u32>),
+    /// The bus lost its subscription for a while and the events of the other nodes
+    /// sent in between are gone.
+    BusReconnected,
 }
 
 impl AppMessage {
+    /// Opens the server queue with room for `WS_APP_QUEUE_DEPTH` messages. Session
+    /// readers and bus listeners wait for room when it is full.
     pub fn channel() -> (AppTx, AppRx) {
-        mpsc::unbounded_channel::<AppMessage>()
+        let depth = std::env::var("WS_APP_QUEUE_DEPTH")
+            .map(|v| {
+                v.parse::<usize>()
+                    .expect("WS_APP_QUEUE_DEPTH cannot be parsed into usize")
+            })
+            .unwrap_or(4096)
+            .max(1);
+        mpsc::channel::<AppMessage>(depth)
     }
 }
 
+/// Close code sent to a session whose queue overflowed.
+pub const SLOW_CONSUMER_CLOSE_CODE: u16 = 1008;
+
+/// Close code sent to the sessions of a user whose access was revoked, e.g. by a
+/// suspension.
+pub const SESSION_REVOKED_CLOSE_CODE: u16 = 4003;
+
 pub enum SessionMessage {
     CloseConnection,
-    Message(String),
+    /// Closes the connection with a close frame; the session reports its own
+    /// disconnect.
+    CloseWithCode(u16, &'static str),
+    Message(WsResponse),
+    /// Like `Message`, but may be dropped when the session falls behind.
+    Event(WsResponse),
 }
 
 impl SessionMessage {
-    pub fn channel() -> (SessionTx, SessionRx) {
-        mpsc::unbounded_channel::<SessionMessage>()
+    pub fn channel(config: SessionQueueConfig) -> (SessionTx, SessionRx) {
+        let queue = Arc::new(SessionQueue {
+            messages: Mutex::new(VecDeque::new()),
+            notify: Notify::new(),
+            closed: AtomicBool::new(false),
+            config,
+        });
+        (SessionTx(queue.clone()), SessionRx(queue))
+    }
+
+    fn is_droppable(&self) -> bool {
+        matches!(self, SessionMessage::Event(_))
+    }
+}
+
+/// What happens when a session queue is full.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SlowConsumerPolicy {
+    /// Drops the oldest queued `Event`; disconnects when there is none.
+    DropOldest,
+    Disconnect,
+}
+
+#[derive(Clone, Copy)]
+pub struct SessionQueueConfig {
+    pub depth: usize,
+    pub policy: SlowConsumerPolicy,
+}
+
+impl SessionQueueConfig {
+    pub fn from_env() -> Self {
+        let depth = std::env::var("WS_SESSION_QUEUE_DEPTH")
+            .map(|v| {
+                v.parse::<usize>()
+                    .expect("WS_SESSION_QUEUE_DEPTH cannot be parsed into usize")
+            })
+            .unwrap_or(256)
+            .max(1);
+        let policy = match std::env::var("WS_SLOW_CONSUMER_POLICY").as_deref() {
+            Ok("disconnect") => SlowConsumerPolicy::Disconnect,
+            Ok("drop") | Err(_) => SlowConsumerPolicy::DropOldest,
+            Ok(policy) => panic!("WS_SLOW_CONSUMER_POLICY '{policy}' is not supported"),
+        };
+        Self { depth, policy }
+    }
+}
+
+struct SessionQueue {
+    messages: Mutex<VecDeque<SessionMessage>>,
+    notify: Notify,
+    closed: AtomicBool,
+    config: SessionQueueConfig,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SessionSendError {
+    /// The session is gone.
+    Closed,
+    /// The queue is full and nothing could be dropped.
+    Full,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Queued {
+    Queued,
+    /// Queued after dropping the oldest event.
+    DroppedOldest,
+}
+
+/// The sending half of a bounded session queue.
+#[derive(Clone)]
+pub struct SessionTx(Arc<SessionQueue>);
+
+impl SessionTx {
+    pub fn send(
+        &self,
+        message: SessionMessage,
+    ) -> Result<Queued, SessionSendError> {
+        if self.0.closed.load(Ordering::Relaxed) {
+            return Err(SessionSendError::Closed);
+        }
+        let mut messages = self.0.messages.lock().unwrap();
+        let mut queued = Queued::Queued;
+        if messages.len() >= self.0.config.depth {
+            let oldest_event = match self.0.config.policy {
+                SlowConsumerPolicy::DropOldest => messages.iter().position(|m| m.is_droppable()),
+                SlowConsumerPolicy::Disconnect => None,
+            };
+            let Some(i) = oldest_event else {
+                return Err(SessionSendError::Full);
+            };
+            messages.remove(i);
+            queued = Queued::DroppedOldest;
+        }
+        messages.push_back(message);
+        drop(messages);
+        self.0.notify.notify_one();
+        Ok(queued)
+    }
+
+    /// Closes the connection after what is already queued, even when the queue is full.
+    pub fn close(&self) {
+        self.0
+            .messages
+            .lock()
+            .unwrap()
+            .push_back(SessionMessage::CloseConnection);
+        self.0.notify.notify_one();
+    }
+
+    /// Discards everything queued and closes the connection with the given code; later
+    /// sends fail as if the session were gone.
+    pub fn close_with_code(
+        &self,
+        code: u16,
+        reason: &'static str,
+    ) {
+        self.0.closed.store(true, Ordering::Relaxed);
+        let mut messages = self.0.messages.lock().unwrap();
+        messages.clear();
+        messages.push_back(SessionMessage::CloseWithCode(code, reason));
+        drop(messages);
+        self.0.notify.notify_one();
+    }
+
+    pub fn depth(&self) -> usize {
+        self.0.messages.lock().unwrap().len()
+    }
+}
+
+/// The receiving half of a bounded session queue.
+pub struct SessionRx(Arc<SessionQueue>);
+
+impl SessionRx {
+    pub async fn recv(&mut self) -> Option<SessionMessage> {
+        loop {
+            if let Some(message) = self.0.messages.lock().unwrap().pop_front() {
+                return Some(message);
+            }
+            self.0.notify.notified().await;
+        }
+    }
+}
+
+impl Drop for SessionRx {
+    fn drop(&mut self) {
+        self.0.closed.store(true, Ordering::Relaxed);
     }
 }
@@ -1,8 +1,17 @@
+pub mod bus;
+mod codec;
+mod executor;
+mod fallback;
+mod handler;
 pub mod message;
 mod model;
+mod presence;
+mod registry;
 pub mod server;
 pub mod session;
 
+pub use codec::*;
+pub use fallback::*;
 pub use model::*;
 pub use server::*;
 pub use session::*;
@@ -1,44 +1,214 @@
+use std::collections::HashSet;
 use std::str::FromStr;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
 
 use base64::engine::general_purpose;
 use base64::Engine;
 use chrono::NaiveDateTime;
-use rand::Rng;
+use serde::de::Visitor;
 use serde::Deserialize;
 use serde::Serialize;
 use serde_json::Error;
 
 use crate::repository::AttachmentFileType;
+use crate::service::ConversationSettingsModel;
+use crate::service::ConversationType;
+use crate::service::DirectMessageModel;
 use crate::service::GroupMessageModel;
+use crate::service::PresenceState;
+use crate::service::UserProfile;
 
+use super::bus::node_id;
 use super::message::SessionTx;
 
-#[derive(Clone, Eq, Hash, PartialEq)]
-pub(crate) struct SessionID(i32);
+static NEXT_SESSION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// The protocol of clients that do not send `HELLO`; they receive every event.
+pub const LEGACY_PROTOCOL_VERSION: u32 = 1;
+pub const PROTOCOL_VERSION: u32 = 2;
+/// Clients asking for an older protocol are refused.
+pub const MIN_PROTOCOL_VERSION: u32 = LEGACY_PROTOCOL_VERSION;
+
+/// Optional events a client declares in `HELLO`. Unknown capabilities are ignored.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum Capability {
+    /// `USERS_ONLINE`
+    Presence,
+    /// `SESSIONS_CHANGED`
+    Sessions,
+    /// `PROFILE_UPDATED`
+    Profiles,
+    /// `TOKEN_EXPIRING`
+    TokenExpiry,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 4] = [
+        Capability::Presence,
+        Capability::Sessions,
+        Capability::Profiles,
+        Capability::TokenExpiry,
+    ];
+}
+
+impl FromStr for Capability {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "presence" => Ok(Capability::Presence),
+            "sessions" => Ok(Capability::Sessions),
+            "profiles" => Ok(Capability::Profiles),
+            "tokenExpiry" => Ok(Capability::TokenExpiry),
+            _ => Err(format!("Unknown capability {s}")),
+        }
+    }
+}
+
+#[derive(Clone, Eq, Hash, PartialEq, PartialOrd, Ord)]
+pub(crate) struct SessionID(u64);
 
 impl SessionID {
+    /// Unique across the nodes sharing a fan-out bus.
     pub(crate) fn create() -> Self {
-        SessionID(rand::thread_rng().gen_range(10_000..=99_999))
+        let counter = NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed);
+        SessionID(((node_id() as u64) << 32) | counter)
+    }
+
+    pub(crate) fn value(&self) -> u64 {
+        self.0
     }
 }
 
 pub(crate) struct SessionHandle {
     pub(crate) user_id: i32,
     pub(crate) sender: SessionTx,
+    pub(crate) user_agent: Option<String>,
+    pub(crate) connected_at: NaiveDateTime,
+    /// Reported by the client through `SET_IDLE`.
+    pub(crate) idle: bool,
+    /// Declared by the client through `HELLO`; `None` for legacy clients, which only
+    /// get the events of the legacy protocol.
+    pub(crate) capabilities: Option<HashSet<Capability>>,
+}
+
+impl SessionHandle {
+    pub(crate) fn accepts(
+        &self,
+        message: &WsResponse,
+    ) -> bool {
+        match (&self.capabilities, message.capability()) {
+            // and pongs, which only answer a `PING` the client sent
+            (None, _) => message.is_legacy() || matches!(message, WsResponse::Pong),
+            (Some(capabilities), Some(capability)) => capabilities.contains(&capability),
+            (Some(_), None) => true,
+        }
+    }
+}
+
+/// A message sent without a session, as it was stored.
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum SentMessage {
+    Direct(DirectMessageModel),
+    Group(GroupMessageModel),
+}
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct OnlineStats {
+    pub online_users: usize,
+    pub online_sessions: usize,
+    /// Messages waiting in the session queues of this node.
+    pub queued_messages: usize,
+    pub max_queue_depth: usize,
+    pub dropped_messages: u64,
+    pub slow_consumer_disconnects: u64,
+    /// Conversations with requests being handled on this node.
+    pub active_conversations: usize,
 }
 
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct MessageAttachment {
     pub name: String,
-    pub content_base64: String,
+    pub content_base64: AttachmentContent,
 }
 
 impl MessageAttachment {
     pub fn content_as_bytes(&self) -> Result<Vec<u8>, String> {
-        general_purpose::STANDARD
-            .decode(&self.content_base64)
-            .map_err(|e| e.to_string())
+        match &self.content_base64 {
+            AttachmentContent::Base64(content) => general_purpose::STANDARD
+                .decode(content)
+                .map_err(|e| e.to_string()),
+            AttachmentContent::Raw(bytes) => Ok(bytes.clone()),
+        }
+    }
+}
+
+/// Base64 text in JSON; MessagePack clients may send a binary value with the raw
+/// bytes instead.
+pub enum AttachmentContent {
+    Base64(String),
+    Raw(Vec<u8>),
+}
+
+impl Serialize for AttachmentContent {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            AttachmentContent::Base64(content) => serializer.serialize_str(content),
+            AttachmentContent::Raw(bytes) => serializer.serialize_bytes(bytes),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for AttachmentContent {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct AttachmentContentVisitor;
+
+        impl<'de> Visitor<'de> for AttachmentContentVisitor {
+            type Value = AttachmentContent;
+
+            fn expecting(
+                &self,
+                f: &mut std::fmt::Formatter,
+            ) -> std::fmt::Result {
+                f.write_str("base64 text or binary attachment content")
+            }
+
+            fn visit_str<E: serde::de::Error>(
+                self,
+                v: &str,
+            ) -> Result<Self::Value, E> {
+                Ok(AttachmentContent::Base64(v.to_string()))
+            }
+
+            fn visit_bytes<E: serde::de::Error>(
+                self,
+                v: &[u8],
+            ) -> Result<Self::Value, E> {
+                Ok(AttachmentContent::Raw(v.to_vec()))
+            }
+
+            fn visit_byte_buf<E: serde::de::Error>(
+                self,
+                v: Vec<u8>,
+            ) -> Result<Self::Value, E> {
+                Ok(AttachmentContent::Raw(v))
+            }
+        }
+
+        deserializer.deserialize_any(AttachmentContentVisitor)
     }
 }
 
@@ -46,7 +216,48 @@ impl MessageAttachment {
 #[serde(rename_all = "camelCase")]
 pub struct UserOnlineStatus {
     pub(crate) user_id: i32,
-    pub(crate) online: bool,
+    pub(crate) state: PresenceState,
+    /// Set when a user goes offline and their visibility allows it.
+    pub(crate) last_seen_at: Option<NaiveDateTime>,
+}
+
+/// `USERS_ONLINE` as legacy clients know it, which only tells whether users are
+/// online.
+#[derive(Serialize)]
+#[serde(tag = "type", rename = "USERS_ONLINE")]
+pub struct LegacyUsersOnline {
+    users: Vec<LegacyUserOnlineStatus>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LegacyUserOnlineStatus {
+    user_id: i32,
+    online: bool,
+}
+
+impl From<&[UserOnlineStatus]> for LegacyUsersOnline {
+    fn from(users: &[UserOnlineStatus]) -> Self {
+        let users = users
+            .iter()
+            .map(|status| LegacyUserOnlineStatus {
+                user_id: status.user_id,
+                online: status.state.public() != PresenceState::Offline,
+            })
+            .collect();
+        Self { users }
+    }
+}
+
+/// One of the live sessions of a user, as listed in `SESSIONS_CHANGED`.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionInfo {
+    pub(crate) session_id: u64,
+    pub(crate) user_agent: Option<String>,
+    pub(crate) connected_at: NaiveDateTime,
+    /// Whether this is the session receiving the list.
+    pub(crate) current: bool,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -97,6 +308,47 @@ pub enum WsRequest {
         message_id: i32,
         edited_content: String,
     },
+
+    #[serde(rename = "SET_PRESENCE")]
+    #[serde(rename_all = "camelCase")]
+    SetPresence { state: PresenceState },
+
+    #[serde(rename = "SET_IDLE")]
+    #[serde(rename_all = "camelCase")]
+    SetIdle { idle: bool },
+
+    /// Keeps the session alive for clients that cannot send ping frames.
+    #[serde(rename = "PING")]
+    Ping,
+
+    /// Hands the session a fresh token before the current one expires.
+    #[serde(rename = "REAUTHENTICATE")]
+    #[serde(rename_all = "camelCase")]
+    Reauthenticate { token: String },
+
+    /// Opens a versioned session; clients that skip it are served the legacy protocol.
+    #[serde(rename = "HELLO")]
+    #[serde(rename_all = "camelCase")]
+    Hello {
+        protocol_version: u32,
+        #[serde(default)]
+        capabilities: Vec<String>,
+    },
+}
+
+impl WsRequest {
+    /// Whether the request sends or changes a message, which guests cannot do.
+    pub(crate) fn writes(&self) -> bool {
+        matches!(
+            self,
+            WsRequest::SendMessage { .. }
+                | WsRequest::SendGroupMessage { .. }
+                | WsRequest::DeleteDirectMessage { .. }
+                | WsRequest::DeleteGroupMessage { .. }
+                | WsRequest::EditDirectMessage { .. }
+                | WsRequest::EditGroupMessage { .. }
+        )
+    }
 }
 
 impl FromStr for WsRequest {
@@ -112,6 +364,15 @@ impl ToString for WsRequest {
     }
 }
 
+/// The first frame of a websocket whose upgrade request carried no credentials.
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+pub enum WsAuthRequest {
+    #[serde(rename = "AUTH")]
+    #[serde(rename_all = "camelCase")]
+    Auth { token: String },
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct MessageNotificationAttachment {
     pub id: i32,
@@ -143,6 +404,7 @@ pub enum WsResponse {
         group_id: i32,
         content: String,
         sent_at: NaiveDateTime,
+        collapsed: bool,
         attachments: Vec<MessageNotificationAttachment>,
     },
 
@@ -180,6 +442,102 @@ pub enum WsResponse {
     #[serde(rename = "USERS_ONLINE")]
     #[serde(rename_all = "camelCase")]
     UsersOnline { users: Vec<UserOnlineStatus> },
+
+    #[serde(rename = "PROFILE_UPDATED")]
+    #[serde(rename_all = "camelCase")]
+    ProfileUpdated {
+        user_id: i32,
+        username: String,
+        profile: UserProfile,
+    },
+
+    /// Sent to every session of a user when they change a conversation's settings.
+    #[serde(rename = "CONVERSATION_SETTINGS_UPDATED")]
+    #[serde(rename_all = "camelCase")]
+    ConversationSettingsUpdated {
+        conversation_type: ConversationType,
+        conversation_id: i32,
+        settings: ConversationSettingsModel,
+    },
+
+    #[serde(rename = "SESSIONS_CHANGED")]
+    #[serde(rename_all = "camelCase")]
+    SessionsChanged { sessions: Vec<SessionInfo> },
+
+    #[serde(rename = "PONG")]
+    Pong,
+
+    #[serde(rename = "TOKEN_EXPIRING")]
+    #[serde(rename_all = "camelCase")]
+    TokenExpiring { expires_at: NaiveDateTime },
+
+    #[serde(rename = "REAUTHENTICATED")]
+    #[serde(rename_all = "camelCase")]
+    Reauthenticated { expires_at: NaiveDateTime },
+
+    #[serde(rename = "AUTHENTICATED")]
+    #[serde(rename_all = "camelCase")]
+    Authenticated { expires_at: NaiveDateTime },
+
+    #[serde(rename = "WELCOME")]
+    #[serde(rename_all = "camelCase")]
+    Welcome {
+        protocol_version: u32,
+        session_id: u64,
+        server_time: NaiveDateTime,
+        features: Vec<Capability>,
+    },
+
+    /// Events may have been missed; the client reloads its state over the REST API.
+    #[serde(rename = "RESYNC")]
+    Resync,
+
+    /// Replaces the silently ignored parse errors of the legacy protocol.
+    #[serde(rename = "INVALID_REQUEST")]
+    #[serde(rename_all = "camelCase")]
+    InvalidRequest {
+        request_type: Option<String>,
+        message: String,
+    },
+}
+
+impl WsResponse {
+    /// Whether the message may be dropped for a session that falls behind; a newer one
+    /// of the same kind replaces it.
+    pub fn is_droppable(&self) -> bool {
+        matches!(
+            self,
+            WsResponse::UsersOnline { .. } | WsResponse::SessionsChanged { .. }
+        )
+    }
+
+    /// The capability a client must declare to receive the message.
+    /// Whether the response was part of the protocol before `HELLO`. Legacy clients
+    /// get no other events.
+    pub fn is_legacy(&self) -> bool {
+        matches!(
+            self,
+            WsResponse::MessageNotification { .. }
+                | WsResponse::GroupMessageNotification { .. }
+                | WsResponse::ReadDirectNotification { .. }
+                | WsResponse::ErrorNotification { .. }
+                | WsResponse::DeleteMessageNotification { .. }
+                | WsResponse::DeleteGroupMessageNotification { .. }
+                | WsResponse::UpdateDirectMessageNotification { .. }
+                | WsResponse::UpdateGroupMessageNotification { .. }
+                | WsResponse::UsersOnline { .. }
+        )
+    }
+
+    pub fn capability(&self) -> Option<Capability> {
+        match self {
+            WsResponse::UsersOnline { .. } => Some(Capability::Presence),
+            WsResponse::SessionsChanged { .. } => Some(Capability::Sessions),
+            WsResponse::ProfileUpdated { .. } => Some(Capability::Profiles),
+            WsResponse::TokenExpiring { .. } => Some(Capability::TokenExpiry),
+            _ => None,
+        }
+    }
 }
 
 impl ToString for WsResponse {
@@ -197,6 +555,7 @@ impl WsResponse {
             group_id: message.group_id,
             content: message.content,
             sent_at: message.sent_at,
+            collapsed: message.collapsed,
             attachments: message
                 .attachments
                 .iter()
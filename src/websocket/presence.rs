@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use chrono::NaiveDateTime;
+
+use crate::repository::PresenceRepository;
+use crate::service::ContactService;
+use crate::service::PresenceState;
+use crate::service::PresenceVisibility;
+
+use super::SessionID;
+
+/// The contacts a presence update of a user concerns, whether they are online or not.
+#[derive(Default)]
+pub struct PresenceContacts {
+    /// Direct contacts, without those blocked either way.
+    pub contact_ids: HashSet<i32>,
+    /// The contacts whose presence the user may see.
+    pub visible: HashSet<i32>,
+    /// The contacts who may see the user's presence.
+    pub audience: HashSet<i32>,
+}
+
+/// A presence update of a user, together with what it needed from the database. It
+/// is loaded off the server loop and applied on it.
+pub enum PresenceUpdate {
+    /// A session of the user connected. `stored_state` is the state the user chose
+    /// before, when the server did not know it yet.
+    Connected {
+        stored_state: Option<PresenceState>,
+        contacts: PresenceContacts,
+    },
+    /// The user chose a state, which is stored.
+    Chosen {
+        state: PresenceState,
+        contacts: PresenceContacts,
+    },
+    /// The public state of the user changed.
+    Changed {
+        state: PresenceState,
+        last_seen_at: Option<NaiveDateTime>,
+        contacts: PresenceContacts,
+    },
+    /// The last session of the user on this node closed.
+    Disconnected {
+        last_seen_at: Option<NaiveDateTime>,
+        contacts: PresenceContacts,
+    },
+    /// The user changed who may see their presence.
+    VisibilityChanged { contacts: PresenceContacts },
+    /// A session of the user negotiated presence events through `HELLO`, and only
+    /// got its contacts in the legacy shape so far.
+    Negotiated {
+        session_id: SessionID,
+        contacts: PresenceContacts,
+    },
+    /// A block between the user and another one changed. `visible` tells whether the
+    /// user may see the other's presence now, `shown` whether the other may see theirs.
+    BlockChanged {
+        other_id: i32,
+        visible: bool,
+        shown: bool,
+    },
+}
+
+/// Reads what presence updates need from the database, away from the server loop.
+#[derive(Clone)]
+pub(crate) struct PresenceLoader {
+    pub(crate) contact_service: ContactService,
+    pub(crate) presence_repository: PresenceRepository,
+}
+
+impl PresenceLoader {
+    pub(crate) async fn find_contact_ids(
+        &self,
+        user_id: i32,
+    ) -> Result<HashSet<i32>, anyhow::Error> {
+        let block_related = self.contact_service.find_block_related_ids(user_id).await?;
+        Ok(self
+            .contact_service
+            .find_direct_contacts_for_user(user_id)
+            .await?
+            .iter()
+            .map(|c| c.id)
+            .filter(|id| !block_related.contains(id))
+            .collect())
+    }
+
+    /// The contacts of a user along with who may see whose presence.
+    pub(crate) async fn find_contacts(
+        &self,
+        user_id: i32,
+    ) -> Result<PresenceContacts, anyhow::Error> {
+        let contact_ids = self.find_contact_ids(user_id).await?;
+        self.find_visibility(user_id, contact_ids).await
+    }
+
+    /// Splits the given contacts of a user into those whose presence the user may
+    /// see, and those who may see the user's presence.
+    pub(crate) async fn find_visibility(
+        &self,
+        user_id: i32,
+        contact_ids: HashSet<i32>,
+    ) -> Result<PresenceContacts, anyhow::Error> {
+        let partners = self
+            .presence_repository
+            .find_conversation_partner_ids(user_id)
+            .await
+            .map_err(|e| anyhow!(e))?
+            .into_iter()
+            .collect::<HashSet<_>>();
+        let mut user_ids = contact_ids.iter().cloned().collect::<Vec<_>>();
+        user_ids.push(user_id);
+        let visibility = self
+            .presence_repository
+            .find_presence_visibility(user_ids)
+            .await
+            .map_err(|e| anyhow!(e))?
+            .into_iter()
+            .map(|p| {
+                let visibility = PresenceVisibility::from_str(&p.presence_visibility)
+                    .unwrap_or(PresenceVisibility::Everyone);
+                (p.user_id, visibility)
+            })
+            .collect::<HashMap<_, _>>();
+        let visible = contact_ids
+            .iter()
+            .filter(|id| {
+                visibility
+                    .get(id)
+                    .is_some_and(|v| v.visible_to(partners.contains(id)))
+            })
+            .cloned()
+            .collect();
+        let own_visibility = visibility
+            .get(&user_id)
+            .copied()
+            .unwrap_or(PresenceVisibility::Everyone);
+        let audience = contact_ids
+            .iter()
+            .filter(|id| own_visibility.visible_to(partners.contains(id)))
+            .cloned()
+            .collect();
+        Ok(PresenceContacts {
+            contact_ids,
+            visible,
+            audience,
+        })
+    }
+
+    /// The state a user chose in an earlier session.
+    pub(crate) async fn find_presence_state(
+        &self,
+        user_id: i32,
+    ) -> PresenceState {
+        match self.presence_repository.find_presence_state(user_id).await {
+            Ok(state) => state
+                .and_then(|s| PresenceState::from_str(&s).ok())
+                .unwrap_or(PresenceState::Online),
+            Err(e) => {
+                log::error!("{e}");
+                PresenceState::Online
+            }
+        }
+    }
+
+    pub(crate) async fn update_last_seen(
+        &self,
+        user_id: i32,
+    ) -> Option<NaiveDateTime> {
+        match self.presence_repository.update_last_seen(user_id).await {
+            Ok(last_seen_at) => last_seen_at,
+            Err(e) => {
+                log::error!("{e}");
+                None
+            }
+        }
+    }
+}
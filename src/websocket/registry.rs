@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::RwLock;
+
+use super::message::Queued;
+use super::message::SessionMessage;
+use super::message::SessionSendError;
+use super::message::SessionTx;
+use super::message::SLOW_CONSUMER_CLOSE_CODE;
+use super::Capability;
+use super::SessionHandle;
+use super::SessionID;
+use super::WsResponse;
+
+/// The live sessions of this node, shared between the websocket server and the
+/// tasks handling requests. The lock is never held while a message is queued.
+#[derive(Clone, Default)]
+pub(crate) struct SessionRegistry {
+    sessions: Arc<RwLock<Sessions>>,
+    /// Events dropped from the queues of slow sessions.
+    dropped_messages: Arc<AtomicU64>,
+    slow_consumer_disconnects: Arc<AtomicU64>,
+}
+
+#[derive(Default)]
+struct Sessions {
+    by_id: HashMap<SessionID, SessionHandle>,
+    /// The sessions of each user, so a user's sessions are found without a scan.
+    by_user: HashMap<i32, Vec<SessionID>>,
+}
+
+impl Sessions {
+    fn of_user(
+        &self,
+        user_id: i32,
+    ) -> impl Iterator<Item = (&SessionID, &SessionHandle)> {
+        self.by_user
+            .get(&user_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|session_id| self.by_id.get_key_value(session_id))
+    }
+}
+
+impl SessionRegistry {
+    pub(crate) fn insert(
+        &self,
+        session_id: SessionID,
+        handle: SessionHandle,
+    ) {
+        let mut sessions = self.sessions.write().unwrap();
+        sessions
+            .by_user
+            .entry(handle.user_id)
+            .or_default()
+            .push(session_id.clone());
+        sessions.by_id.insert(session_id, handle);
+    }
+
+    pub(crate) fn remove(
+        &self,
+        session_id: &SessionID,
+    ) -> Option<SessionHandle> {
+        let mut sessions = self.sessions.write().unwrap();
+        let handle = sessions.by_id.remove(session_id)?;
+        if let Some(session_ids) = sessions.by_user.get_mut(&handle.user_id) {
+            session_ids.retain(|id| id != session_id);
+            if session_ids.is_empty() {
+                sessions.by_user.remove(&handle.user_id);
+            }
+        }
+        Some(handle)
+    }
+
+    pub(crate) fn set_idle(
+        &self,
+        session_id: &SessionID,
+        idle: bool,
+    ) -> Option<()> {
+        self.sessions
+            .write()
+            .unwrap()
+            .by_id
+            .get_mut(session_id)?
+            .idle = idle;
+        Some(())
+    }
+
+    pub(crate) fn set_capabilities(
+        &self,
+        session_id: &SessionID,
+        capabilities: HashSet<Capability>,
+    ) -> Option<()> {
+        self.sessions
+            .write()
+            .unwrap()
+            .by_id
+            .get_mut(session_id)?
+            .capabilities = Some(capabilities);
+        Some(())
+    }
+
+    pub(crate) fn user_id(
+        &self,
+        session_id: &SessionID,
+    ) -> Option<i32> {
+        self.sessions
+            .read()
+            .unwrap()
+            .by_id
+            .get(session_id)
+            .map(|handle| handle.user_id)
+    }
+
+    pub(crate) fn has_user(
+        &self,
+        user_id: i32,
+    ) -> bool {
+        self.sessions.read().unwrap().by_user.contains_key(&user_id)
+    }
+
+    /// The users with a session on this node.
+    pub(crate) fn user_ids(&self) -> HashSet<i32> {
+        self.sessions
+            .read()
+            .unwrap()
+            .by_user
+            .keys()
+            .cloned()
+            .collect()
+    }
+
+    pub(crate) fn session_ids(
+        &self,
+        user_id: i32,
+    ) -> Vec<SessionID> {
+        self.sessions
+            .read()
+            .unwrap()
+            .by_user
+            .get(&user_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Reads the sessions; `f` must not call back into the registry.
+    pub(crate) fn with_sessions<R>(
+        &self,
+        f: impl FnOnce(&HashMap<SessionID, SessionHandle>) -> R,
+    ) -> R {
+        f(&self.sessions.read().unwrap().by_id)
+    }
+
+    /// Reads the sessions of a user; `f` must not call back into the registry.
+    pub(crate) fn with_user_sessions<R>(
+        &self,
+        user_id: i32,
+        f: impl FnOnce(Vec<(&SessionID, &SessionHandle)>) -> R,
+    ) -> R {
+        f(self.sessions.read().unwrap().of_user(user_id).collect())
+    }
+
+    pub(crate) fn send(
+        &self,
+        user_id: i32,
+        message: &WsResponse,
+    ) {
+        self.send_to_users(&[user_id], message);
+    }
+
+    /// Sends a message to the sessions of several users, looking them up at once.
+    pub(crate) fn send_to_users(
+        &self,
+        user_ids: &[i32],
+        message: &WsResponse,
+    ) {
+        let senders = {
+            let sessions = self.sessions.read().unwrap();
+            user_ids
+                .iter()
+                .flat_map(|user_id| sessions.of_user(*user_id))
+                .filter(|(_, handle)| handle.accepts(message))
+                .map(|(_, handle)| (handle.sender.clone(), handle.user_id))
+                .collect::<Vec<_>>()
+        };
+        for (sender, user_id) in senders {
+            self.queue(&sender, user_id, message);
+        }
+    }
+
+    /// Sends a message to every session of this node.
+    pub(crate) fn send_to_all(
+        &self,
+        message: &WsResponse,
+    ) {
+        let senders = self.with_sessions(|sessions| {
+            sessions
+                .values()
+                .filter(|handle| handle.accepts(message))
+                .map(|handle| (handle.sender.clone(), handle.user_id))
+                .collect::<Vec<_>>()
+        });
+        for (sender, user_id) in senders {
+            self.queue(&sender, user_id, message);
+        }
+    }
+
+    pub(crate) fn send_to_session(
+        &self,
+        session_id: &SessionID,
+        message: &WsResponse,
+    ) -> Option<()> {
+        let (sender, user_id, accepts) = self.with_sessions(|sessions| {
+            let handle = sessions.get(session_id)?;
+            Some((
+                handle.sender.clone(),
+                handle.user_id,
+                handle.accepts(message),
+            ))
+        })?;
+        if accepts {
+            self.queue(&sender, user_id, message);
+        }
+        Some(())
+    }
+
+    /// Queues a message on a session, closing the session when it cannot keep up.
+    fn queue(
+        &self,
+        sender: &SessionTx,
+        user_id: i32,
+        message: &WsResponse,
+    ) {
+        let message = if message.is_droppable() {
+            SessionMessage::Event(message.clone())
+        } else {
+            SessionMessage::Message(message.clone())
+        };
+        match sender.send(message) {
+            Ok(Queued::Queued) => {}
+            Ok(Queued::DroppedOldest) => {
+                self.dropped_messages.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(SessionSendError::Full) => {
+                log::warn!("closing a slow session of user {user_id}");
+                self.slow_consumer_disconnects
+                    .fetch_add(1, Ordering::Relaxed);
+                sender.close_with_code(SLOW_CONSUMER_CLOSE_CODE, "Slow consumer");
+            }
+            // the session reports its own disconnect
+            Err(SessionSendError::Closed) => {}
+        }
+    }
+
+    pub(crate) fn dropped_messages(&self) -> u64 {
+        self.dropped_messages.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn slow_consumer_disconnects(&self) -> u64 {
+        self.slow_consumer_disconnects.load(Ordering::Relaxed)
+    }
+}
@@ -1,140 +1,653 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
-use std::str::FromStr;
+use std::future::Future;
+use std::time::Instant;
 
+use anyhow::anyhow;
+use chrono::NaiveDateTime;
+use tokio::sync::oneshot;
+
+use super::bus::BusEvent;
+use super::bus::FanoutBus;
+use super::bus::RemoteSession;
+use super::bus::HEARTBEAT_INTERVAL;
+use super::executor::KeyedExecutor;
+use super::handler::ConversationKey;
+use super::handler::RequestHandler;
+use super::presence::PresenceContacts;
+use super::presence::PresenceLoader;
+use super::presence::PresenceUpdate;
+use super::registry::SessionRegistry;
+use super::session::HeartbeatConfig;
 use super::session::SessionFactory;
-use super::MessageAttachment;
-use super::MessageNotificationAttachment;
+use super::Capability;
+use super::OnlineStats;
+use super::SentMessage;
 use super::SessionHandle;
 use super::SessionID;
+use super::SessionInfo;
 use super::UserOnlineStatus;
 use super::WsRequest;
 use super::WsResponse::*;
 use super::WsResponse::{self};
 use crate::repository::group::GroupRepository;
 use crate::repository::message::MessageRepository;
+use crate::repository::PresenceRepository;
+use crate::service::AuthService;
 use crate::service::ContactService;
-use crate::service::CreateAttachmentModel;
-use crate::service::CreateDirectMessageModel;
-use crate::service::CreateGroupMessageModel;
-use crate::service::DirectMessageModel;
+use crate::service::JwtService;
 use crate::service::MessageService;
+use crate::service::PresenceState;
+use crate::service::PushNotification;
+use crate::service::PushService;
 use crate::websocket::message::AppMessage;
 use crate::websocket::message::AppRx;
-use crate::websocket::message::SessionMessage;
+use crate::websocket::message::AppTx;
+use crate::websocket::message::SessionQueueConfig;
 use crate::websocket::message::SessionTx;
+use crate::websocket::message::SESSION_REVOKED_CLOSE_CODE;
 
 pub struct WsServer {
-    user_storage: HashMap<SessionID, SessionHandle>,
+    registry: SessionRegistry,
     app_rx: AppRx,
-    message_repository: MessageRepository,
-    group_repository: GroupRepository,
-    message_service: MessageService,
-    contact_service: ContactService,
+    /// Handles chat requests off the server loop.
+    handler: RequestHandler,
+    executor: KeyedExecutor<ConversationKey>,
+    /// Finds the conversation of each request, one after another for each session, so
+    /// that the requests of a session reach their conversation in order.
+    lookup_executor: KeyedExecutor<SessionID>,
+    /// Loads presence updates off the server loop, one after another for each user.
+    presence_executor: KeyedExecutor<i32>,
+    presence_loader: PresenceLoader,
+    app_tx: AppTx,
+    /// The state chosen by each online user.
+    presence_states: HashMap<i32, PresenceState>,
+    /// When set, connecting beyond this many sessions closes the oldest ones of the user.
+    max_sessions_per_user: Option<usize>,
+    fanout_bus: FanoutBus,
+    /// Sessions other nodes hold, by user and node.
+    remote_sessions: HashMap<i32, HashMap<u32, Vec<RemoteSession>>>,
+    /// When each of the other nodes was last heard from.
+    node_seen: HashMap<u32, Instant>,
+    push_service: PushService,
 }
 
 impl WsServer {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         message_repository: MessageRepository,
         group_repository: GroupRepository,
         message_service: MessageService,
         contact_service: ContactService,
+        presence_repository: PresenceRepository,
+        push_service: PushService,
+        auth_service: AuthService,
+        jwt_service: JwtService,
+        fanout_bus: FanoutBus,
+        app_tx: AppTx,
+        app_rx: AppRx,
     ) -> (Self, SessionFactory) {
-        let (app_tx, app_rx) = AppMessage::channel();
+        fanout_bus.start(app_tx.clone());
 
-        let user_storage = HashMap::new();
-        let message_repository = message_repository;
+        let registry = SessionRegistry::default();
+        let max_sessions_per_user = std::env::var("WS_MAX_SESSIONS_PER_USER")
+            .ok()
+            .map(|v| {
+                v.parse::<usize>()
+                    .expect("WS_MAX_SESSIONS_PER_USER cannot be parsed into usize")
+            })
+            .filter(|max| *max > 0);
         let ws_server = Self {
-            user_storage,
+            registry: registry.clone(),
             app_rx,
-            message_repository,
-            group_repository,
-            message_service,
-            contact_service,
+            handler: RequestHandler {
+                registry,
+                message_repository,
+                group_repository,
+                message_service,
+                auth_service: auth_service.clone(),
+                fanout_bus: fanout_bus.clone(),
+                app_tx: app_tx.clone(),
+            },
+            executor: KeyedExecutor::new(),
+            lookup_executor: KeyedExecutor::new(),
+            presence_executor: KeyedExecutor::new(),
+            presence_loader: PresenceLoader {
+                contact_service,
+                presence_repository,
+            },
+            app_tx: app_tx.clone(),
+            presence_states: HashMap::new(),
+            max_sessions_per_user,
+            fanout_bus,
+            remote_sessions: HashMap::new(),
+            node_seen: HashMap::new(),
+            push_service,
+        };
+        let session_factory = SessionFactory {
+            app_tx,
+            jwt_service,
+            auth_service,
+            queue_config: SessionQueueConfig::from_env(),
+            heartbeat_config: HeartbeatConfig::from_env(),
         };
-        let session_factory = SessionFactory { app_tx };
         (ws_server, session_factory)
     }
 
-    async fn get_online_contact_ids(
+    /// Loads what a presence update of a user needs off the server loop, then hands
+    /// it back to be applied. The updates of a user apply in the order they were
+    /// loaded.
+    fn load_presence(
         &self,
         user_id: i32,
-    ) -> Result<HashSet<i32>, anyhow::Error> {
-        let contact_ids = self
-            .contact_service
-            .find_direct_contacts_for_user(user_id)
-            .await?
-            .iter()
-            .map(|c| c.id)
-            .collect::<HashSet<_>>();
-        let online_users = self
-            .user_storage
+        load: impl Future<Output = Option<PresenceUpdate>> + Send + 'static,
+    ) {
+        let app_tx = self.app_tx.clone();
+        self.presence_executor.spawn(user_id, async move {
+            if let Some(update) = load.await {
+                let _ = app_tx.send(AppMessage::Presence { user_id, update }).await;
+            }
+        });
+    }
+
+    /// Those of the users who have a session on any node.
+    fn online_ids<'a>(
+        &'a self,
+        user_ids: &'a HashSet<i32>,
+    ) -> impl Iterator<Item = i32> + 'a {
+        user_ids
             .iter()
-            .map(|(_, handle)| handle.user_id)
-            .collect::<HashSet<_>>();
-        let online_contacts = online_users
-            .intersection(&contact_ids)
             .cloned()
-            .collect::<HashSet<_>>();
-        Ok(online_contacts)
+            .filter(|user_id| self.is_online(*user_id))
     }
 
-    async fn send_online_notification(
+    fn notify_contacts(
         &self,
         user_id: i32,
-    ) -> Option<()> {
-        let online_contacts = self
-            .get_online_contact_ids(user_id)
-            .await
-            .ok()?
-            .iter()
-            .map(|user_id| UserOnlineStatus {
-                user_id: *user_id,
-                online: true,
+        message: WsResponse,
+    ) {
+        let loader = self.presence_loader.clone();
+        let registry = self.registry.clone();
+        self.presence_executor.spawn(user_id, async move {
+            let contact_ids = match loader.find_contact_ids(user_id).await {
+                Ok(contact_ids) => contact_ids,
+                Err(e) => return log::error!("{e}"),
+            };
+            let mut user_ids = contact_ids.into_iter().collect::<Vec<_>>();
+            user_ids.push(user_id);
+            registry.send_to_users(&user_ids, &message);
+        });
+    }
+
+    /// Re-sends a user's presence to their online contacts after the user changed
+    /// who may see it.
+    fn send_presence_changed(
+        &self,
+        user_id: i32,
+    ) {
+        if !self.is_online(user_id) {
+            return;
+        }
+        let loader = self.presence_loader.clone();
+        self.load_presence(user_id, async move {
+            let contacts = loader
+                .find_contacts(user_id)
+                .await
+                .inspect_err(|e| log::error!("{e}"))
+                .ok()?;
+            Some(PresenceUpdate::VisibilityChanged { contacts })
+        });
+    }
+
+    fn presence_visibility_changed(
+        &self,
+        user_id: i32,
+        contacts: PresenceContacts,
+    ) {
+        if !self.is_online(user_id) {
+            return;
+        }
+        let public_state = self.public_presence_state(user_id);
+        for contact_id in self.online_ids(&contacts.contact_ids) {
+            let state = if contacts.audience.contains(&contact_id) {
+                public_state
+            } else {
+                PresenceState::Offline
+            };
+            self.send_session_message(
+                contact_id,
+                WsResponse::UsersOnline {
+                    users: vec![UserOnlineStatus {
+                        user_id,
+                        state,
+                        last_seen_at: None,
+                    }],
+                },
+            );
+        }
+    }
+
+    /// Whether the user has a session on any node.
+    fn is_online(
+        &self,
+        user_id: i32,
+    ) -> bool {
+        self.has_local_session(user_id) || self.remote_sessions.contains_key(&user_id)
+    }
+
+    fn has_local_session(
+        &self,
+        user_id: i32,
+    ) -> bool {
+        self.registry.has_user(user_id)
+    }
+
+    /// The effective state of a user: the chosen one, turned into `Away` when all of
+    /// the user's sessions are idle.
+    fn presence_state(
+        &self,
+        user_id: i32,
+    ) -> PresenceState {
+        if !self.is_online(user_id) {
+            return PresenceState::Offline;
+        }
+        let remote_idle = self
+            .remote_sessions
+            .get(&user_id)
+            .into_iter()
+            .flat_map(|nodes| nodes.values().flatten())
+            .map(|session| session.idle);
+        let local_idle = self.registry.with_user_sessions(user_id, |sessions| {
+            sessions.iter().all(|(_, handle)| handle.idle)
+        });
+        let all_idle = local_idle && remote_idle.into_iter().all(|idle| idle);
+        match self.presence_states.get(&user_id) {
+            Some(PresenceState::Online) | None if all_idle => PresenceState::Away,
+            Some(state) => *state,
+            None => PresenceState::Online,
+        }
+    }
+
+    fn public_presence_state(
+        &self,
+        user_id: i32,
+    ) -> PresenceState {
+        self.presence_state(user_id).public()
+    }
+
+    /// Sends a user's state to the online contacts allowed to see it.
+    fn broadcast_presence_state(
+        &self,
+        user_id: i32,
+        state: PresenceState,
+        last_seen_at: Option<NaiveDateTime>,
+        contacts: &PresenceContacts,
+    ) {
+        let users = vec![UserOnlineStatus {
+            user_id,
+            state,
+            last_seen_at,
+        }];
+        for contact_id in self.online_ids(&contacts.audience) {
+            self.send_session_message(
+                contact_id,
+                WsResponse::UsersOnline {
+                    users: users.clone(),
+                },
+            );
+        }
+    }
+
+    /// Tells a user's own sessions about a change of the user's state, given the
+    /// state before the change. Returns the new public state when it changed too.
+    fn send_own_presence_state(
+        &self,
+        user_id: i32,
+        prev_state: PresenceState,
+    ) -> Option<PresenceState> {
+        let state = self.presence_state(user_id);
+        if state != prev_state {
+            self.send_session_message(
+                user_id,
+                WsResponse::UsersOnline {
+                    users: vec![UserOnlineStatus {
+                        user_id,
+                        state,
+                        last_seen_at: None,
+                    }],
+                },
+            );
+        }
+        (state.public() != prev_state.public()).then(|| state.public())
+    }
+
+    /// Tells a user's own sessions and their contacts about a change of the user's
+    /// state, given the state before the change.
+    fn send_presence_state(
+        &self,
+        user_id: i32,
+        prev_state: PresenceState,
+    ) {
+        let Some(state) = self.send_own_presence_state(user_id, prev_state) else {
+            return;
+        };
+        let loader = self.presence_loader.clone();
+        self.load_presence(user_id, async move {
+            let contacts = loader
+                .find_contacts(user_id)
+                .await
+                .inspect_err(|e| log::error!("{e}"))
+                .ok()?;
+            Some(PresenceUpdate::Changed {
+                state,
+                last_seen_at: None,
+                contacts,
             })
-            .collect::<Vec<_>>();
+        });
+    }
+
+    /// Re-sends the presence of two users to each other after a block between them
+    /// changed, so a new block hides them and a lifted one shows them again.
+    fn send_block_presence(
+        &self,
+        user_id: i32,
+        other_id: i32,
+    ) {
+        if !self.is_online(user_id) || !self.is_online(other_id) {
+            return;
+        }
+        let loader = self.presence_loader.clone();
+        self.load_presence(user_id, async move {
+            let blocked = loader
+                .contact_service
+                .is_blocked_between(user_id, other_id)
+                .await
+                .inspect_err(|e| log::error!("{e}"))
+                .ok()?;
+            if blocked {
+                return Some(PresenceUpdate::BlockChanged {
+                    other_id,
+                    visible: false,
+                    shown: false,
+                });
+            }
+            let contacts = loader
+                .find_visibility(user_id, HashSet::from([other_id]))
+                .await
+                .inspect_err(|e| log::error!("{e}"))
+                .ok()?;
+            Some(PresenceUpdate::BlockChanged {
+                other_id,
+                visible: contacts.visible.contains(&other_id),
+                shown: contacts.audience.contains(&other_id),
+            })
+        });
+    }
+
+    fn block_presence_changed(
+        &self,
+        user_id: i32,
+        other_id: i32,
+        visible: bool,
+        shown: bool,
+    ) {
+        let statuses = [(user_id, other_id, visible), (other_id, user_id, shown)];
+        for (receiver_id, status_id, shown) in statuses {
+            let state = if shown {
+                self.public_presence_state(status_id)
+            } else {
+                PresenceState::Offline
+            };
+            self.send_session_message(
+                receiver_id,
+                WsResponse::UsersOnline {
+                    users: vec![UserOnlineStatus {
+                        user_id: status_id,
+                        state,
+                        last_seen_at: None,
+                    }],
+                },
+            );
+        }
+    }
+
+    fn send_online_notification(
+        &self,
+        user_id: i32,
+        contacts: &PresenceContacts,
+    ) {
+        let state = self.presence_state(user_id);
         self.send_session_message(
             user_id,
             WsResponse::UsersOnline {
-                users: online_contacts.clone(),
+                users: self.online_statuses(user_id, contacts),
             },
         );
 
+        if state.public() == PresenceState::Offline {
+            return;
+        }
+
         // let the user's contacts know that he is online
-        let user_online = vec![UserOnlineStatus {
+        self.broadcast_presence_state(user_id, state.public(), None, contacts);
+    }
+
+    /// The contacts of a user that are online and visible to them, along with the
+    /// user's own state, so every session starts from the one chosen before.
+    fn online_statuses(
+        &self,
+        user_id: i32,
+        contacts: &PresenceContacts,
+    ) -> Vec<UserOnlineStatus> {
+        let mut statuses = self
+            .online_ids(&contacts.visible)
+            .map(|user_id| UserOnlineStatus {
+                user_id,
+                state: self.public_presence_state(user_id),
+                last_seen_at: None,
+            })
+            .filter(|status| status.state != PresenceState::Offline)
+            .collect::<Vec<_>>();
+        statuses.push(UserOnlineStatus {
             user_id,
-            online: true,
-        }];
-        online_contacts.iter().for_each(|status| {
-            self.send_session_message(
-                status.user_id,
-                WsResponse::UsersOnline {
-                    users: user_online.clone(),
-                },
-            )
+            state: self.presence_state(user_id),
+            last_seen_at: None,
         });
-        Some(())
+        statuses
     }
 
-    async fn session_up(
+    /// A session that negotiated a newer protocol was sent the connect-time events
+    /// in the legacy shape, or not at all, so it is sent them again.
+    fn capabilities_set(
+        &self,
+        session_id: SessionID,
+        capabilities: HashSet<Capability>,
+    ) {
+        let Some(user_id) = self.registry.user_id(&session_id) else {
+            return;
+        };
+        let sessions = capabilities.contains(&Capability::Sessions);
+        let presence = capabilities.contains(&Capability::Presence);
+        self.registry.set_capabilities(&session_id, capabilities);
+        if sessions {
+            self.send_sessions_changed(user_id);
+        }
+        if presence {
+            let loader = self.presence_loader.clone();
+            self.load_presence(user_id, async move {
+                let contacts = loader
+                    .find_contacts(user_id)
+                    .await
+                    .inspect_err(|e| log::error!("{e}"))
+                    .ok()?;
+                Some(PresenceUpdate::Negotiated {
+                    session_id,
+                    contacts,
+                })
+            });
+        }
+    }
+
+    /// Lists the live sessions of a user to each of them, so a user's devices know
+    /// about each other.
+    fn send_sessions_changed(
+        &self,
+        user_id: i32,
+    ) {
+        let local_sessions = self.registry.with_user_sessions(user_id, |sessions| {
+            sessions
+                .into_iter()
+                .map(|(session_id, handle)| {
+                    let info = SessionInfo {
+                        session_id: session_id.value(),
+                        user_agent: handle.user_agent.clone(),
+                        connected_at: handle.connected_at,
+                        current: false,
+                    };
+                    (session_id.clone(), info)
+                })
+                .collect::<Vec<_>>()
+        });
+        let remote_sessions = self
+            .remote_sessions
+            .get(&user_id)
+            .into_iter()
+            .flat_map(|nodes| nodes.values().flatten())
+            .map(|session| SessionInfo {
+                session_id: session.session_id,
+                user_agent: session.user_agent.clone(),
+                connected_at: session.connected_at,
+                current: false,
+            })
+            .collect::<Vec<_>>();
+        for (receiver_id, _) in local_sessions.iter() {
+            let mut sessions = local_sessions
+                .iter()
+                .map(|(session_id, info)| SessionInfo {
+                    current: session_id == receiver_id,
+                    ..info.clone()
+                })
+                .chain(remote_sessions.iter().cloned())
+                .collect::<Vec<_>>();
+            sessions.sort_by_key(|session| session.session_id);
+            self.registry
+                .send_to_session(receiver_id, &SessionsChanged { sessions });
+        }
+    }
+
+    /// Tells the other nodes which sessions this node holds for a user.
+    fn publish_sessions(
+        &self,
+        user_id: i32,
+        last_seen_at: Option<NaiveDateTime>,
+    ) {
+        let sessions = self.registry.with_user_sessions(user_id, |sessions| {
+            sessions
+                .into_iter()
+                .map(|(session_id, handle)| RemoteSession {
+                    session_id: session_id.value(),
+                    user_agent: handle.user_agent.clone(),
+                    connected_at: handle.connected_at,
+                    idle: handle.idle,
+                })
+                .collect()
+        });
+        let chosen_state = self
+            .presence_states
+            .get(&user_id)
+            .copied()
+            .unwrap_or(PresenceState::Online);
+        self.fanout_bus.publish(BusEvent::Sessions {
+            user_id,
+            sessions,
+            chosen_state,
+            last_seen_at,
+        });
+    }
+
+    /// Closes the oldest sessions of a user until another one fits within the limit.
+    fn close_excess_sessions(
+        &mut self,
+        user_id: i32,
+    ) {
+        let Some(max) = self.max_sessions_per_user else {
+            return;
+        };
+        let mut session_ids = self.registry.session_ids(user_id);
+        if session_ids.len() < max {
+            return;
+        }
+        session_ids.sort();
+        let excess = session_ids.len() + 1 - max;
+        for session_id in session_ids.into_iter().take(excess) {
+            self.send_session_error(
+                session_id.clone(),
+                "Closed because the session limit was reached".to_string(),
+            );
+            if let Some(sess) = self.registry.remove(&session_id) {
+                sess.sender.close();
+            }
+        }
+    }
+
+    fn session_up(
         &mut self,
         session_id: SessionID,
         user_id: i32,
+        user_agent: Option<String>,
         sess_tx: SessionTx,
-    ) -> Option<()> {
-        let prev = self.user_storage.insert(
+    ) {
+        let load_state = !self.presence_states.contains_key(&user_id);
+        if load_state {
+            // shown as offline until the state chosen before is loaded
+            self.presence_states
+                .insert(user_id, PresenceState::Invisible);
+        }
+        self.close_excess_sessions(user_id);
+        self.registry.insert(
             session_id,
             SessionHandle {
                 user_id,
-                sender: sess_tx.clone(),
+                sender: sess_tx,
+                user_agent,
+                connected_at: chrono::Utc::now().naive_utc(),
+                idle: false,
+                capabilities: None,
             },
         );
-        self.send_online_notification(user_id).await;
+        self.send_sessions_changed(user_id);
+        let loader = self.presence_loader.clone();
+        self.load_presence(user_id, async move {
+            let stored_state = match load_state {
+                true => Some(loader.find_presence_state(user_id).await),
+                false => None,
+            };
+            let contacts = loader
+                .find_contacts(user_id)
+                .await
+                .inspect_err(|e| log::error!("{e}"))
+                .unwrap_or_default();
+            Some(PresenceUpdate::Connected {
+                stored_state,
+                contacts,
+            })
+        });
+    }
 
-        if let Some(session) = prev {
-            let _ = session.sender.send(SessionMessage::CloseConnection);
+    fn session_connected(
+        &mut self,
+        user_id: i32,
+        stored_state: Option<PresenceState>,
+        contacts: PresenceContacts,
+    ) {
+        if !self.has_local_session(user_id) {
+            return;
         }
-        Some(())
+        if let Some(state) = stored_state {
+            self.presence_states.insert(user_id, state);
+        }
+        self.send_online_notification(user_id, &contacts);
+        self.publish_sessions(user_id, None);
     }
 
     fn send_session_message(
@@ -142,15 +655,7 @@ impl WsServer {
         user_id: i32,
         message: WsResponse,
     ) {
-        self.user_storage
-            .iter()
-            .filter(|(_, handle)| handle.user_id == user_id)
-            .for_each(|(_, handle)| {
-                handle
-                    .sender
-                    .send(SessionMessage::Message(message.to_string()))
-                    .unwrap()
-            });
+        self.registry.send(user_id, &message);
     }
 
     fn send_session_error(
@@ -158,455 +663,486 @@ impl WsServer {
         session_id: SessionID,
         message: String,
     ) -> Option<()> {
-        let session_handle = self.user_storage.get(&session_id)?;
-        session_handle
-            .sender
-            .send(SessionMessage::Message(
-                ErrorNotification { message }.to_string(),
-            ))
-            .unwrap();
-        Some(())
-    }
-
-    fn send_new_message_notification(
-        &self,
-        user_id: i32,
-        msg: DirectMessageModel,
-    ) {
-        let message = MessageNotification {
-            id: msg.id,
-            sender_uid: msg.sender_id,
-            receiver_uid: msg.receiver_id,
-            is_user: user_id == msg.sender_id,
-            content: msg.content,
-            sent_at: msg.sent_at,
-            receiver_read: msg.read,
-            attachments: msg
-                .attachments
-                .iter()
-                .map(|at| MessageNotificationAttachment {
-                    id: at.id,
-                    file_type: at.file_type.clone(),
-                })
-                .collect(),
-        };
-        self.send_session_message(user_id, message);
+        self.registry
+            .send_to_session(&session_id, &ErrorNotification { message })
     }
 
-    async fn handle_read_message(
-        &self,
+    fn session_message(
+        &mut self,
         session_id: SessionID,
-        sender_uid: i32,
-    ) -> Option<()> {
-        let session_handle = self.user_storage.get(&session_id)?;
-        let receiver_uid = session_handle.user_id;
-        let message = ReadDirectNotification {
-            sender_uid,
-            receiver_uid,
-        };
-        let res = self
-            .message_repository
-            .update_message_read(receiver_uid, sender_uid)
-            .await;
-        match res {
-            Ok(_) => {}
-            Err(e) => return Some(log::info!("{}", e)),
+        request: WsRequest,
+    ) {
+        match request {
+            WsRequest::SetPresence { state } => {
+                self.handle_set_presence(session_id, state);
+            }
+            WsRequest::SetIdle { idle } => {
+                self.handle_set_idle(session_id, idle);
+            }
+            WsRequest::Ping => {
+                self.registry.send_to_session(&session_id, &Pong);
+            }
+            request => {
+                let Some(user_id) = self.registry.user_id(&session_id) else {
+                    return;
+                };
+                let handler = self.handler.clone();
+                let executor = self.executor.clone();
+                self.lookup_executor.spawn(session_id.clone(), async move {
+                    let Some(key) = handler.conversation_key(user_id, &request).await else {
+                        return;
+                    };
+                    executor.spawn(key, async move {
+                        handler.handle(session_id, user_id, request).await;
+                    });
+                });
+            }
         };
-        self.send_session_message(sender_uid, message);
-        Some(())
     }
 
-    async fn handle_group_read_message(
+    fn send_without_session(
         &self,
-        session_id: SessionID,
-        group_id: i32,
-    ) -> Option<()> {
-        let SessionHandle { user_id, .. } = self.user_storage.get(&session_id)?;
-        let res = self
-            .group_repository
-            .read_all_message(*user_id, group_id)
-            .await;
-        match res {
-            Ok(succ) if succ => (),
-            Ok(_) => log::error!("failed to update group message read"),
-            Err(e) => log::error!("{e}"),
+        user_id: i32,
+        request: WsRequest,
+        reply: oneshot::Sender<Result<SentMessage, anyhow::Error>>,
+    ) {
+        let Some(key) = ConversationKey::of(user_id, &request) else {
+            let _ = reply.send(Err(anyhow!("Request cannot be sent without a session")));
+            return;
         };
-        Some(())
+        let handler = self.handler.clone();
+        self.executor.spawn(key, async move {
+            let _ = reply.send(handler.send(user_id, request).await);
+        });
     }
 
-    async fn handle_user_send_message(
-        &self,
+    fn handle_set_presence(
+        &mut self,
         session_id: SessionID,
-        receiver_uid: i32,
-        msg: String,
-        attachments: Vec<MessageAttachment>,
+        state: PresenceState,
     ) -> Option<()> {
-        let handle = self.user_storage.get(&session_id)?;
-        let sender_uid = handle.user_id;
-        let mut create_attachments = Vec::<CreateAttachmentModel>::new();
-        for at in attachments {
-            let bytes = match at.content_as_bytes() {
-                Ok(b) => b,
-                Err(e) => {
-                    log::error!("{e}");
-                    continue;
-                }
-            };
-            create_attachments.push(CreateAttachmentModel {
-                name: at.name.clone(),
-                attachment: bytes,
-            });
+        let user_id = self.registry.user_id(&session_id)?;
+        if state == PresenceState::Offline {
+            self.send_session_error(session_id, "Presence state cannot be offline".to_string());
+            return Some(());
         }
-        let res = self
-            .message_service
-            .create_direct_message(CreateDirectMessageModel {
-                receiver_id: receiver_uid,
-                sender_id: sender_uid,
-                content: msg,
-                attachment: create_attachments,
-            })
-            .await;
-        let msg = match res {
-            Ok(msg) => msg,
-            Err(e) => {
+        let loader = self.presence_loader.clone();
+        let registry = self.registry.clone();
+        self.load_presence(user_id, async move {
+            let res = loader
+                .presence_repository
+                .update_presence_state(user_id, state.to_string())
+                .await;
+            if let Err(e) = res {
                 log::error!("{e}");
-                self.send_session_error(session_id, e.to_string());
-                return Some(());
+                registry.send_to_session(&session_id, &ErrorNotification { message: e });
+                return None;
             }
-        };
-        self.send_new_message_notification(sender_uid, msg.clone());
-        self.send_new_message_notification(receiver_uid, msg);
+            let contacts = loader
+                .find_contacts(user_id)
+                .await
+                .inspect_err(|e| log::error!("{e}"))
+                .unwrap_or_default();
+            Some(PresenceUpdate::Chosen { state, contacts })
+        });
         Some(())
     }
 
-    async fn handle_send_group_message(
-        &self,
-        session_id: SessionID,
-        group_id: i32,
-        message: String,
-        attachments: Vec<MessageAttachment>,
-    ) -> Option<()> {
-        let SessionHandle {
-            user_id: sender_uid,
-            ..
-        } = self.user_storage.get(&session_id)?;
-        let result = self.group_repository.find_group_members(group_id).await;
-        let member_ids = match result {
-            Ok(ids) => ids,
-            Err(e) => return Some(log::error!("{e}")),
-        };
-        if !member_ids.contains(&sender_uid) {
-            log::error!("User with id '{sender_uid}' is not part of group with id '{group_id}'");
-            return Some(());
-        }
-        let mut create_attachments = Vec::<CreateAttachmentModel>::new();
-        for at in attachments {
-            let bytes = match at.content_as_bytes() {
-                Ok(b) => b,
-                Err(e) => {
-                    log::error!("{e}");
-                    continue;
-                }
-            };
-            create_attachments.push(CreateAttachmentModel {
-                name: at.name.clone(),
-                attachment: bytes,
-            });
+    fn presence_chosen(
+        &mut self,
+        user_id: i32,
+        state: PresenceState,
+        contacts: PresenceContacts,
+    ) {
+        if !self.is_online(user_id) {
+            return;
         }
-        let res = self
-            .message_service
-            .create_group_message(CreateGroupMessageModel {
-                group_id,
-                sender_id: *sender_uid,
-                content: message,
-                attachment: create_attachments,
-            })
-            .await;
-        let message = match res {
-            Ok(m) => WsResponse::from_group_message(m),
-            Err(e) => return Some(log::error!("{e}")),
-        };
-        for mid in member_ids.iter() {
-            self.send_session_message(*mid, message.clone());
+        let prev_state = self.presence_state(user_id);
+        self.presence_states.insert(user_id, state);
+        if let Some(state) = self.send_own_presence_state(user_id, prev_state) {
+            self.broadcast_presence_state(user_id, state, None, &contacts);
         }
-        Some(())
+        self.publish_sessions(user_id, None);
     }
 
-    async fn handle_delete_direct_message(
-        &self,
+    fn handle_set_idle(
+        &mut self,
         session_id: SessionID,
-        message_id: i32,
+        idle: bool,
     ) -> Option<()> {
-        let SessionHandle {
-            user_id: sender_id, ..
-        } = self.user_storage.get(&session_id)?;
-        let result = self.message_repository.find_message_by_id(message_id).await;
-        let message = match result {
-            Ok(Some(message)) => message,
-            Ok(None) => return Some(()),
-            Err(e) => return Some(log::info!("{e}")),
-        };
-        if message.sender_id != *sender_id {
-            return Some(());
-        }
-        let result = self.message_repository.delete_message(message_id).await;
-        let success = match result {
-            Ok(succ) => succ,
-            Err(e) => return Some(log::error!("{e}")),
-        };
-        if !success {
-            return Some(());
-        }
-        let sender_message = WsResponse::DeleteMessageNotification {
-            contact_id: message.receiver_id,
-            message_id,
-        };
-        let receiver_message = WsResponse::DeleteMessageNotification {
-            contact_id: message.sender_id,
-            message_id,
-        };
-        self.send_session_message(message.sender_id, sender_message);
-        self.send_session_message(message.receiver_id, receiver_message);
+        let user_id = self.registry.user_id(&session_id)?;
+        let prev_state = self.presence_state(user_id);
+        self.registry.set_idle(&session_id, idle)?;
+        self.send_presence_state(user_id, prev_state);
+        self.publish_sessions(user_id, None);
         Some(())
     }
 
-    async fn handle_delete_group_message(
-        &self,
-        session_id: SessionID,
-        message_id: i32,
-    ) -> Option<()> {
-        use WsResponse::*;
-        let session_handle = self.user_storage.get(&session_id)?;
-        let result = self.group_repository.find_message_by_id(message_id).await;
-        let message = match result {
-            Ok(Some(mess)) => mess,
-            Ok(None) => return Some(()),
-            Err(e) => return Some(log::info!("{}", e)),
-        };
-        if message.sender_id != session_handle.user_id {
-            return Some(());
-        }
-        let result = self
-            .group_repository
-            .set_message_to_delete(message_id)
-            .await;
-        let success = match result {
-            Ok(succ) => succ,
-            Err(e) => return Some(log::info!("{e}")),
-        };
-        if !success {
-            return Some(());
+    fn send_offline_notification(
+        &mut self,
+        user_id: i32,
+    ) {
+        if self.is_online(user_id) {
+            return;
         }
-        let result = self
-            .group_repository
-            .find_group_members(message.group_id)
-            .await;
-        let members = match result {
-            Ok(mems) => mems,
-            Err(e) => return Some(log::info!("{e}")),
-        };
-        for user_id in members {
-            self.send_session_message(
-                user_id,
-                DeleteGroupMessageNotification {
-                    group_id: message.group_id,
-                    message_id: message.id,
-                },
-            );
+
+        // contacts already see an invisible user as offline, and they don't learn when it left
+        if self.presence_states.get(&user_id) == Some(&PresenceState::Invisible) {
+            self.publish_sessions(user_id, None);
+            self.presence_states.remove(&user_id);
+            return;
         }
-        Some(())
+        let loader = self.presence_loader.clone();
+        self.load_presence(user_id, async move {
+            let last_seen_at = loader.update_last_seen(user_id).await;
+            let contacts = loader
+                .find_contacts(user_id)
+                .await
+                .inspect_err(|e| log::error!("{e}"))
+                .unwrap_or_default();
+            Some(PresenceUpdate::Disconnected {
+                last_seen_at,
+                contacts,
+            })
+        });
     }
 
-    async fn handle_edit_direct_message(
-        &self,
-        session_id: SessionID,
-        message_id: i32,
-        edited_content: String,
-    ) -> Option<()> {
-        let session_handle = self.user_storage.get(&session_id)?;
-        let result = self.message_repository.find_message_by_id(message_id).await;
-        let message = match result {
-            Ok(Some(mess)) => mess,
-            Ok(None) => return Some(()),
-            Err(e) => return Some(log::error!("{e}")),
-        };
-        if message.sender_id != session_handle.user_id {
-            return Some(());
+    fn session_disconnected(
+        &mut self,
+        user_id: i32,
+        last_seen_at: Option<NaiveDateTime>,
+        contacts: PresenceContacts,
+    ) {
+        self.publish_sessions(user_id, last_seen_at);
+        // the user came back in the meantime
+        if self.is_online(user_id) {
+            return;
         }
-        let result = self
-            .message_repository
-            .edit_message_by_id(message_id, edited_content.clone())
-            .await;
-        let message = match result {
-            Ok(mess) => mess,
-            Err(e) => return Some(log::error!("{e}")),
-        };
+        self.presence_states.remove(&user_id);
 
-        self.send_session_message(
-            message.sender_id,
-            UpdateDirectMessageNotification {
-                contact_id: message.receiver_id,
-                message_id,
-                content: edited_content.clone(),
-            },
-        );
+        // if not still online, then send message to all online contacts that you are offline
+        self.broadcast_presence_state(user_id, PresenceState::Offline, last_seen_at, &contacts);
+    }
 
-        self.send_session_message(
-            message.receiver_id,
-            UpdateDirectMessageNotification {
-                contact_id: message.sender_id,
-                message_id,
-                content: edited_content.clone(),
-            },
-        );
-        Some(())
+    /// Follows up on a user losing sessions of this node, given their state before.
+    fn sessions_closed(
+        &mut self,
+        user_id: i32,
+        prev_state: PresenceState,
+    ) {
+        if self.is_online(user_id) {
+            // the remaining sessions may all be idle
+            self.send_presence_state(user_id, prev_state);
+            self.send_sessions_changed(user_id);
+            self.publish_sessions(user_id, None);
+        } else {
+            self.send_offline_notification(user_id);
+        }
     }
 
-    async fn handle_edit_group_message(
-        &self,
+    fn session_down(
+        &mut self,
         session_id: SessionID,
-        message_id: i32,
-        edited_content: String,
     ) -> Option<()> {
-        let session_handle = self.user_storage.get(&session_id)?;
-        let result = self.group_repository.find_message_by_id(message_id).await;
-        let message = match result {
-            Ok(Some(mess)) => mess,
-            Ok(None) => return Some(()),
-            Err(e) => return Some(log::error!("{e}")),
-        };
-        if message.sender_id != session_handle.user_id {
-            return Some(());
+        let user_id = self.registry.user_id(&session_id)?;
+        let prev_state = self.presence_state(user_id);
+        let sess = self.registry.remove(&session_id)?;
+
+        // need to check all sessions of a particular user is down, before notifying to online contacts that the user is down
+        sess.sender.close();
+        self.sessions_closed(user_id, prev_state);
+        Some(())
+    }
+
+    fn disconnect_user(
+        &mut self,
+        user_id: i32,
+    ) {
+        let session_ids = self.registry.session_ids(user_id);
+        if session_ids.is_empty() {
+            return;
         }
-        let result = self
-            .group_repository
-            .edit_message_by_id(message_id, edited_content.clone())
-            .await;
-        let _ = match result {
-            Ok(mess) => mess,
-            Err(e) => return Some(log::error!("{e}")),
-        };
-        let result = self
-            .group_repository
-            .find_group_members(message.group_id)
-            .await;
-        let members = match result {
-            Ok(mem) => mem,
-            Err(e) => return Some(log::error!("{e}")),
-        };
-        for member_id in members {
-            self.send_session_message(
-                member_id,
-                UpdateGroupMessageNotification {
-                    group_id: message.group_id,
-                    message_id,
-                    content: edited_content.clone(),
-                },
-            );
+        let prev_state = self.presence_state(user_id);
+        for session_id in session_ids {
+            if let Some(sess) = self.registry.remove(&session_id) {
+                sess.sender
+                    .close_with_code(SESSION_REVOKED_CLOSE_CODE, "Session revoked");
+            }
         }
-        Some(())
+        self.sessions_closed(user_id, prev_state);
     }
 
-    async fn session_message(
+    /// Takes in the sessions another node holds for a user and passes the resulting
+    /// presence change on to the sessions of this node.
+    fn remote_sessions_changed(
         &mut self,
-        session_id: SessionID,
-        msg: String,
+        node_id: u32,
+        user_id: i32,
+        sessions: Vec<RemoteSession>,
+        chosen_state: PresenceState,
+        last_seen_at: Option<NaiveDateTime>,
     ) {
-        let message = match WsRequest::from_str(&msg) {
-            Ok(msg) => msg,
-            Err(e) => {
-                log::error!("parsing error: {e}");
-                return;
+        let prev_state = self.presence_state(user_id);
+        let nodes = self.remote_sessions.entry(user_id).or_default();
+        if sessions.is_empty() {
+            nodes.remove(&node_id);
+        } else {
+            nodes.insert(node_id, sessions);
+        }
+        if nodes.is_empty() {
+            self.remote_sessions.remove(&user_id);
+        }
+
+        if self.is_online(user_id) {
+            self.presence_states.insert(user_id, chosen_state);
+            self.send_presence_state(user_id, prev_state);
+            self.send_sessions_changed(user_id);
+            return;
+        }
+        self.presence_states.remove(&user_id);
+        if prev_state.public() == PresenceState::Offline {
+            return;
+        }
+        let loader = self.presence_loader.clone();
+        self.load_presence(user_id, async move {
+            // when the last sessions closed on several nodes at once, none of them knew
+            // it was the last one
+            let last_seen_at = match last_seen_at {
+                Some(last_seen_at) => Some(last_seen_at),
+                None => loader.update_last_seen(user_id).await,
+            };
+            let contacts = loader
+                .find_contacts(user_id)
+                .await
+                .inspect_err(|e| log::error!("{e}"))
+                .ok()?;
+            Some(PresenceUpdate::Changed {
+                state: PresenceState::Offline,
+                last_seen_at,
+                contacts,
+            })
+        });
+    }
+
+    /// Applies a presence update of a user once what it needs is loaded.
+    fn presence_loaded(
+        &mut self,
+        user_id: i32,
+        update: PresenceUpdate,
+    ) {
+        match update {
+            PresenceUpdate::Connected {
+                stored_state,
+                contacts,
+            } => self.session_connected(user_id, stored_state, contacts),
+            PresenceUpdate::Chosen { state, contacts } => {
+                self.presence_chosen(user_id, state, contacts)
             }
-        };
-        match message {
-            WsRequest::SendMessage {
-                receiver_uid,
-                message,
-                attachments,
-            } => {
-                self.handle_user_send_message(session_id, receiver_uid, message, attachments)
-                    .await;
+            PresenceUpdate::Changed {
+                state,
+                last_seen_at,
+                contacts,
+            } => self.broadcast_presence_state(user_id, state, last_seen_at, &contacts),
+            PresenceUpdate::Disconnected {
+                last_seen_at,
+                contacts,
+            } => self.session_disconnected(user_id, last_seen_at, contacts),
+            PresenceUpdate::VisibilityChanged { contacts } => {
+                self.presence_visibility_changed(user_id, contacts)
             }
-            WsRequest::SendGroupMessage {
-                group_id,
-                message,
-                attachments,
+            PresenceUpdate::Negotiated {
+                session_id,
+                contacts,
             } => {
-                self.handle_send_group_message(session_id, group_id, message, attachments)
-                    .await;
+                let users = self.online_statuses(user_id, &contacts);
+                self.registry
+                    .send_to_session(&session_id, &WsResponse::UsersOnline { users });
+            }
+            PresenceUpdate::BlockChanged {
+                other_id,
+                visible,
+                shown,
+            } => self.block_presence_changed(user_id, other_id, visible, shown),
+        }
+    }
+
+    /// Recovers from events lost while the bus was down: the other nodes learn this
+    /// node's sessions again and the clients reload their state.
+    fn resync(&self) {
+        for user_id in self.registry.user_ids() {
+            self.publish_sessions(user_id, None);
+        }
+        self.registry.send_to_all(&Resync);
+    }
+
+    /// Checks off the server loop which of the nodes heard from have stopped, and
+    /// hands those back as `NodesExpired`.
+    fn expire_nodes(&self) {
+        if self.node_seen.is_empty() {
+            return;
+        }
+        let timeout = HEARTBEAT_INTERVAL * 3;
+        let nodes = self
+            .node_seen
+            .iter()
+            .map(|(node_id, seen)| (*node_id, seen.elapsed() > timeout))
+            .collect::<Vec<_>>();
+        let fanout_bus = self.fanout_bus.clone();
+        let app_tx = self.app_tx.clone();
+        tokio::spawn(async move {
+            let node_ids = nodes.iter().map(|(node_id, _)| *node_id).collect();
+            // a bus that does not answer in time counts as not keeping track
+            let live_nodes =
+                tokio::time::timeout(HEARTBEAT_INTERVAL, fanout_bus.live_nodes(node_ids))
+                    .await
+                    .ok()
+                    .flatten();
+            let expired = nodes
+                .into_iter()
+                .filter(|(node_id, timed_out)| match &live_nodes {
+                    Some(live_nodes) => !live_nodes.contains(node_id),
+                    None => *timed_out,
+                })
+                .map(|(node_id, _)| node_id)
+                .collect::<Vec<_>>();
+            if !expired.is_empty() {
+                let _ = app_tx.send(AppMessage::NodesExpired(expired)).await;
             }
-            WsRequest::ReadDirectMessage { receiver_uid } => {
-                self.handle_read_message(session_id, receiver_uid).await;
+        });
+    }
+
+    /// Drops the sessions of nodes that stopped sending heartbeats.
+    fn nodes_expired(
+        &mut self,
+        node_ids: Vec<u32>,
+    ) {
+        for node_id in node_ids {
+            if self.node_seen.remove(&node_id).is_none() {
+                continue;
             }
-            WsRequest::ReadGroupMessage { group_id } => {
-                self.handle_group_read_message(session_id, group_id).await;
+            log::info!("node {node_id} expired");
+            let user_ids = self
+                .remote_sessions
+                .iter()
+                .filter(|(_, nodes)| nodes.contains_key(&node_id))
+                .map(|(user_id, _)| *user_id)
+                .collect::<Vec<_>>();
+            for user_id in user_ids {
+                let chosen_state = self
+                    .presence_states
+                    .get(&user_id)
+                    .copied()
+                    .unwrap_or(PresenceState::Online);
+                self.remote_sessions_changed(node_id, user_id, vec![], chosen_state, None);
             }
-            WsRequest::DeleteDirectMessage { message_id } => {
-                self.handle_delete_direct_message(session_id, message_id)
-                    .await;
+        }
+    }
+
+    /// Carries out an event on the sessions of this node.
+    fn handle_bus_event(
+        &mut self,
+        event: BusEvent,
+    ) {
+        match event {
+            BusEvent::Notify { user_ids, message } => {
+                self.registry.send_to_users(&user_ids, &message);
             }
-            WsRequest::DeleteGroupMessage { message_id } => {
-                self.handle_delete_group_message(session_id, message_id)
-                    .await;
+            BusEvent::NotifyContacts { user_id, message } => {
+                self.notify_contacts(user_id, message);
             }
-            WsRequest::EditDirectMessage {
-                message_id,
-                edited_content,
-            } => {
-                self.handle_edit_direct_message(session_id, message_id, edited_content)
-                    .await;
+            BusEvent::DisconnectUser { user_id } => {
+                self.disconnect_user(user_id);
             }
-            WsRequest::EditGroupMessage {
-                message_id,
-                edited_content,
-            } => {
-                self.handle_edit_group_message(session_id, message_id, edited_content)
-                    .await;
+            BusEvent::PresenceChanged { user_id } => {
+                self.send_presence_changed(user_id);
             }
-        };
+            BusEvent::BlockChanged { user_id, other_id } => {
+                self.send_block_presence(user_id, other_id);
+            }
+            // only sent between nodes
+            BusEvent::Sessions { .. } | BusEvent::Hello | BusEvent::Heartbeat => {}
+        }
     }
 
-    async fn send_offline_notification(
-        &self,
-        user_id: i32,
-    ) -> Option<()> {
-        let still_online = self
-            .user_storage
-            .iter()
-            .any(|(_, handle)| handle.user_id == user_id);
-
-        if still_online {
-            return Some(());
-        }
+    /// Handles an event here and on every other node.
+    fn fan_out(
+        &mut self,
+        event: BusEvent,
+    ) {
+        self.fanout_bus.publish(event.clone());
+        self.handle_bus_event(event);
+    }
 
-        // if not still online, then send message to all online contacts that you are offline
-        let online_contacts = self.get_online_contact_ids(user_id).await.ok()?;
-        let user_online = vec![UserOnlineStatus {
-            user_id,
-            online: false,
-        }];
-        online_contacts.into_iter().for_each(|user_id| {
-            self.send_session_message(
+    fn handle_remote_event(
+        &mut self,
+        node_id: u32,
+        event: BusEvent,
+    ) {
+        self.node_seen.insert(node_id, Instant::now());
+        match event {
+            BusEvent::Sessions {
                 user_id,
-                WsResponse::UsersOnline {
-                    users: user_online.clone(),
-                },
-            )
-        });
-        Some(())
+                sessions,
+                chosen_state,
+                last_seen_at,
+            } => {
+                self.remote_sessions_changed(
+                    node_id,
+                    user_id,
+                    sessions,
+                    chosen_state,
+                    last_seen_at,
+                );
+            }
+            BusEvent::Hello => {
+                for user_id in self.registry.user_ids() {
+                    self.publish_sessions(user_id, None);
+                }
+            }
+            BusEvent::Heartbeat => {}
+            event => self.handle_bus_event(event),
+        }
     }
 
-    async fn session_down(
-        &mut self,
-        session_id: SessionID,
-    ) -> Option<()> {
-        let sess = self.user_storage.remove(&session_id)?;
+    /// Pushes a notification to the users who are offline on every node. Sessions
+    /// of invisible users count as online, they get their messages live.
+    fn push_offline(
+        &self,
+        user_ids: Vec<i32>,
+        notification: PushNotification,
+    ) {
+        let offline = user_ids
+            .into_iter()
+            .filter(|user_id| !self.is_online(*user_id))
+            .collect();
+        self.push_service.dispatch(offline, notification);
+    }
 
-        // need to check all sessions of a particular user is down, before notifying to online contacts that the user is down
-        let _ = sess.sender.send(SessionMessage::CloseConnection);
-        self.send_offline_notification(sess.user_id).await;
-        Some(())
+    /// Counts the sessions of every node.
+    fn online_stats(&self) -> OnlineStats {
+        let mut online_users = self.registry.user_ids();
+        online_users.extend(self.remote_sessions.keys());
+        let remote_sessions = self
+            .remote_sessions
+            .values()
+            .flat_map(|nodes| nodes.values())
+            .map(|sessions| sessions.len())
+            .sum::<usize>();
+        let queue_depths = self.registry.with_sessions(|sessions| {
+            sessions
+                .values()
+                .map(|handle| handle.sender.depth())
+                .collect::<Vec<_>>()
+        });
+        OnlineStats {
+            online_users: online_users.len(),
+            online_sessions: queue_depths.len() + remote_sessions,
+            queued_messages: queue_depths.iter().sum(),
+            max_queue_depth: queue_depths.into_iter().max().unwrap_or(0),
+            dropped_messages: self.registry.dropped_messages(),
+            slow_consumer_disconnects: self.registry.slow_consumer_disconnects(),
+            active_conversations: self.executor.active(),
+        }
     }
 
     pub async fn run(mut self) -> std::io::Result<()> {
@@ -615,16 +1151,65 @@ impl WsServer {
                 AppMessage::Connect {
                     session_id,
                     user_id,
+                    user_agent,
                     sess_tx,
                 } => {
-                    self.session_up(session_id, user_id, sess_tx).await;
+                    self.session_up(session_id, user_id, user_agent, sess_tx);
                 }
                 AppMessage::Message {
                     session_id,
-                    message,
-                } => self.session_message(session_id, message).await,
+                    request,
+                } => self.session_message(session_id, request),
                 AppMessage::Disconnect { session_id } => {
-                    self.session_down(session_id).await;
+                    self.session_down(session_id);
+                }
+                AppMessage::Send {
+                    user_id,
+                    request,
+                    reply,
+                } => self.send_without_session(user_id, request, reply),
+                AppMessage::SetCapabilities {
+                    session_id,
+                    capabilities,
+                } => self.capabilities_set(session_id, capabilities),
+                AppMessage::DisconnectUser { user_id } => {
+                    self.fan_out(BusEvent::DisconnectUser { user_id });
+                }
+                AppMessage::Notify { user_ids, message } => {
+                    self.fan_out(BusEvent::Notify { user_ids, message });
+                }
+                AppMessage::NotifyContacts { user_id, message } => {
+                    self.fan_out(BusEvent::NotifyContacts { user_id, message });
+                }
+                AppMessage::Push {
+                    user_ids,
+                    notification,
+                } => {
+                    self.push_offline(user_ids, notification);
+                }
+                AppMessage::PresenceChanged { user_id } => {
+                    self.fan_out(BusEvent::PresenceChanged { user_id });
+                }
+                AppMessage::BlockChanged { user_id, other_id } => {
+                    self.fan_out(BusEvent::BlockChanged { user_id, other_id });
+                }
+                AppMessage::OnlineStats { reply } => {
+                    let _ = reply.send(self.online_stats());
+                }
+                AppMessage::Bus { node_id, event } => {
+                    self.handle_remote_event(node_id, event);
+                }
+                AppMessage::Presence { user_id, update } => {
+                    self.presence_loaded(user_id, update);
+                }
+                AppMessage::ExpireNodes => {
+                    self.expire_nodes();
+                }
+                AppMessage::NodesExpired(node_ids) => {
+                    self.nodes_expired(node_ids);
+                }
+                AppMessage::BusReconnected => {
+                    self.resync();
                 }
             }
         }
@@ -1,185 +1,589 @@
+use std::collections::HashSet;
+use std::str::FromStr;
 use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
 
-use crate::middleware::verify_token;
+use crate::service::AuthService;
+use crate::service::JwtService;
 use crate::websocket::message::AppMessage;
 use crate::websocket::message::AppTx;
 use crate::websocket::message::SessionMessage;
+use crate::websocket::message::SessionQueueConfig;
+use anyhow::anyhow;
+use axum::extract::ws::CloseFrame;
 use axum::extract::ws::Message;
 use axum::extract::ws::WebSocket;
 use axum::Error;
-use futures_util::stream::SplitSink;
+use chrono::NaiveDateTime;
 use futures_util::FutureExt;
+use futures_util::Sink;
 use futures_util::SinkExt;
+use futures_util::Stream;
 use futures_util::StreamExt;
 use merge_streams::MergeStreams;
-use tokio::sync::mpsc;
-use tokio::task::JoinHandle;
+use serde::Deserialize;
+use tokio::sync::oneshot;
 
+use super::Capability;
+use super::SentMessage;
 use super::SessionID;
+use super::WsEncoding;
+use super::WsRequest;
+use super::WsResponse;
+use super::LEGACY_PROTOCOL_VERSION;
+use super::MIN_PROTOCOL_VERSION;
+use super::PROTOCOL_VERSION;
 
 #[derive(Clone)]
 pub struct SessionFactory {
     pub app_tx: AppTx,
+    pub jwt_service: JwtService,
+    pub auth_service: AuthService,
+    pub queue_config: SessionQueueConfig,
+    pub heartbeat_config: HeartbeatConfig,
 }
 
 impl SessionFactory {
+    /// `token_expires_at` is the `exp` claim of the token the user authenticated with.
     pub fn create_session(
         &self,
-        token: String,
         user_id: i32,
+        token_expires_at: u64,
+        user_agent: Option<String>,
+        encoding: WsEncoding,
     ) -> Session {
         Session {
             user_id,
+            encoding,
             session_id: SessionID::create(),
-            token,
+            user_agent,
+            token_expires_at,
             app_tx: self.app_tx.clone(),
+            jwt_service: self.jwt_service.clone(),
+            auth_service: self.auth_service.clone(),
+            queue_config: self.queue_config,
+            heartbeat_config: self.heartbeat_config,
+            protocol_version: LEGACY_PROTOCOL_VERSION,
+            hello_received: false,
+            capabilities: None,
         }
     }
+
+    /// Sends a `SEND_MESSAGE` or `SEND_GROUP_MESSAGE` request on behalf of a user
+    /// who has no session, delivering it like one sent over a websocket.
+    pub async fn send_message(
+        &self,
+        user_id: i32,
+        request: WsRequest,
+    ) -> Result<SentMessage, anyhow::Error> {
+        let (reply, sent) = oneshot::channel();
+        self.app_tx
+            .send(AppMessage::Send {
+                user_id,
+                request,
+                reply,
+            })
+            .await
+            .map_err(|_| anyhow!("Websocket server is not running"))?;
+        sent.await?
+    }
+}
+
+/// How often sessions ping their client, and how long a client may stay silent
+/// before its session is closed.
+#[derive(Clone, Copy)]
+pub struct HeartbeatConfig {
+    pub interval: Duration,
+    pub timeout: Duration,
+}
+
+impl HeartbeatConfig {
+    pub fn from_env() -> Self {
+        let interval = std::env::var("WS_PING_INTERVAL_SECS")
+            .map(|v| {
+                v.parse::<u64>()
+                    .expect("WS_PING_INTERVAL_SECS cannot be parsed into u64")
+            })
+            .unwrap_or(30)
+            .max(1);
+        let timeout = std::env::var("WS_PONG_TIMEOUT_SECS")
+            .map(|v| {
+                v.parse::<u64>()
+                    .expect("WS_PONG_TIMEOUT_SECS cannot be parsed into u64")
+            })
+            .unwrap_or(interval * 2);
+        Self {
+            interval: Duration::from_secs(interval),
+            timeout: Duration::from_secs(timeout),
+        }
+    }
+}
+
+/// A client that does not take a message within this time is disconnected.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sent when a client has not answered pings within the heartbeat timeout.
+pub const HEARTBEAT_TIMEOUT_CLOSE_CODE: u16 = 1001;
+
+/// How long before its token expires a session sends `TOKEN_EXPIRING`.
+const TOKEN_EXPIRY_WARNING: Duration = Duration::from_secs(60);
+
+/// Sent when the token of a session expires without being replaced.
+pub const TOKEN_EXPIRED_CLOSE_CODE: u16 = 4001;
+
+/// Sent when a client asks for a protocol version older than the server supports.
+pub const UNSUPPORTED_PROTOCOL_CLOSE_CODE: u16 = 4002;
+
+/// Sent when a websocket opened without credentials does not authenticate with a
+/// valid `AUTH` frame in time.
+pub const AUTHENTICATION_FAILED_CLOSE_CODE: u16 = 4000;
+
+/// Just the `type` of a request that could not be decoded.
+#[derive(Deserialize)]
+struct RequestType {
+    #[serde(rename = "type")]
+    request_type: String,
 }
 
 type AxumWsMessageRecv = Option<Result<Message, Error>>;
 
 enum SessionSource {
-    WebSocketText(String),
+    /// A text or binary frame.
+    WebSocketData(Message),
+    /// Any other frame, which still shows the client is alive.
+    WebSocketFrame,
     WebSocketClose,
     WebSocketError(Error),
     SessionMessage(SessionMessage),
-    TokenChecker(bool),
+    TokenTimer,
+    Heartbeat,
 }
 
 pub struct Session {
     user_id: i32,
+    encoding: WsEncoding,
     session_id: SessionID,
-    token: String,
+    user_agent: Option<String>,
+    /// The `exp` claim of the token the session was last authenticated with.
+    token_expires_at: u64,
     app_tx: AppTx,
+    jwt_service: JwtService,
+    auth_service: AuthService,
+    queue_config: SessionQueueConfig,
+    heartbeat_config: HeartbeatConfig,
+    /// Negotiated through `HELLO`.
+    protocol_version: u32,
+    hello_received: bool,
+    /// Declared through `HELLO`; `None` for legacy clients, which only receive the
+    /// events of the legacy protocol.
+    capabilities: Option<HashSet<Capability>>,
 }
 
 impl Session {
+    /// Returns false once the client can no longer be written to.
     pub async fn handle_session_message(
-        ws: &mut SplitSink<WebSocket, Message>,
-        msg: String,
-    ) {
-        let Err(e) = ws.send(Message::Text(msg)).await else {
-            return;
-        };
-        log::error!("{e}");
+        ws: &mut (impl Sink<Message, Error = Error> + Unpin),
+        encoding: WsEncoding,
+        legacy: bool,
+        msg: &WsResponse,
+    ) -> bool {
+        match encoding.encode_response(msg, legacy) {
+            Ok(msg) => Self::write(ws, msg).await,
+            Err(e) => {
+                // the message is skipped, the session goes on
+                log::error!("encoding error: {e}");
+                true
+            }
+        }
+    }
+
+    async fn write(
+        ws: &mut (impl Sink<Message, Error = Error> + Unpin),
+        msg: Message,
+    ) -> bool {
+        match tokio::time::timeout(WRITE_TIMEOUT, ws.send(msg)).await {
+            Ok(Ok(_)) => true,
+            Ok(Err(e)) => {
+                log::error!("{e}");
+                false
+            }
+            Err(_) => {
+                log::warn!("websocket write timed out");
+                false
+            }
+        }
     }
 
     pub async fn handle_websocket_message(
         &self,
-        msg: String,
+        request: WsRequest,
     ) {
-        self.app_tx
+        let res = self
+            .app_tx
             .send(AppMessage::Message {
                 session_id: self.session_id.clone(),
-                message: msg.to_string(),
+                request,
+            })
+            .await;
+        if res.is_err() {
+            log::error!("websocket server is gone");
+        }
+    }
+
+    async fn send_disconnect(&self) {
+        let res = self
+            .app_tx
+            .send(AppMessage::Disconnect {
+                session_id: self.session_id.clone(),
             })
-            .unwrap();
+            .await;
+        if res.is_err() {
+            log::error!("websocket server is gone");
+        }
+    }
+
+    /// Takes over a fresh token of the same user, returning when it expires. Like
+    /// every request, it fails once the account is suspended or needs a new password.
+    async fn reauthenticate(
+        &mut self,
+        token: &str,
+    ) -> Result<NaiveDateTime, String> {
+        let token = token.trim_start_matches("Bearer ");
+        let claims = self.jwt_service.verify(token).map_err(|e| e.to_string())?;
+        if claims.user_id().ok() != Some(self.user_id) {
+            return Err("Token belongs to another user".to_string());
+        }
+        self.auth_service
+            .ensure_active(self.user_id)
+            .await
+            .map_err(|e| e.to_string())?;
+        self.token_expires_at = claims.exp;
+        Ok(self.token_expiry())
+    }
+
+    /// Negotiates the protocol a client asked for in `HELLO`. Returns the reply, or
+    /// `None` when the client's version is not supported.
+    async fn hello(
+        &mut self,
+        protocol_version: u32,
+        capabilities: Vec<String>,
+    ) -> Option<WsResponse> {
+        if self.hello_received {
+            return Some(WsResponse::ErrorNotification {
+                message: "HELLO was already received".to_string(),
+            });
+        }
+        let protocol_version = protocol_version.min(PROTOCOL_VERSION);
+        if protocol_version < MIN_PROTOCOL_VERSION {
+            return None;
+        }
+        self.hello_received = true;
+        self.protocol_version = protocol_version;
+        if protocol_version > LEGACY_PROTOCOL_VERSION {
+            let capabilities = capabilities
+                .iter()
+                .filter_map(|c| Capability::from_str(c).ok())
+                .collect::<HashSet<_>>();
+            let res = self
+                .app_tx
+                .send(AppMessage::SetCapabilities {
+                    session_id: self.session_id.clone(),
+                    capabilities: capabilities.clone(),
+                })
+                .await;
+            if res.is_err() {
+                log::error!("websocket server is gone");
+            }
+            self.capabilities = Some(capabilities);
+        }
+        Some(WsResponse::Welcome {
+            protocol_version,
+            session_id: self.session_id.value(),
+            server_time: chrono::Utc::now().naive_utc(),
+            features: Capability::ALL
+                .into_iter()
+                .filter(|c| self.accepts(*c))
+                .collect(),
+        })
+    }
+
+    fn accepts(
+        &self,
+        capability: Capability,
+    ) -> bool {
+        self.capabilities
+            .as_ref()
+            .is_some_and(|capabilities| capabilities.contains(&capability))
+    }
+
+    fn token_expiry(&self) -> NaiveDateTime {
+        NaiveDateTime::from_timestamp_opt(self.token_expires_at as i64, 0)
+            .unwrap_or(NaiveDateTime::MIN)
+    }
+
+    /// When the token timer fires next: before expiry to warn the client, then at
+    /// expiry.
+    fn token_deadline(
+        &self,
+        warned: bool,
+    ) -> tokio::time::Instant {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let expires = tokio::time::Instant::now()
+            + Duration::from_secs(self.token_expires_at.saturating_sub(now));
+        if warned {
+            expires
+        } else {
+            expires.checked_sub(TOKEN_EXPIRY_WARNING).unwrap_or(expires)
+        }
+    }
+
+    pub fn session_id(&self) -> u64 {
+        self.session_id.value()
     }
 
     pub async fn run(
         self,
         ws: WebSocket,
     ) {
-        let (mut ws_tx, mut ws_rx) = ws.split();
-        let (session_tx, mut session_rx) = SessionMessage::channel();
-        let (token_checker, mut checker_rx) = self.spawn_token_checker();
-        self.app_tx
+        let (ws_tx, ws_rx) = ws.split();
+        self.run_with(ws_tx, ws_rx).await
+    }
+
+    /// Runs the session over any transport that carries websocket frames.
+    pub async fn run_with(
+        mut self,
+        mut ws_tx: impl Sink<Message, Error = Error> + Unpin,
+        mut ws_rx: impl Stream<Item = Result<Message, Error>> + Unpin,
+    ) {
+        let (session_tx, mut session_rx) = SessionMessage::channel(self.queue_config);
+        let mut token_warned = false;
+        let mut token_timer = Box::pin(tokio::time::sleep_until(self.token_deadline(false)));
+        let mut heartbeat = tokio::time::interval_at(
+            tokio::time::Instant::now() + self.heartbeat_config.interval,
+            self.heartbeat_config.interval,
+        );
+        heartbeat.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut last_heard = Instant::now();
+        let res = self
+            .app_tx
             .send(AppMessage::Connect {
                 user_id: self.user_id,
                 session_id: self.session_id.clone(),
+                user_agent: self.user_agent.clone(),
                 sess_tx: session_tx,
             })
-            .expect("This should be okay");
+            .await;
+        if res.is_err() {
+            log::error!("websocket server is gone");
+            let _ = ws_tx.send(Message::Close(None)).await;
+            return;
+        }
         loop {
-            let ws_source = ws_rx
-                .next()
-                .into_stream()
-                .filter_map(|s| async move { s })
-                .filter_map(|r| async move {
-                    match r {
-                        Ok(Message::Text(text)) => Some(SessionSource::WebSocketText(text)),
-                        Ok(Message::Close(_)) => Some(SessionSource::WebSocketClose),
-                        Ok(_) => None,
-                        Err(e) => Some(SessionSource::WebSocketError(e)),
-                    }
-                });
+            let ws_source = ws_rx.next().into_stream().map(|r| match r {
+                Some(Ok(frame @ (Message::Text(_) | Message::Binary(_)))) => {
+                    SessionSource::WebSocketData(frame)
+                }
+                Some(Ok(Message::Close(_))) | None => SessionSource::WebSocketClose,
+                Some(Ok(_)) => SessionSource::WebSocketFrame,
+                Some(Err(e)) => SessionSource::WebSocketError(e),
+            });
             let session_message_source = session_rx
                 .recv()
                 .into_stream()
                 .filter_map(|s| async move { s })
                 .map(SessionSource::SessionMessage);
-            let token_checker_source = checker_rx
-                .recv()
+            let token_source = (&mut token_timer)
                 .into_stream()
-                .filter_map(|s| async move { s })
-                .map(SessionSource::TokenChecker);
-            let mut source = (ws_source, session_message_source, token_checker_source).merge();
+                .map(|_| SessionSource::TokenTimer);
+            let heartbeat_source = heartbeat
+                .tick()
+                .into_stream()
+                .map(|_| SessionSource::Heartbeat);
+            let mut source = (
+                ws_source,
+                session_message_source,
+                token_source,
+                heartbeat_source,
+            )
+                .merge();
 
             let Some(msg) = source.next().await else {
                 continue;
             };
 
             match msg {
-                SessionSource::SessionMessage(SessionMessage::Message(text)) => {
-                    Self::handle_session_message(&mut ws_tx, text).await;
+                SessionSource::SessionMessage(SessionMessage::Message(msg))
+                | SessionSource::SessionMessage(SessionMessage::Event(msg)) => {
+                    if !Self::handle_session_message(
+                        &mut ws_tx,
+                        self.encoding,
+                        self.capabilities.is_none(),
+                        &msg,
+                    )
+                    .await
+                    {
+                        self.send_disconnect().await;
+                        break;
+                    }
+                }
+                SessionSource::WebSocketData(frame) => {
+                    last_heard = Instant::now();
+                    let request = match WsEncoding::decode::<WsRequest>(&frame) {
+                        Ok(request) => request,
+                        Err(e) if self.protocol_version > LEGACY_PROTOCOL_VERSION => {
+                            let request_type = WsEncoding::decode::<RequestType>(&frame)
+                                .ok()
+                                .map(|r| r.request_type);
+                            let reply = WsResponse::InvalidRequest {
+                                request_type,
+                                message: e,
+                            };
+                            if !Self::handle_session_message(
+                                &mut ws_tx,
+                                self.encoding,
+                                self.capabilities.is_none(),
+                                &reply,
+                            )
+                            .await
+                            {
+                                self.send_disconnect().await;
+                                break;
+                            }
+                            continue;
+                        }
+                        Err(e) => {
+                            log::error!("parsing error: {e}");
+                            continue;
+                        }
+                    };
+                    let reply = match request {
+                        WsRequest::Reauthenticate { token } => {
+                            match self.reauthenticate(&token).await {
+                                Ok(expires_at) => {
+                                    token_warned = false;
+                                    token_timer.as_mut().reset(self.token_deadline(false));
+                                    WsResponse::Reauthenticated { expires_at }
+                                }
+                                Err(message) => WsResponse::ErrorNotification { message },
+                            }
+                        }
+                        WsRequest::Hello {
+                            protocol_version,
+                            capabilities,
+                        } => {
+                            let Some(reply) = self.hello(protocol_version, capabilities).await
+                            else {
+                                self.send_disconnect().await;
+                                let frame = CloseFrame {
+                                    code: UNSUPPORTED_PROTOCOL_CLOSE_CODE,
+                                    reason: "Unsupported protocol version".into(),
+                                };
+                                let _ = Self::write(&mut ws_tx, Message::Close(Some(frame))).await;
+                                break;
+                            };
+                            reply
+                        }
+                        request => {
+                            self.handle_websocket_message(request).await;
+                            continue;
+                        }
+                    };
+                    if !Self::handle_session_message(
+                        &mut ws_tx,
+                        self.encoding,
+                        self.capabilities.is_none(),
+                        &reply,
+                    )
+                    .await
+                    {
+                        self.send_disconnect().await;
+                        break;
+                    }
                 }
-                SessionSource::WebSocketText(msg) => {
-                    self.handle_websocket_message(msg).await;
+                // pongs, and pings axum answers on its own
+                SessionSource::WebSocketFrame => {
+                    last_heard = Instant::now();
+                }
+                SessionSource::Heartbeat => {
+                    if last_heard.elapsed() >= self.heartbeat_config.timeout {
+                        log::info!("closing an unresponsive session of user {}", self.user_id);
+                        self.send_disconnect().await;
+                        let frame = CloseFrame {
+                            code: HEARTBEAT_TIMEOUT_CLOSE_CODE,
+                            reason: "Heartbeat timeout".into(),
+                        };
+                        let _ = Self::write(&mut ws_tx, Message::Close(Some(frame))).await;
+                        break;
+                    }
+                    if !Self::write(&mut ws_tx, Message::Ping(Vec::new())).await {
+                        self.send_disconnect().await;
+                        break;
+                    }
                 }
 
                 // Errors
                 SessionSource::WebSocketError(e) => {
                     log::info!("{e}");
+                    self.send_disconnect().await;
+                    break;
                 }
 
                 // close connections
-
-                // from token checker
-                SessionSource::TokenChecker(_) => {
-                    self.app_tx
-                        .send(AppMessage::Disconnect {
-                            session_id: self.session_id,
-                        })
-                        .unwrap();
-                    let _ = ws_tx.send(Message::Close(None)).await;
+                SessionSource::TokenTimer if !token_warned => {
+                    token_warned = true;
+                    token_timer.as_mut().reset(self.token_deadline(true));
+                    if !self.accepts(Capability::TokenExpiry) {
+                        continue;
+                    }
+                    let warning = WsResponse::TokenExpiring {
+                        expires_at: self.token_expiry(),
+                    };
+                    if !Self::handle_session_message(
+                        &mut ws_tx,
+                        self.encoding,
+                        self.capabilities.is_none(),
+                        &warning,
+                    )
+                    .await
+                    {
+                        self.send_disconnect().await;
+                        break;
+                    }
+                }
+                SessionSource::TokenTimer => {
+                    self.send_disconnect().await;
+                    let frame = CloseFrame {
+                        code: TOKEN_EXPIRED_CLOSE_CODE,
+                        reason: "Token expired".into(),
+                    };
+                    let _ = Self::write(&mut ws_tx, Message::Close(Some(frame))).await;
                     break;
                 }
                 // from server
                 SessionSource::SessionMessage(SessionMessage::CloseConnection) => {
-                    token_checker.abort();
                     let _ = ws_tx.send(Message::Close(None)).await;
                     break;
                 }
+                SessionSource::SessionMessage(SessionMessage::CloseWithCode(code, reason)) => {
+                    self.send_disconnect().await;
+                    let frame = CloseFrame {
+                        code,
+                        reason: reason.into(),
+                    };
+                    let _ = ws_tx.send(Message::Close(Some(frame))).await;
+                    break;
+                }
                 // from client
                 SessionSource::WebSocketClose => {
-                    token_checker.abort();
-                    let _ = self
-                        .app_tx
-                        .send(AppMessage::Disconnect {
-                            session_id: self.session_id,
-                        })
-                        .unwrap();
+                    self.send_disconnect().await;
                     break;
                 }
             };
         }
     }
-
-    pub fn spawn_token_checker(&self) -> (JoinHandle<()>, mpsc::UnboundedReceiver<bool>) {
-        let (ch_tx, ch_rx) = mpsc::unbounded_channel::<bool>();
-        let token = self.token.clone();
-        let handle = tokio::spawn(async move {
-            loop {
-                tokio::time::sleep(Duration::from_secs(1)).await;
-                let valid = verify_token(token.clone());
-                if let Err(_) = valid {
-                    ch_tx.send(true).unwrap();
-                    break;
-                }
-            }
-            ()
-        });
-        (handle, ch_rx)
-    }
 }